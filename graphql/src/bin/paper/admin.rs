@@ -0,0 +1,350 @@
+//! Subcommands for operators, they go through the services of the `Module`
+//! without a viewer.
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use paper::{
+    admin::{AdminPaperFilter, AdminService},
+    audit::{AuditEventInput, AuditEventKind, AuditService},
+    paper::{PaperCursor, PaperFilter, PaperOrderField},
+    user::{UserCursor, UserFilter, UserIdentifier, UserService},
+    OrderBy, OrderDirection, Pagination, PaginationList,
+};
+use paper_graphql::{Config, Module};
+use paper_impl::event::EventBus;
+use serde::Serialize;
+use shaku::HasProvider;
+
+type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+pub fn subcommands() -> Vec<App<'static, 'static>> {
+    let id =
+        |name: &'static str, help: &'static str| Arg::with_name(name).help(help).required(true);
+    let page = |app: App<'static, 'static>| {
+        app.arg(
+            Arg::with_name("first")
+                .long("first")
+                .help("number of items to list")
+                .takes_value(true)
+                .default_value("20"),
+        )
+        .arg(
+            Arg::with_name("after")
+                .long("after")
+                .help("list the items after this id")
+                .takes_value(true),
+        )
+    };
+
+    vec![
+        SubCommand::with_name("user")
+            .about("inspect and repair users")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(page(
+                SubCommand::with_name("list")
+                    .about("list users ordered by id")
+                    .arg(
+                        Arg::with_name("search")
+                            .long("search")
+                            .help("part of the name, or the id")
+                            .takes_value(true),
+                    )
+                    .arg(
+                        Arg::with_name("disabled")
+                            .long("disabled")
+                            .help("only disabled users"),
+                    ),
+            ))
+            .subcommand(
+                SubCommand::with_name("show")
+                    .about("show a user")
+                    .arg(id("user", "id or name of the user")),
+            )
+            .subcommand(
+                SubCommand::with_name("rename")
+                    .about("rename a user")
+                    .arg(id("id", "id of the user"))
+                    .arg(id("name", "new name")),
+            )
+            .subcommand(
+                SubCommand::with_name("disable")
+                    .about("disable a user")
+                    .arg(id("id", "id of the user")),
+            )
+            .subcommand(
+                SubCommand::with_name("enable")
+                    .about("enable a disabled user")
+                    .arg(id("id", "id of the user")),
+            )
+            .subcommand(
+                SubCommand::with_name("purge")
+                    .about("remove the deleted users past their grace period and their papers"),
+            ),
+        SubCommand::with_name("paper")
+            .about("inspect and repair papers")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(page(
+                SubCommand::with_name("list")
+                    .about("list papers ordered by id")
+                    .arg(
+                        Arg::with_name("user")
+                            .long("user")
+                            .help("only the papers of this user id")
+                            .takes_value(true),
+                    )
+                    .arg(
+                        Arg::with_name("deleted")
+                            .long("deleted")
+                            .help("deleted papers instead of the others"),
+                    ),
+            ))
+            .subcommand(
+                SubCommand::with_name("restore")
+                    .about("undo the deletion of a paper")
+                    .arg(id("id", "id of the paper")),
+            )
+            .subcommand(
+                SubCommand::with_name("purge")
+                    .about("remove deleted papers for good")
+                    .arg(
+                        Arg::with_name("id")
+                            .help("id of the paper")
+                            .required_unless("deleted-before-days"),
+                    )
+                    .arg(
+                        Arg::with_name("deleted-before-days")
+                            .long("deleted-before-days")
+                            .help("every paper deleted at least this many days ago")
+                            .takes_value(true)
+                            .conflicts_with("id"),
+                    ),
+            ),
+        SubCommand::with_name("token")
+            .about("access tokens")
+            .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+            .subcommand(
+                SubCommand::with_name("mint")
+                    .about("mint an access token of a user, for debugging")
+                    .arg(id("id", "id of the user"))
+                    .arg(
+                        Arg::with_name("expires-in-sec")
+                            .long("expires-in-sec")
+                            .help("lifetime of the access token, defaults to the config")
+                            .takes_value(true),
+                    ),
+            ),
+        SubCommand::with_name("migrate").about("bring the storage up to date"),
+    ]
+}
+
+/// Run the operator subcommand of `matches`.
+pub async fn run(config: &Config, matches: &ArgMatches<'static>) -> Result<()> {
+    let db = mongodb::Client::with_uri_str(&config.storage.uri)
+        .await?
+        .database(&config.storage.database);
+    // Events have no subscriber outside of the server.
+    let module = super::build_module(config, &db, &EventBus::channel(1));
+
+    let admin_service: Box<dyn AdminService> = module.provide()?;
+
+    match matches.subcommand() {
+        ("user", Some(matches)) => match matches.subcommand() {
+            ("list", Some(matches)) => {
+                let filter = UserFilter {
+                    search: matches.value_of("search").map(str::to_owned),
+                    disabled: match matches.is_present("disabled") {
+                        true => Some(true),
+                        false => None,
+                    },
+                };
+                let pagination = Pagination::After {
+                    after: matches
+                        .value_of("after")
+                        .map(|x| UserCursor { id: x.into() }),
+                    skip: None,
+                    first: first(matches)?,
+                };
+                print_page(admin_service.select_user_page(pagination, filter).await?)
+            }
+            ("show", Some(matches)) => {
+                let user_service: Box<dyn UserService> = module.provide()?;
+                let user = matches.value_of("user").unwrap_or_default();
+                let user = match user_service
+                    .select_user(UserIdentifier::Id(user.into()))
+                    .await
+                {
+                    Ok(user) => user,
+                    Err(_) => {
+                        user_service
+                            .select_user(UserIdentifier::Name(user.to_owned()))
+                            .await?
+                    }
+                };
+                print(&user)
+            }
+            ("rename", Some(matches)) => {
+                let name = matches.value_of("name").unwrap_or_default().to_owned();
+                let user = admin_service
+                    .rename_user(
+                        matches.value_of("id").unwrap_or_default().into(),
+                        name.to_owned(),
+                    )
+                    .await?;
+                audit(
+                    &module,
+                    AuditEventInput {
+                        user_id: Some(user.id.to_owned()),
+                        details: Some(serde_json::json!({ "name": name, "via": "cli" })),
+                        ..AuditEventInput::new(AuditEventKind::UserRename)
+                    },
+                )
+                .await?;
+                print(&user)
+            }
+            (command @ "disable", Some(matches)) | (command @ "enable", Some(matches)) => {
+                let user = admin_service
+                    .set_user_disabled(
+                        matches.value_of("id").unwrap_or_default().into(),
+                        command == "disable",
+                    )
+                    .await?;
+                let kind = match command {
+                    "disable" => AuditEventKind::UserDisable,
+                    _ => AuditEventKind::UserEnable,
+                };
+                audit(
+                    &module,
+                    AuditEventInput {
+                        user_id: Some(user.id.to_owned()),
+                        details: Some(serde_json::json!({ "via": "cli" })),
+                        ..AuditEventInput::new(kind)
+                    },
+                )
+                .await?;
+                print(&user)
+            }
+            ("purge", Some(_)) => {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)?
+                    .as_millis() as u64;
+                let count = admin_service.purge_deleted_users(now).await?;
+                print(&serde_json::json!({ "purged": count }))
+            }
+            _ => unreachable!(),
+        },
+        ("paper", Some(matches)) => match matches.subcommand() {
+            ("list", Some(matches)) => {
+                let filter = AdminPaperFilter {
+                    user_id: matches.value_of("user").map(Into::into),
+                    paper: PaperFilter {
+                        deleted: matches.is_present("deleted"),
+                        ..Default::default()
+                    },
+                };
+                let pagination = Pagination::After {
+                    after: matches.value_of("after").map(|x| PaperCursor {
+                        id: x.into(),
+                        updated_at: None,
+                    }),
+                    skip: None,
+                    first: first(matches)?,
+                };
+                let order_by = OrderBy {
+                    field: PaperOrderField::Id,
+                    direction: OrderDirection::Asc,
+                };
+                print_page(
+                    admin_service
+                        .select_paper_page(pagination, order_by, filter)
+                        .await?,
+                )
+            }
+            ("restore", Some(matches)) => {
+                let paper = admin_service
+                    .restore_paper(matches.value_of("id").unwrap_or_default().into())
+                    .await?;
+                audit(
+                    &module,
+                    AuditEventInput {
+                        user_id: Some(paper.user_id.to_owned()),
+                        paper_id: Some(paper.id.to_owned()),
+                        details: Some(serde_json::json!({ "via": "cli" })),
+                        ..AuditEventInput::new(AuditEventKind::PaperRestore)
+                    },
+                )
+                .await?;
+                print(&paper)
+            }
+            ("purge", Some(matches)) => match matches.value_of("deleted-before-days") {
+                Some(days) => {
+                    let days: u64 = days.parse()?;
+                    let deleted_before = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)?
+                        .checked_sub(std::time::Duration::from_secs(days * 24 * 3600))
+                        .unwrap_or_default()
+                        .as_millis() as u64;
+                    let count = admin_service.purge_deleted_papers(deleted_before).await?;
+                    print(&serde_json::json!({ "purged": count }))
+                }
+                None => {
+                    admin_service
+                        .purge_paper(matches.value_of("id").unwrap_or_default().into())
+                        .await?;
+                    print(&serde_json::json!({ "purged": 1 }))
+                }
+            },
+            _ => unreachable!(),
+        },
+        ("token", Some(matches)) => match matches.subcommand() {
+            ("mint", Some(matches)) => {
+                let expires_in_sec = matches
+                    .value_of("expires-in-sec")
+                    .map(str::parse::<u64>)
+                    .transpose()?;
+                print(
+                    &admin_service
+                        .mint_access_token(
+                            matches.value_of("id").unwrap_or_default().into(),
+                            expires_in_sec,
+                        )
+                        .await?,
+                )
+            }
+            _ => unreachable!(),
+        },
+        ("migrate", Some(_)) => {
+            for applied in admin_service.migrate().await? {
+                println!("{}", applied);
+            }
+            Ok(())
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Record an event done by the operator, it has no actor.
+async fn audit(module: &Module, input: AuditEventInput) -> Result<()> {
+    let audit_service: Box<dyn AuditService> = module.provide()?;
+    audit_service.record(input).await?;
+    Ok(())
+}
+
+fn first(matches: &ArgMatches) -> Result<u64> {
+    Ok(matches.value_of("first").unwrap_or("20").parse()?)
+}
+
+fn print<T: Serialize>(value: &T) -> Result<()> {
+    println!("{}", serde_json::to_string(value)?);
+    Ok(())
+}
+
+/// One item per line, then a hint on stderr when there is a next page.
+fn print_page<T: Serialize>(page: PaginationList<T>) -> Result<()> {
+    for item in page.list.iter() {
+        print(item)?;
+    }
+    eprintln!("{} of {}", page.list.len(), page.total);
+    if page.has_next_page {
+        eprintln!("More items follow, continue with --after <id of the last item>");
+    }
+    Ok(())
+}
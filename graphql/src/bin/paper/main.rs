@@ -0,0 +1,1046 @@
+use std::{
+    future::Future,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    dev::HttpResponseBuilder, http::header, middleware::Condition, web, App, HttpRequest,
+    HttpResponse, HttpServer, Responder,
+};
+use juniper_graphql_ws::ConnectionConfig;
+use paper::{
+    admin::AdminService, attachment::AttachmentService, audit::AuditService, blob::BlobStore,
+    export::ExportService, notification::NotificationService, quota::QuotaService,
+    webhook::WebhookService, ErrorKind,
+};
+use paper_graphql::{
+    cors,
+    logger::{AccessLogFields, Logger, RequestLog},
+    metrics::{self, MongoCommandMetrics},
+    models::{attachment::*, export::*, paper::*},
+    persisted_query::PersistedQueries,
+    rate_limit::{RateLimit, RateLimiter},
+    reload::Reloadable,
+    security_headers, shutdown,
+    telemetry::{self, CommandEventHandlers, MongoCommandSpans, Telemetry},
+    tls::{self, CertificateResolver},
+    *,
+};
+use paper_impl::{
+    admin::*, attachment::*, audit::*, auth::*, blob::*, comment::*, event::*, export::*,
+    notification::*, quota::*, user::*, webhook::*,
+};
+use shaku::{HasComponent, HasProvider};
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+mod admin;
+
+/// The period of the retention purges.
+const HOUR: Duration = Duration::from_secs(3600);
+
+#[actix_web::main]
+async fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let matches = cli().get_matches();
+
+    let config = match build_config(&matches) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    match matches.subcommand() {
+        ("config", Some(_)) => {
+            println!("Config is valid");
+            Ok(())
+        }
+        ("serve", _) | ("", None) => serve(config, matches).await,
+        _ => {
+            if let Err(e) = admin::run(&config, &matches).await {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn serve(
+    config: Config,
+    matches: clap::ArgMatches<'static>,
+) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let addr = (config.address.to_owned(), config.port);
+
+    Logger::init(config.log_level, &config.log).map_err(|e| e.to_string())?;
+
+    let _telemetry = Telemetry::init(&config.telemetry)?;
+
+    let mut client_options = mongodb::options::ClientOptions::parse(&config.storage.uri).await?;
+    client_options.command_event_handler = Some(Arc::new(CommandEventHandlers(vec![
+        Arc::new(MongoCommandMetrics),
+        Arc::new(MongoCommandSpans::default()),
+    ])));
+
+    let db = mongodb::Client::with_options(client_options)?.database(&config.storage.database);
+
+    let rate_limiter = Arc::new(RateLimiter::new(
+        config.rate_limit.clone(),
+        config.access_token.secret.to_owned(),
+    ));
+    actix_web::rt::spawn(rate_limiter.clone().watch(Duration::from_secs(60)));
+
+    let persisted_queries = Arc::new(PersistedQueries::new(config.persisted_queries.clone())?);
+
+    let event_sender = EventBus::channel(1024);
+
+    let module = Arc::new(Reloadable::new(build_module(&config, &db, &event_sender)));
+    bootstrap_admins(&module.load()).await;
+    let shared_config = Arc::new(Reloadable::new(config.clone()));
+
+    let shutdown_timeout_sec = config.shutdown_timeout_sec;
+    let server = {
+        let config = config.clone();
+        let db = db.clone();
+        let module = module.clone();
+        let shared_config = shared_config.clone();
+
+        HttpServer::new(move || {
+            App::new()
+                .wrap(RateLimit(rate_limiter.clone()))
+                .wrap(Condition::new(
+                    config.cors.enabled,
+                    cors::cors(&config.cors, shared_config.clone()),
+                ))
+                .wrap(security_headers::security_headers(&config.security_headers))
+                .wrap(RequestLog)
+                .app_data(web::Data::from(rate_limiter.clone()))
+                .app_data(web::Data::from(persisted_queries.clone()))
+                .app_data(web::Data::from(module.clone()))
+                .app_data(web::Data::from(shared_config.clone()))
+                .data(config.query_limits.clone())
+                .data(db.clone())
+                .data(Schema::new(Query, Mutation, Subscription))
+                .service(graphql_handler)
+                .service(graphql_get_handler)
+                .service(subscriptions_handler)
+                .service(graphiql_handler)
+                .service(healthz_handler)
+                .service(readyz_handler)
+                .service(metrics_handler)
+                .service(export_handler)
+                .service(attachment_upload_handler)
+                .service(attachment_download_handler)
+        })
+        .disable_signals()
+        .shutdown_timeout(shutdown_timeout_sec)
+    };
+
+    let server = match config.tls.enabled {
+        true => {
+            let resolver = Arc::new(CertificateResolver::new(&config.tls)?);
+            let tls_config = tls::server_config(&config.tls, resolver.clone())?;
+
+            actix_web::rt::spawn(
+                resolver.watch(Duration::from_secs(config.tls.reload_interval_sec)),
+            );
+
+            server.bind_rustls(addr, tls_config)?
+        }
+        false => server.bind(addr)?,
+    }
+    .run();
+
+    actix_web::rt::spawn(stop_on_terminate(server.clone()));
+    actix_web::rt::spawn(every(HOUR, module.clone(), |module| async move {
+        let audit_service: Box<dyn AuditService> = module.provide().unwrap();
+        log_purge("expired audit events", audit_service.purge_expired_events()).await
+    }));
+    actix_web::rt::spawn(every(HOUR, module.clone(), |module| async move {
+        let notification_service: Box<dyn NotificationService> = module.provide().unwrap();
+        log_purge(
+            "expired notifications",
+            notification_service.purge_expired_notifications(),
+        )
+        .await
+    }));
+    actix_web::rt::spawn(every(HOUR, module.clone(), |module| async move {
+        let attachment_service: Box<dyn AttachmentService> = module.provide().unwrap();
+        log_purge(
+            "abandoned attachments",
+            attachment_service.purge_abandoned_attachments(),
+        )
+        .await
+    }));
+    actix_web::rt::spawn(every(HOUR, module.clone(), |module| async move {
+        let webhook_service: Box<dyn WebhookService> = module.provide().unwrap();
+        log_purge(
+            "expired webhook deliveries",
+            webhook_service.purge_expired_deliveries(),
+        )
+        .await
+    }));
+    actix_web::rt::spawn(every(
+        Duration::from_secs(1),
+        module.clone(),
+        deliver_webhooks,
+    ));
+    actix_web::rt::spawn(reload_on_hangup(
+        matches,
+        shared_config,
+        module,
+        db,
+        event_sender,
+    ));
+
+    server.await?;
+
+    Ok(())
+}
+
+fn build_module(
+    config: &Config,
+    db: &mongodb::Database,
+    event_sender: &tokio::sync::broadcast::Sender<Event>,
+) -> Module {
+    Module::builder()
+        .with_component_parameters::<AccessTokenConfig>(AccessTokenConfigParameters {
+            expires_in_sec: config.access_token.expires_in_sec,
+            secret: config.access_token.secret.to_owned(),
+        })
+        .with_component_parameters::<RefreshTokenConfig>(RefreshTokenConfigParameters {
+            expires_in_sec: config.refresh_token.expires_in_sec,
+            secret: config.refresh_token.secret.to_owned(),
+        })
+        .with_component_parameters::<PaperTokenConfig>(PaperTokenConfigParameters {
+            expires_in_sec: config.paper_token.expires_in_sec,
+            secret: config.paper_token.secret.to_owned(),
+        })
+        .with_component_parameters::<ExportTokenConfig>(ExportTokenConfigParameters {
+            expires_in_sec: config.export.url_expires_in_sec,
+            secret: config.export.secret(&config.access_token.secret),
+            base_url: config.export.base_url.trim_end_matches('/').to_owned(),
+        })
+        .with_component_parameters::<AttachmentTokenConfig>(AttachmentTokenConfigParameters {
+            expires_in_sec: config.attachment.url_expires_in_sec,
+            secret: config.attachment.secret(&config.access_token.secret),
+            base_url: config.attachment.base_url.trim_end_matches('/').to_owned(),
+        })
+        .with_component_parameters::<GithubAuthConfig>(GithubAuthConfigParameters {
+            list: config
+                .github_auth
+                .iter()
+                .map(|x| GithubAuthConfigItem {
+                    client_id: x.client_id.to_owned(),
+                    client_secret: x.client_secret.to_owned(),
+                })
+                .collect(),
+        })
+        .with_component_parameters::<GoogleAuthConfig>(GoogleAuthConfigParameters {
+            list: config
+                .google_auth
+                .iter()
+                .map(|x| GoogleAuthConfigItem {
+                    client_id: x.client_id.to_owned(),
+                    client_secret: x.client_secret.to_owned(),
+                    redirect_uri: x.redirect_uri.to_owned(),
+                })
+                .collect(),
+        })
+        .with_component_parameters::<AdminConfig>(AdminConfigParameters {
+            bootstrap_admins: config.admin.bootstrap_admins.to_owned(),
+            impersonation_expires_in_sec: config.admin.impersonation_expires_in_sec,
+        })
+        .with_component_parameters::<AccountConfig>(AccountConfigParameters {
+            deletion_grace_period_sec: config.account.deletion_grace_period_sec,
+        })
+        .with_component_parameters::<ExportConfig>(ExportConfigParameters {
+            expires_in_sec: config.export.expires_in_sec,
+        })
+        .with_component_parameters::<AuditConfig>(AuditConfigParameters {
+            retention_days: config.audit.retention_days,
+        })
+        .with_component_parameters::<WebhookConfig>(WebhookConfigParameters {
+            max_per_user: config.webhook.max_per_user,
+            max_attempts: config.webhook.max_attempts,
+            retry_base_sec: config.webhook.retry_base_sec,
+            timeout_sec: config.webhook.timeout_sec,
+            delivery_retention_days: config.webhook.delivery_retention_days,
+            allowed_hosts: config.webhook.allowed_hosts.to_owned(),
+        })
+        .with_component_parameters::<NotificationConfig>(NotificationConfigParameters {
+            retention_days: config.notification.retention_days,
+        })
+        .with_component_parameters::<AttachmentConfig>(AttachmentConfigParameters {
+            max_size: config.attachment.max_size_bytes,
+            upload_expires_in_sec: config.attachment.url_expires_in_sec,
+        })
+        .with_component_parameters::<QuotaConfig>(QuotaConfigParameters {
+            default_plan: config.quota.default_plan.to_owned(),
+            plans: config
+                .quota
+                .plans
+                .iter()
+                .map(|(name, limits)| (name.to_owned(), limits.into()))
+                .collect(),
+            users: config
+                .quota
+                .users
+                .iter()
+                .map(|(user_id, user)| (user_id.to_owned(), user.into()))
+                .collect(),
+        })
+        .with_component_parameters::<StorageDatabase>(StorageDatabaseParameters {
+            database: db.clone(),
+        })
+        .with_component_parameters::<BlobStorage>(BlobStorageParameters {
+            store: blob_store(&config.blob),
+        })
+        .with_component_parameters::<EventBus>(EventBusParameters {
+            sender: event_sender.clone(),
+        })
+        .with_component_parameters::<UserCollectionConfig>(UserCollectionConfigParameters {
+            database: db.clone(),
+            collection: config.storage.collection_user.to_owned(),
+        })
+        .with_component_parameters::<PaperCollectionConfig>(PaperCollectionConfigParameters {
+            database: db.clone(),
+            collection: config.storage.collection_paper.to_owned(),
+        })
+        .with_component_parameters::<CommentCollectionConfig>(CommentCollectionConfigParameters {
+            database: db.clone(),
+            collection: config.storage.collection_comment.to_owned(),
+        })
+        .with_component_parameters::<ExportCollectionConfig>(ExportCollectionConfigParameters {
+            database: db.clone(),
+            collection: config.storage.collection_export.to_owned(),
+        })
+        .with_component_parameters::<AuditCollectionConfig>(AuditCollectionConfigParameters {
+            database: db.clone(),
+            collection: config.storage.collection_audit.to_owned(),
+        })
+        .with_component_parameters::<WebhookCollectionConfig>(WebhookCollectionConfigParameters {
+            database: db.clone(),
+            collection: config.storage.collection_webhook.to_owned(),
+        })
+        .with_component_parameters::<WebhookDeliveryCollectionConfig>(
+            WebhookDeliveryCollectionConfigParameters {
+                database: db.clone(),
+                collection: config.storage.collection_webhook_delivery.to_owned(),
+            },
+        )
+        .with_component_parameters::<NotificationCollectionConfig>(
+            NotificationCollectionConfigParameters {
+                database: db.clone(),
+                collection: config.storage.collection_notification.to_owned(),
+            },
+        )
+        .with_component_parameters::<AttachmentCollectionConfig>(
+            AttachmentCollectionConfigParameters {
+                database: db.clone(),
+                collection: config.storage.collection_attachment.to_owned(),
+            },
+        )
+        .with_component_parameters::<QuotaCollectionConfig>(QuotaCollectionConfigParameters {
+            database: db.clone(),
+            collection: config.storage.collection_quota.to_owned(),
+        })
+        .build()
+}
+
+/// The blob store selected by the config.
+fn blob_store(config: &ConfigBlob) -> Arc<dyn BlobStore> {
+    match config.store {
+        BlobStoreKind::File => Arc::new(FileBlobStore::new(&config.path)),
+        BlobStoreKind::S3 => Arc::new(S3BlobStore::new(
+            &config.s3.endpoint,
+            &config.s3.region,
+            &config.s3.bucket,
+            &config.s3.access_key_id,
+            &config.s3.secret_access_key,
+            Duration::from_secs(config.s3.timeout_sec),
+        )),
+    }
+}
+
+/// Promote the admins of the config who have already signed up.
+async fn bootstrap_admins(module: &Module) {
+    let admin_service: Box<dyn AdminService> = module.provide().unwrap();
+
+    match admin_service.bootstrap_admins().await {
+        Ok(0) => {}
+        Ok(count) => log::info!("Promoted {} users to admin", count),
+        Err(e) => log::error!("Failed to bootstrap admins: {}", e),
+    }
+}
+
+/// Run `task` with the current module every `period` until shutdown.
+async fn every<F, T>(period: Duration, module: Arc<Reloadable<Module>>, task: F)
+where
+    F: Fn(Arc<Module>) -> T,
+    T: Future<Output = ()>,
+{
+    let mut interval = tokio::time::interval(period);
+
+    while !shutdown::is_triggered() {
+        interval.tick().await;
+        task(module.load()).await;
+    }
+}
+
+/// Log how many `what` a purge removed, nothing when there were none.
+async fn log_purge(what: &str, purge: impl Future<Output = paper::Result<u64>>) {
+    match purge.await {
+        Ok(0) => {}
+        Ok(count) => log::info!("Purged {} {}", count, what),
+        Err(e) => log::error!("Failed to purge {}: {}", what, e),
+    }
+}
+
+/// Attempt the due webhook deliveries until none is left.
+async fn deliver_webhooks(module: Arc<Module>) {
+    let webhook_service: Box<dyn WebhookService> = module.provide().unwrap();
+
+    while !shutdown::is_triggered() {
+        match webhook_service.deliver_due().await {
+            Ok(0) => break,
+            Ok(_) => {}
+            Err(e) => {
+                log::error!("Failed to deliver webhooks: {}", e);
+                break;
+            }
+        }
+    }
+}
+
+/// Drain on SIGTERM or SIGINT: stop accepting connections and give in-flight
+/// requests `shutdown_timeout_sec` to finish.
+async fn stop_on_terminate(server: actix_web::dev::Server) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let (mut terminate, mut interrupt) = match (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::interrupt()),
+    ) {
+        (Ok(terminate), Ok(interrupt)) => (terminate, interrupt),
+        (Err(e), _) | (_, Err(e)) => {
+            log::error!("Failed to listen for shutdown signals: {}", e);
+            return;
+        }
+    };
+
+    futures::future::select(Box::pin(terminate.recv()), Box::pin(interrupt.recv())).await;
+
+    log::info!("Shutting down, draining in-flight requests");
+    shutdown::trigger();
+    server.stop(true).await;
+}
+
+/// Load the config again on SIGHUP and swap the rotatable settings, requests
+/// already running keep the module they started with.
+async fn reload_on_hangup(
+    matches: clap::ArgMatches<'static>,
+    config: Arc<Reloadable<Config>>,
+    module: Arc<Reloadable<Module>>,
+    db: mongodb::Database,
+    event_sender: tokio::sync::broadcast::Sender<Event>,
+) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = match signal(SignalKind::hangup()) {
+        Ok(hangup) => hangup,
+        Err(e) => {
+            log::error!("Failed to listen for SIGHUP: {}", e);
+            return;
+        }
+    };
+
+    while hangup.recv().await.is_some() {
+        let new = match build_config(&matches) {
+            Ok(new) => config.load().reload(new),
+            Err(e) => {
+                log::error!("Failed to reload config, keeping the current one: {}", e);
+                continue;
+            }
+        };
+
+        Logger::configure(new.log_level, &new.log);
+        module.store(build_module(&new, &db, &event_sender));
+        config.store(new);
+        bootstrap_admins(&module.load()).await;
+
+        log::info!("Reloaded config");
+    }
+}
+
+const GRAPHIQL_HTML: &str = include_str!("../../../graphiql.html");
+
+lazy_static::lazy_static! {
+    static ref GRAPHIQL_CSP: String = security_headers::graphiql_csp(GRAPHIQL_HTML);
+}
+
+#[actix_web::get("/graphiql")]
+async fn graphiql_handler() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/html")
+        .header("Content-Security-Policy", GRAPHIQL_CSP.as_str())
+        .body(GRAPHIQL_HTML)
+}
+
+#[actix_web::post("/graphql")]
+async fn graphql_handler(
+    req: HttpRequest,
+    body: web::Bytes,
+    schema: web::Data<Schema>,
+    module: web::Data<Reloadable<Module>>,
+    rate_limiter: web::Data<RateLimiter>,
+    query_limits: web::Data<ConfigQueryLimits>,
+    persisted_queries: web::Data<PersistedQueries>,
+) -> HttpResponse {
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|x| x.to_str().ok());
+
+    let mut payload = match GraphQLPayload::parse(content_type, &body) {
+        Ok(payload) => payload,
+        Err(e) => return HttpResponse::BadRequest().json(e.to_response_body()),
+    };
+
+    if let Err(e) = payload.resolve_persisted_queries(&persisted_queries) {
+        return HttpResponse::BadRequest().json(e.to_response_body());
+    }
+
+    match execute(
+        &req,
+        payload,
+        &schema,
+        module.load(),
+        rate_limiter,
+        &query_limits,
+    )
+    .await
+    {
+        Ok((true, response)) => HttpResponse::Ok().json(response),
+        Ok((false, response)) => HttpResponse::BadRequest().json(response),
+        Err(e) => HttpResponse::BadRequest().json(e.to_response_body()),
+    }
+}
+
+/// Persisted queries over `GET`, so that proxies can cache the responses of
+/// anonymous requests. Mutations are only accepted over `POST`.
+#[actix_web::get("/graphql")]
+async fn graphql_get_handler(
+    req: HttpRequest,
+    schema: web::Data<Schema>,
+    module: web::Data<Reloadable<Module>>,
+    rate_limiter: web::Data<RateLimiter>,
+    query_limits: web::Data<ConfigQueryLimits>,
+    persisted_queries: web::Data<PersistedQueries>,
+) -> HttpResponse {
+    let mut operation = match GraphQLOperation::from_query_string(req.query_string()) {
+        Ok(operation) => operation,
+        Err(e) => return no_store(HttpResponse::BadRequest()).json(e.to_response_body()),
+    };
+
+    if operation.persisted_query_hash().is_none() {
+        return no_store(HttpResponse::BadRequest()).json(
+            Error::unknown("GET requests must use a persisted query".to_owned()).to_response_body(),
+        );
+    }
+
+    if let Err(e) = persisted_queries.resolve(&mut operation) {
+        return no_store(HttpResponse::BadRequest()).json(e.to_response_body());
+    }
+
+    match operation.operation_type() {
+        Ok(OperationType::Query) => {}
+        Ok(_) => {
+            return no_store(HttpResponse::MethodNotAllowed()).json(
+                Error::unknown("Only queries can be sent with GET".to_owned()).to_response_body(),
+            )
+        }
+        Err(e) => {
+            return no_store(HttpResponse::BadRequest()).json(Error::from(e).to_response_body())
+        }
+    }
+
+    let anonymous = !req.headers().contains_key("Authorization");
+    let max_age_sec = persisted_queries.max_age_sec();
+
+    match execute(
+        &req,
+        GraphQLPayload::Single(operation),
+        &schema,
+        module.load(),
+        rate_limiter,
+        &query_limits,
+    )
+    .await
+    {
+        Ok((true, response)) => HttpResponse::Ok()
+            .header(
+                header::CACHE_CONTROL,
+                match anonymous {
+                    true => format!("public, max-age={}", max_age_sec),
+                    false => "private, no-cache".to_owned(),
+                },
+            )
+            .header(header::VARY, "Authorization")
+            .json(response),
+        Ok((false, response)) => no_store(HttpResponse::BadRequest()).json(response),
+        Err(e) => no_store(HttpResponse::BadRequest()).json(e.to_response_body()),
+    }
+}
+
+fn no_store(mut builder: HttpResponseBuilder) -> HttpResponseBuilder {
+    builder.header(header::CACHE_CONTROL, "no-store");
+    builder
+}
+
+/// Check the limits of `payload` and execute it, returns whether the
+/// execution succeeded along with the response body.
+async fn execute(
+    req: &HttpRequest,
+    payload: GraphQLPayload,
+    schema: &Schema,
+    module: web::Data<Reloadable<Module>>,
+    rate_limiter: web::Data<RateLimiter>,
+    query_limits: &ConfigQueryLimits,
+) -> Result<(bool, serde_json::Value)> {
+    payload.check_limits(query_limits)?;
+
+    let operation_names: Vec<Option<String>> = payload
+        .operations()
+        .iter()
+        .map(|x| x.operation_name.to_owned())
+        .collect();
+    let operation_label = operation_names
+        .iter()
+        .map(|x| x.as_deref().unwrap_or("anonymous"))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let request = payload.into_request()?;
+
+    let access_token = req
+        .headers()
+        .get("Authorization")
+        .and_then(|x| x.to_str().ok())
+        .and_then(bearer_token);
+
+    let context = Context::new(
+        module,
+        access_token,
+        rate_limiter.client_ip(req),
+        user_agent(req),
+        rate_limiter.into_inner(),
+    )
+    .with_max_page_size(query_limits.max_page_size);
+
+    let access_token = context.decode_access_token().ok();
+    AccessLogFields {
+        operation_name: Some(operation_label.to_owned()),
+        user_id: access_token.as_ref().map(|x| x.sub.to_string()),
+        actor_id: access_token
+            .as_ref()
+            .and_then(|x| x.act.as_ref())
+            .map(|x| x.to_string()),
+    }
+    .insert(req);
+
+    // Requests of a user, not those of an admin impersonating them, count
+    // towards their daily limit.
+    if let Some(access_token) = access_token.as_ref().filter(|x| x.act.is_none()) {
+        let quota_service: Box<dyn QuotaService> = context.module.provide().unwrap();
+        match quota_service.count_api_request(&access_token.sub).await {
+            Ok(_) => {}
+            Err(e) if e.kind == ErrorKind::QuotaExceeded => return Err(e.into()),
+            Err(e) => log::error!("Failed to count the request of {}: {}", access_token.sub, e),
+        }
+    }
+
+    let span = tracing::info_span!(
+        "graphql",
+        otel.name = "graphql",
+        otel.kind = "server",
+        graphql.operation.name = %operation_label,
+    );
+    span.set_parent(telemetry::extract_context(req.headers()));
+
+    let started_at = Instant::now();
+    let response = request.execute(schema, &context).instrument(span).await;
+    let elapsed = started_at.elapsed();
+
+    for operation_name in operation_names.iter() {
+        metrics::observe_graphql_request(operation_name.as_deref(), elapsed);
+    }
+
+    let body = serde_json::to_value(&response).map_err(|e| Error::unknown(e.to_string()))?;
+
+    Ok((response.is_ok(), body))
+}
+
+/// GraphQL subscriptions over the `graphql-ws` protocol, the access token is
+/// taken from the `Authorization` field of the connection init payload.
+#[actix_web::get("/subscriptions")]
+async fn subscriptions_handler(
+    req: HttpRequest,
+    payload: web::Payload,
+    schema: web::Data<Schema>,
+    module: web::Data<Reloadable<Module>>,
+    rate_limiter: web::Data<RateLimiter>,
+    query_limits: web::Data<ConfigQueryLimits>,
+) -> std::result::Result<HttpResponse, actix_web::Error> {
+    let module = module.load();
+    let max_page_size = query_limits.max_page_size;
+    let client_ip = rate_limiter.client_ip(&req);
+    let user_agent = user_agent(&req);
+    let rate_limiter = rate_limiter.into_inner();
+
+    let init = move |params: juniper::Variables| async move {
+        let access_token = params
+            .get("Authorization")
+            .or_else(|| params.get("authorization"))
+            .and_then(|x| x.as_string_value())
+            .and_then(bearer_token);
+
+        let context = Context::new(module, access_token, client_ip, user_agent, rate_limiter);
+        context.access_token().await?;
+        let context = context
+            .with_max_page_size(max_page_size)
+            .with_websocket_connection();
+
+        Ok(ConnectionConfig::new(context).with_keep_alive_interval(Duration::from_secs(15)))
+            as Result<_>
+    };
+
+    juniper_actix::subscriptions::subscriptions_handler(req, payload, schema.into_inner(), init)
+        .await
+}
+
+/// Liveness, the process is able to serve requests.
+#[actix_web::get("/healthz")]
+async fn healthz_handler() -> impl Responder {
+    HttpResponse::Ok().json(serde_json::json!({ "status": "ok" }))
+}
+
+/// Readiness, MongoDB answers a ping, the config is sane and the server is
+/// not shutting down.
+#[actix_web::get("/readyz")]
+async fn readyz_handler(
+    config: web::Data<Reloadable<Config>>,
+    db: web::Data<mongodb::Database>,
+) -> impl Responder {
+    let mongodb = match tokio::time::timeout(
+        Duration::from_secs(2),
+        db.run_command(mongodb::bson::doc! { "ping": 1 }, None),
+    )
+    .await
+    {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("Ping timed out".to_owned()),
+    };
+    let config = config.load().validate();
+    let shutdown = match shutdown::is_triggered() {
+        true => Err("Shutting down".to_owned()),
+        false => Ok(()),
+    };
+    let ready = mongodb.is_ok() && config.is_ok() && shutdown.is_ok();
+
+    let status = |x: &std::result::Result<(), String>| match x {
+        Ok(()) => "ok".to_owned(),
+        Err(e) => e.to_owned(),
+    };
+    let body = serde_json::json!({
+        "status": if ready { "ok" } else { "unavailable" },
+        "checks": {
+            "mongodb": status(&mongodb),
+            "config": status(&config),
+            "shutdown": status(&shutdown),
+        },
+    });
+
+    match ready {
+        true => HttpResponse::Ok().json(body),
+        false => HttpResponse::ServiceUnavailable().json(body),
+    }
+}
+
+#[actix_web::get("/metrics")]
+async fn metrics_handler() -> impl Responder {
+    let (content_type, body) = metrics::render();
+
+    HttpResponse::Ok().content_type(content_type).body(body)
+}
+
+/// The zip archive of a data export, `token` is the signed part of its
+/// download URL. It is streamed from the blob store.
+#[actix_web::get("/exports/{token}")]
+async fn export_handler(
+    token: web::Path<String>,
+    module: web::Data<Reloadable<Module>>,
+) -> impl Responder {
+    use futures::StreamExt;
+
+    let module = module.load();
+    let config: &dyn ExportTokenConfigInterface = module.resolve_ref();
+
+    let export_id = match ExportTokenPayload::decode(&token, &config.secret) {
+        Ok(payload) => payload.sub,
+        Err(_) => return no_store(HttpResponse::Forbidden()).finish(),
+    };
+
+    let export_service: Box<dyn ExportService> = module.provide().unwrap();
+
+    match export_service
+        .select_export_archive(export_id.to_owned())
+        .await
+    {
+        Ok(archive) => no_store(HttpResponse::Ok())
+            .content_type("application/zip")
+            .header(
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"paper-export-{}.zip\"", export_id),
+            )
+            .streaming(archive.map(|x| {
+                x.map(web::Bytes::from)
+                    .map_err(actix_web::error::ErrorInternalServerError)
+            })),
+        Err(e) if e.kind == ErrorKind::NotFound => no_store(HttpResponse::NotFound()).finish(),
+        Err(e) => {
+            log::error!("Failed to read export {}: {}", export_id, e);
+            no_store(HttpResponse::InternalServerError()).finish()
+        }
+    }
+}
+
+/// Store the content of an attachment, `token` is the signed part of its
+/// upload URL. The body is read up to the size the attachment was created
+/// with.
+#[actix_web::put("/attachments/{token}")]
+async fn attachment_upload_handler(
+    token: web::Path<String>,
+    mut body: web::Payload,
+    module: web::Data<Reloadable<Module>>,
+) -> impl Responder {
+    use futures::StreamExt;
+
+    let module = module.load();
+    let config: &dyn AttachmentTokenConfigInterface = module.resolve_ref();
+
+    let (attachment_id, viewer_id) = match AttachmentTokenPayload::decode(&token, &config.secret) {
+        Ok(payload) if payload.upload => (payload.sub, payload.viewer),
+        _ => return no_store(HttpResponse::Forbidden()).finish(),
+    };
+
+    let attachment_service: Box<dyn AttachmentService> = module.provide().unwrap();
+
+    let attachment = match attachment_service
+        .select_attachment(viewer_id.to_owned(), attachment_id.to_owned())
+        .await
+    {
+        Ok(attachment) => attachment,
+        Err(e) => return error_response(e),
+    };
+
+    // The declared size is only a limit, the buffer grows with what arrives.
+    let mut content = Vec::new();
+    while let Some(chunk) = body.next().await {
+        match chunk {
+            Ok(chunk) if (content.len() + chunk.len()) as u64 <= attachment.size => {
+                content.extend_from_slice(&chunk)
+            }
+            Ok(_) => return no_store(HttpResponse::PayloadTooLarge()).finish(),
+            Err(_) => return no_store(HttpResponse::BadRequest()).finish(),
+        }
+    }
+
+    match attachment_service
+        .upload_attachment(viewer_id, attachment_id, content)
+        .await
+    {
+        Ok(_) => no_store(HttpResponse::NoContent()).finish(),
+        Err(e) => error_response(e),
+    }
+}
+
+/// The content of an attachment, `token` is the signed part of its download
+/// URL. The paper is checked to be readable by the viewer the URL was issued
+/// to. It is streamed from the blob store.
+#[actix_web::get("/attachments/{token}")]
+async fn attachment_download_handler(
+    token: web::Path<String>,
+    module: web::Data<Reloadable<Module>>,
+) -> impl Responder {
+    use futures::StreamExt;
+
+    let module = module.load();
+    let config: &dyn AttachmentTokenConfigInterface = module.resolve_ref();
+
+    let (attachment_id, viewer_id) = match AttachmentTokenPayload::decode(&token, &config.secret) {
+        Ok(payload) if !payload.upload => (payload.sub, payload.viewer),
+        _ => return no_store(HttpResponse::Forbidden()).finish(),
+    };
+
+    let attachment_service: Box<dyn AttachmentService> = module.provide().unwrap();
+
+    match attachment_service
+        .select_attachment_content(viewer_id, attachment_id)
+        .await
+    {
+        Ok((attachment, content)) => {
+            // Images are embedded in papers, other files are saved. SVG can
+            // run scripts and is saved too.
+            let disposition = match attachment.content_type.as_str() {
+                "image/png" | "image/jpeg" | "image/gif" | "image/webp" => "inline",
+                _ => "attachment",
+            };
+
+            no_store(HttpResponse::Ok())
+                .content_type(attachment.content_type.as_str())
+                .header(
+                    header::CONTENT_DISPOSITION,
+                    format!(
+                        "{}; filename=\"{}\"",
+                        disposition,
+                        attachment.name.replace(|c| c == '"' || c == '\\', "_")
+                    ),
+                )
+                .streaming(content.map(|x| {
+                    x.map(web::Bytes::from)
+                        .map_err(actix_web::error::ErrorInternalServerError)
+                }))
+        }
+        Err(e) => error_response(e),
+    }
+}
+
+/// The status of a failed request to the upload or download URL of an
+/// attachment.
+fn error_response(e: paper::Error) -> HttpResponse {
+    match e.kind {
+        ErrorKind::NotFound => no_store(HttpResponse::NotFound()).finish(),
+        ErrorKind::Unaurhorized | ErrorKind::Forbidden | ErrorKind::QuotaExceeded => {
+            no_store(HttpResponse::Forbidden()).finish()
+        }
+        ErrorKind::InvalidInput => {
+            no_store(HttpResponse::BadRequest()).body(e.message.unwrap_or_default())
+        }
+        ErrorKind::Unknown => {
+            log::warn!("Attachment request failed: {}", e);
+            no_store(HttpResponse::BadRequest()).body(e.message.unwrap_or_default())
+        }
+    }
+}
+
+fn user_agent(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::USER_AGENT)
+        .and_then(|x| x.to_str().ok())
+        .map(str::to_owned)
+}
+
+fn bearer_token(value: &str) -> Option<String> {
+    if value.starts_with("Bearer ") {
+        Some(value.trim_start_matches("Bearer ").to_owned())
+    } else {
+        None
+    }
+}
+
+fn cli() -> clap::App<'static, 'static> {
+    clap::App::new(env!("CARGO_BIN_NAME"))
+        .version(clap::crate_version!())
+        .author(clap::crate_authors!())
+        .about(clap::crate_description!())
+        .after_help(
+            "Config layers: defaults, the config file, PAPER__* environment variables \
+             (nested keys separated by __, e.g. PAPER__ACCESS_TOKEN__SECRET), then flags. \
+             Secrets, e.g. access_token.secret_file, may be read from a file.",
+        )
+        .arg(
+            clap::Arg::with_name("config")
+                .short("c")
+                .long("config")
+                .help("path to configuration file")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            clap::Arg::with_name("set")
+                .long("set")
+                .value_name("KEY=VALUE")
+                .help("override a config value, e.g. storage.database=paper")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1)
+                .global(true),
+        )
+        .arg(
+            clap::Arg::with_name("address")
+                .long("address")
+                .help("address to listen on")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            clap::Arg::with_name("port")
+                .long("port")
+                .help("port to listen on")
+                .takes_value(true)
+                .global(true),
+        )
+        .arg(
+            clap::Arg::with_name("log-level")
+                .long("log-level")
+                .help("one of OFF/ERROR/WARN/INFO/DEBUG/TRACE")
+                .takes_value(true)
+                .global(true),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("serve")
+                .about("serve the GraphQL API, the default without a subcommand"),
+        )
+        .subcommand(
+            clap::SubCommand::with_name("config")
+                .about("configuration commands")
+                .setting(clap::AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    clap::SubCommand::with_name("check")
+                        .about("load and validate the configuration, then exit"),
+                ),
+        )
+        .subcommands(admin::subcommands())
+}
+
+fn build_config(matches: &clap::ArgMatches) -> std::result::Result<Config, String> {
+    // Global args given after a subcommand are only in the matches of the
+    // subcommand, the matches of the innermost one have them all.
+    let mut matches = matches;
+    while let (_, Some(x)) = matches.subcommand() {
+        matches = x;
+    }
+
+    let mut overrides: Vec<String> = Vec::new();
+    for (arg, key) in [
+        ("address", "address"),
+        ("port", "port"),
+        ("log-level", "log_level"),
+    ]
+    .iter()
+    {
+        if let Some(value) = matches.value_of(arg) {
+            overrides.push(format!("{}={}", key, value));
+        }
+    }
+    if let Some(values) = matches.values_of("set") {
+        overrides.extend(values.map(str::to_owned));
+    }
+    let config_file = matches.value_of("config");
+
+    Config::load(config_file, &overrides)
+}
@@ -1,4 +1,6 @@
-use crate::logger::LogLevelFilter;
+use std::collections::HashMap;
+
+use crate::logger::{LogFormat, LogLevelFilter, ModuleLevelFilter};
 use serde::Deserialize;
 
 #[derive(Debug, Clone, Deserialize)]
@@ -7,11 +9,24 @@ pub struct Config {
 
     pub port: u16,
 
-    pub cors: bool,
+    #[serde(default)]
+    pub cors: ConfigCors,
+
+    #[serde(default)]
+    pub security_headers: ConfigSecurityHeaders,
+
+    #[serde(default)]
+    pub tls: ConfigTls,
+
+    /// Seconds in-flight requests are given to finish on shutdown.
+    pub shutdown_timeout_sec: u64,
 
     #[serde(with = "LogLevelFilter")]
     pub log_level: log::LevelFilter,
 
+    #[serde(default)]
+    pub log: ConfigLog,
+
     pub access_token: ConfigAccessToken,
 
     pub refresh_token: ConfigAccessToken,
@@ -23,6 +38,925 @@ pub struct Config {
     pub github_auth: Vec<ConfigGithubAuth>,
 
     pub google_auth: Vec<ConfigGoogleAuth>,
+
+    #[serde(default)]
+    pub admin: ConfigAdmin,
+
+    #[serde(default)]
+    pub account: ConfigAccount,
+
+    #[serde(default)]
+    pub export: ConfigExport,
+
+    #[serde(default)]
+    pub audit: ConfigAudit,
+
+    #[serde(default)]
+    pub webhook: ConfigWebhook,
+
+    #[serde(default)]
+    pub notification: ConfigNotification,
+
+    #[serde(default)]
+    pub attachment: ConfigAttachment,
+
+    #[serde(default)]
+    pub blob: ConfigBlob,
+
+    #[serde(default)]
+    pub quota: ConfigQuota,
+
+    #[serde(default)]
+    pub rate_limit: ConfigRateLimit,
+
+    #[serde(default)]
+    pub query_limits: ConfigQueryLimits,
+
+    #[serde(default)]
+    pub persisted_queries: ConfigPersistedQueries,
+
+    #[serde(default)]
+    pub telemetry: ConfigTelemetry,
+}
+
+/// Lowest layer of the config, the file, the environment and the command line
+/// are merged on top of it.
+const DEFAULT_CONFIG: &str = r#"
+address = "127.0.0.1"
+port = 8080
+shutdown_timeout_sec = 30
+log_level = "WARN"
+github_auth = []
+google_auth = []
+
+[access_token]
+expires_in_sec = 86400
+
+[refresh_token]
+expires_in_sec = 2592000
+
+[paper_token]
+expires_in_sec = 86400
+
+[storage]
+database = "paper"
+collection_user = "user"
+collection_paper = "paper"
+collection_comment = "comment"
+collection_export = "export"
+collection_audit = "audit"
+collection_webhook = "webhook"
+collection_webhook_delivery = "webhook_delivery"
+collection_notification = "notification"
+collection_attachment = "attachment"
+collection_quota = "quota"
+"#;
+
+/// Prefix of the environment variables overriding the config, nested keys
+/// are separated by `__` as well, e.g. `PAPER__ACCESS_TOKEN__SECRET`. The
+/// double underscore leaves out `PAPER_PORT`, `PAPER_SERVICE_HOST` and the
+/// like which Kubernetes sets for a service named paper.
+pub const ENV_PREFIX: &str = "PAPER__";
+
+/// Keys ending with this suffix name a file holding the value of the key
+/// without it, e.g. `secret_file` for `secret`.
+const FILE_SUFFIX: &str = "_file";
+
+/// The secrets which may be read from a file, `*.` matches any parent. Other
+/// keys ending with `FILE_SUFFIX` are refused, e.g. `tls.key_file` would
+/// otherwise replace the key path with the key.
+const FILE_KEYS: &[&str] = &[
+    "*.secret",
+    "*.client_secret",
+    "blob.s3.secret_access_key",
+    "storage.uri",
+];
+
+/// Whether the first key of `path` is one of `Config`, a value for another
+/// key would otherwise be silently ignored.
+fn check_key(path: &[String]) -> std::result::Result<(), String> {
+    let key = path.first().map(String::as_str).unwrap_or_default();
+    let key = key.strip_suffix(FILE_SUFFIX).unwrap_or(key);
+    match config_keys().contains(&key) {
+        true => Ok(()),
+        false => Err(format!("{} is not a config key", key)),
+    }
+}
+
+/// The top level keys of `Config`, as its `Deserialize` implementation knows
+/// them.
+fn config_keys() -> &'static [&'static str] {
+    use serde::de::{self, Visitor};
+
+    /// Keeps the fields of the struct it is asked to deserialize.
+    struct Fields<'a>(&'a mut &'static [&'static str]);
+
+    impl<'de, 'a> de::Deserializer<'de> for Fields<'a> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: Visitor<'de>>(
+            self,
+            _visitor: V,
+        ) -> std::result::Result<V::Value, Self::Error> {
+            Err(de::Error::custom("Only a struct has fields"))
+        }
+
+        fn deserialize_struct<V: Visitor<'de>>(
+            self,
+            _name: &'static str,
+            fields: &'static [&'static str],
+            _visitor: V,
+        ) -> std::result::Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("Fields kept"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf
+            option unit unit_struct newtype_struct seq tuple tuple_struct map enum
+            identifier ignored_any
+        }
+    }
+
+    let mut keys: &'static [&'static str] = &[];
+    let _ = Config::deserialize(Fields(&mut keys));
+    keys
+}
+
+impl Config {
+    /// Merge the defaults, the config `file`, the `PAPER__*` environment
+    /// variables and the `key.path=value` `overrides` in this order, then
+    /// validate the result. Variables and overrides of keys `Config` does
+    /// not have are refused.
+    ///
+    /// Values from the environment and the overrides replacing a string stay
+    /// strings, others are parsed as TOML values.
+    pub fn load(file: Option<&str>, overrides: &[String]) -> std::result::Result<Self, String> {
+        let mut config: toml::Value = DEFAULT_CONFIG.parse().expect("Invalid default config");
+
+        if let Some(file) = file {
+            let content = std::fs::read_to_string(file)
+                .map_err(|e| format!("Failed to read config file {}: {}", file, e))?;
+            let layer: toml::Value = content
+                .parse()
+                .map_err(|e| format!("Failed to parse config file {}: {}", file, e))?;
+            merge(&mut config, layer);
+        }
+
+        let mut vars: Vec<(String, String)> = std::env::vars()
+            .filter(|(key, _)| key.starts_with(ENV_PREFIX))
+            .collect();
+        vars.sort();
+        for (key, value) in vars {
+            let path: Vec<String> = key[ENV_PREFIX.len()..]
+                .split("__")
+                .map(|x| x.to_ascii_lowercase())
+                .collect();
+            check_key(&path).map_err(|e| format!("{}: {}", key, e))?;
+            set(&mut config, &path, &value).map_err(|e| format!("{}: {}", key, e))?;
+        }
+
+        for x in overrides {
+            let mut parts = x.splitn(2, '=');
+            let key = parts.next().unwrap_or_default();
+            let value = parts
+                .next()
+                .ok_or_else(|| format!("{}: expected key=value", x))?;
+            let path: Vec<String> = key.split('.').map(str::to_owned).collect();
+            check_key(&path).map_err(|e| format!("{}: {}", key, e))?;
+            set(&mut config, &path, value).map_err(|e| format!("{}: {}", key, e))?;
+        }
+
+        resolve_files(&mut config, "")?;
+
+        let config: Config = config
+            .try_into()
+            .map_err(|e| format!("Invalid config: {}", e))?;
+        config
+            .validate()
+            .map_err(|e| format!("Invalid config: {}", e))?;
+
+        Ok(config)
+    }
+
+    /// Check the values a deserialized config can still get wrong.
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        let mut problems = Vec::new();
+
+        for (name, token) in [
+            ("access_token", &self.access_token),
+            ("refresh_token", &self.refresh_token),
+            ("paper_token", &self.paper_token),
+        ]
+        .iter()
+        {
+            if token.secret.is_empty() {
+                problems.push(format!("{}.secret is empty", name));
+            }
+            if token.expires_in_sec == 0 {
+                problems.push(format!("{}.expires_in_sec is 0", name));
+            }
+        }
+
+        if self.storage.uri.is_empty() {
+            problems.push("storage.uri is empty".to_owned());
+        }
+        if self.storage.database.is_empty() {
+            problems.push("storage.database is empty".to_owned());
+        }
+
+        if self.webhook.max_attempts == 0 || self.webhook.timeout_sec == 0 {
+            problems.push(
+                "webhook.max_attempts and webhook.timeout_sec must be greater than 0".to_owned(),
+            );
+        }
+
+        if self.rate_limit.enabled {
+            for (name, bucket) in [
+                ("ip", &self.rate_limit.ip),
+                ("user", &self.rate_limit.user),
+                ("auth", &self.rate_limit.auth),
+            ]
+            .iter()
+            {
+                if bucket.capacity == 0 || bucket.refill_per_sec <= 0.0 {
+                    problems.push(format!(
+                        "rate_limit.{} needs a capacity and refill_per_sec greater than 0",
+                        name
+                    ));
+                }
+            }
+        }
+
+        if self.tls.enabled && (self.tls.cert.is_empty() || self.tls.key.is_empty()) {
+            problems.push("tls.cert and tls.key are required when tls is enabled".to_owned());
+        }
+        if self.tls.enabled && self.tls.reload_interval_sec == 0 {
+            problems.push("tls.reload_interval_sec is 0".to_owned());
+        }
+
+        if self.export.expires_in_sec == 0 || self.export.url_expires_in_sec == 0 {
+            problems.push(
+                "export.expires_in_sec and export.url_expires_in_sec must be greater than 0"
+                    .to_owned(),
+            );
+        }
+
+        if self.attachment.url_expires_in_sec == 0 {
+            problems.push("attachment.url_expires_in_sec is 0".to_owned());
+        }
+        match self.blob.store {
+            BlobStoreKind::File if self.blob.path.is_empty() => {
+                problems.push("blob.path is required by the file store".to_owned());
+            }
+            BlobStoreKind::S3
+                if self.blob.s3.endpoint.is_empty() || self.blob.s3.bucket.is_empty() =>
+            {
+                problems.push(
+                    "blob.s3.endpoint and blob.s3.bucket are required by the s3 store".to_owned(),
+                );
+            }
+            BlobStoreKind::S3 if self.blob.s3.timeout_sec == 0 => {
+                problems.push("blob.s3.timeout_sec is 0".to_owned());
+            }
+            _ => {}
+        }
+
+        if !self.quota.plans.contains_key(&self.quota.default_plan) {
+            problems.push(format!(
+                "quota.default_plan {} is not in quota.plans",
+                self.quota.default_plan
+            ));
+        }
+        for (user_id, user) in self.quota.users.iter() {
+            if let Some(plan) = &user.plan {
+                if !self.quota.plans.contains_key(plan) {
+                    problems.push(format!(
+                        "quota.users.{}.plan {} is not in quota.plans",
+                        user_id, plan
+                    ));
+                }
+            }
+        }
+
+        if self.admin.impersonation_expires_in_sec == 0 {
+            problems.push("admin.impersonation_expires_in_sec is 0".to_owned());
+        }
+
+        if let Err(e) = self.cors.validate() {
+            problems.push(e);
+        }
+
+        if !(0.0..=1.0).contains(&self.telemetry.sample_ratio) {
+            problems.push("telemetry.sample_ratio must be between 0 and 1".to_owned());
+        }
+
+        if self.query_limits.max_depth == 0
+            || self.query_limits.max_complexity == 0
+            || self.query_limits.max_page_size == 0
+        {
+            problems.push("query_limits must be greater than 0".to_owned());
+        }
+
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(problems.join(", ")),
+        }
+    }
+
+    /// This config with the settings rotatable without a restart taken from
+    /// `new`: the OAuth clients, token lifetimes, admins, account deletion
+    /// grace period, audit and notification retention, webhook limits, quotas,
+    /// CORS origins and logging.
+    pub fn reload(&self, new: Config) -> Self {
+        let mut config = self.clone();
+
+        config.github_auth = new.github_auth;
+        config.google_auth = new.google_auth;
+        config.access_token.expires_in_sec = new.access_token.expires_in_sec;
+        config.refresh_token.expires_in_sec = new.refresh_token.expires_in_sec;
+        config.paper_token.expires_in_sec = new.paper_token.expires_in_sec;
+        config.admin = new.admin;
+        config.account = new.account;
+        config.audit = new.audit;
+        config.webhook = new.webhook;
+        config.notification = new.notification;
+        config.quota = new.quota;
+        config.cors.enabled = new.cors.enabled;
+        config.cors.allowed_origins = new.cors.allowed_origins;
+        config.log_level = new.log_level;
+        config.log = new.log;
+
+        config
+    }
+}
+
+/// Merge the tables of `layer` into `config`, other values are replaced.
+fn merge(config: &mut toml::Value, layer: toml::Value) {
+    match (config, layer) {
+        (toml::Value::Table(config), toml::Value::Table(layer)) => {
+            for (key, value) in layer {
+                match config.get_mut(&key) {
+                    Some(x) => merge(x, value),
+                    None => {
+                        config.insert(key, value);
+                    }
+                }
+            }
+        }
+        (config, layer) => *config = layer,
+    }
+}
+
+/// Set the value at `path`, numeric segments index arrays.
+fn set(config: &mut toml::Value, path: &[String], value: &str) -> std::result::Result<(), String> {
+    let (key, rest) = match path.split_first() {
+        Some(x) => x,
+        None => return Err("empty key".to_owned()),
+    };
+
+    let next = match config {
+        toml::Value::Table(table) => {
+            if !rest.is_empty() && !table.contains_key(key) {
+                table.insert(key.to_owned(), toml::Value::Table(Default::default()));
+            }
+            if rest.is_empty() {
+                let value = parse_value(table.get(key), value);
+                table.insert(key.to_owned(), value);
+                return Ok(());
+            }
+            table.get_mut(key)
+        }
+        toml::Value::Array(array) => {
+            let index: usize = key
+                .parse()
+                .map_err(|_| format!("{} is not an array index", key))?;
+            if index > array.len() {
+                return Err(format!("index {} is out of bounds", index));
+            }
+            if index == array.len() {
+                array.push(toml::Value::Table(Default::default()));
+            }
+            if rest.is_empty() {
+                array[index] = parse_value(array.get(index), value);
+                return Ok(());
+            }
+            array.get_mut(index)
+        }
+        _ => None,
+    };
+
+    match next {
+        Some(next) => set(next, rest, value),
+        None => Err(format!("{} is not a table or an array", key)),
+    }
+}
+
+fn parse_value(current: Option<&toml::Value>, value: &str) -> toml::Value {
+    if let Some(toml::Value::String(_)) = current {
+        return toml::Value::String(value.to_owned());
+    }
+
+    format!("value = {}", value)
+        .parse::<toml::Value>()
+        .ok()
+        .and_then(|mut x| x.as_table_mut().and_then(|x| x.remove("value")))
+        .unwrap_or_else(|| toml::Value::String(value.to_owned()))
+}
+
+fn is_file_key(key: &str) -> bool {
+    FILE_KEYS.iter().any(|x| match x.strip_prefix("*.") {
+        Some(name) => key == name || key.ends_with(&format!(".{}", name)),
+        None => key == *x,
+    })
+}
+
+/// Replace every `<key>_file` of `FILE_KEYS` with `<key>` holding the content
+/// of the file.
+fn resolve_files(config: &mut toml::Value, path: &str) -> std::result::Result<(), String> {
+    match config {
+        toml::Value::Table(table) => {
+            let files: Vec<String> = table
+                .keys()
+                .filter(|x| x.ends_with(FILE_SUFFIX))
+                .cloned()
+                .collect();
+
+            for key in files {
+                let name = key[..key.len() - FILE_SUFFIX.len()].to_owned();
+                if !is_file_key(&format!("{}{}", path, name)) {
+                    return Err(format!("{}{} can not be read from a file", path, name));
+                }
+                let file = match table.remove(&key) {
+                    Some(toml::Value::String(file)) => file,
+                    _ => return Err(format!("{}{} must be a file path", path, key)),
+                };
+                let content = std::fs::read_to_string(&file)
+                    .map_err(|e| format!("Failed to read {}{} {}: {}", path, key, file, e))?;
+                table.insert(
+                    name,
+                    toml::Value::String(content.trim_end_matches(&['\r', '\n'][..]).to_owned()),
+                );
+            }
+
+            for (key, value) in table.iter_mut() {
+                resolve_files(value, &format!("{}{}.", path, key))?;
+            }
+        }
+        toml::Value::Array(array) => {
+            for (index, value) in array.iter_mut().enumerate() {
+                resolve_files(value, &format!("{}{}.", path, index))?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigAdmin {
+    /// Users made admins at startup and when they sign up, as
+    /// `github:<github user id>`, `google:<google user id>` or a user id.
+    pub bootstrap_admins: Vec<String>,
+
+    /// Lifetime of the access tokens of impersonated users.
+    pub impersonation_expires_in_sec: u64,
+}
+
+impl Default for ConfigAdmin {
+    fn default() -> Self {
+        Self {
+            bootstrap_admins: Vec::new(),
+            impersonation_expires_in_sec: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigAccount {
+    /// Seconds a deleted account can be restored before it is purged.
+    pub deletion_grace_period_sec: u64,
+}
+
+impl Default for ConfigAccount {
+    fn default() -> Self {
+        Self {
+            deletion_grace_period_sec: 2592000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigExport {
+    /// Seconds a data export is kept once it has been assembled.
+    pub expires_in_sec: u64,
+
+    /// Lifetime of the signed download URLs.
+    pub url_expires_in_sec: u64,
+
+    /// Signs the download URLs, derived from the access token secret when
+    /// empty.
+    pub secret: String,
+
+    /// Prepended to the `/exports/` path of the download URLs, relative URLs
+    /// are returned when empty.
+    pub base_url: String,
+}
+
+impl Default for ConfigExport {
+    fn default() -> Self {
+        Self {
+            expires_in_sec: 604800,
+            url_expires_in_sec: 3600,
+            secret: String::new(),
+            base_url: String::new(),
+        }
+    }
+}
+
+impl ConfigExport {
+    pub fn secret(&self, access_token_secret: &str) -> String {
+        match self.secret.is_empty() {
+            true => format!("{}:export", access_token_secret),
+            false => self.secret.to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigAudit {
+    /// Days the audit events are kept, 0 keeps them forever.
+    pub retention_days: u64,
+}
+
+impl Default for ConfigAudit {
+    fn default() -> Self {
+        Self {
+            retention_days: 365,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigNotification {
+    /// Days the notifications are kept, read or not, 0 keeps them forever.
+    pub retention_days: u64,
+}
+
+impl Default for ConfigNotification {
+    fn default() -> Self {
+        Self { retention_days: 90 }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigAttachment {
+    /// Bytes of an attachment, 0 is unlimited. The attachments of a user are
+    /// bounded by `quota`.
+    pub max_size_bytes: u64,
+
+    /// Lifetime of the signed upload and download URLs, a pending attachment
+    /// is removed once its upload URL has expired.
+    pub url_expires_in_sec: u64,
+
+    /// Signs the URLs, derived from the access token secret when empty.
+    pub secret: String,
+
+    /// Prepended to the `/attachments/` path of the URLs, relative URLs are
+    /// returned when empty.
+    pub base_url: String,
+}
+
+impl Default for ConfigAttachment {
+    fn default() -> Self {
+        Self {
+            max_size_bytes: 10485760,
+            url_expires_in_sec: 3600,
+            secret: String::new(),
+            base_url: String::new(),
+        }
+    }
+}
+
+impl ConfigAttachment {
+    pub fn secret(&self, access_token_secret: &str) -> String {
+        match self.secret.is_empty() {
+            true => format!("{}:attachment", access_token_secret),
+            false => self.secret.to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigBlob {
+    pub store: BlobStoreKind,
+
+    /// Directory of the file store.
+    pub path: String,
+
+    pub s3: ConfigBlobS3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlobStoreKind {
+    File,
+    S3,
+}
+
+impl Default for ConfigBlob {
+    fn default() -> Self {
+        Self {
+            store: BlobStoreKind::File,
+            path: "blobs".to_owned(),
+            s3: ConfigBlobS3::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigBlobS3 {
+    /// e.g. `https://s3.eu-west-1.amazonaws.com` or `http://127.0.0.1:9000`,
+    /// buckets are addressed path-style.
+    pub endpoint: String,
+
+    pub region: String,
+
+    pub bucket: String,
+
+    pub access_key_id: String,
+
+    pub secret_access_key: String,
+
+    /// Seconds a request to the store is given to complete.
+    pub timeout_sec: u64,
+}
+
+impl Default for ConfigBlobS3 {
+    fn default() -> Self {
+        Self {
+            endpoint: String::new(),
+            region: "us-east-1".to_owned(),
+            bucket: String::new(),
+            access_key_id: String::new(),
+            secret_access_key: String::new(),
+            timeout_sec: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigQuota {
+    /// The plan of the users without one in `users`.
+    pub default_plan: String,
+
+    /// Limits by plan name.
+    pub plans: HashMap<String, ConfigQuotaLimits>,
+
+    /// Plans and limits replacing those of the plan by user id.
+    pub users: HashMap<String, ConfigQuotaUser>,
+}
+
+impl Default for ConfigQuota {
+    fn default() -> Self {
+        Self {
+            default_plan: "free".to_owned(),
+            plans: vec![("free".to_owned(), ConfigQuotaLimits::default())]
+                .into_iter()
+                .collect(),
+            users: HashMap::new(),
+        }
+    }
+}
+
+/// 0 is unlimited.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigQuotaLimits {
+    /// Papers not deleted.
+    pub max_papers: u64,
+
+    /// Bytes of the attachments of the papers of a user.
+    pub max_attachment_storage_bytes: u64,
+
+    /// Requests to the API per UTC day.
+    pub max_api_requests_per_day: u64,
+}
+
+impl Default for ConfigQuotaLimits {
+    fn default() -> Self {
+        Self {
+            max_papers: 1000,
+            max_attachment_storage_bytes: 104857600,
+            max_api_requests_per_day: 100000,
+        }
+    }
+}
+
+impl From<&ConfigQuotaLimits> for paper::quota::QuotaLimits {
+    fn from(v: &ConfigQuotaLimits) -> Self {
+        Self {
+            max_papers: v.max_papers,
+            max_attachment_storage: v.max_attachment_storage_bytes,
+            max_api_requests_per_day: v.max_api_requests_per_day,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigQuotaUser {
+    pub plan: Option<String>,
+
+    pub max_papers: Option<u64>,
+
+    pub max_attachment_storage_bytes: Option<u64>,
+
+    pub max_api_requests_per_day: Option<u64>,
+}
+
+impl From<&ConfigQuotaUser> for paper::quota::QuotaOverride {
+    fn from(v: &ConfigQuotaUser) -> Self {
+        Self {
+            plan: v.plan.to_owned(),
+            max_papers: v.max_papers,
+            max_attachment_storage: v.max_attachment_storage_bytes,
+            max_api_requests_per_day: v.max_api_requests_per_day,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigWebhook {
+    /// Webhooks a user can register.
+    pub max_per_user: u64,
+
+    /// Attempts of a delivery before it is given up.
+    pub max_attempts: u32,
+
+    /// Seconds before the first retry, doubled after each failed attempt.
+    pub retry_base_sec: u64,
+
+    /// Seconds an endpoint is given to respond.
+    pub timeout_sec: u64,
+
+    /// Days the delivery log is kept, 0 keeps it forever.
+    pub delivery_retention_days: u64,
+
+    /// Hosts webhooks may be delivered to although they resolve to a
+    /// loopback, private or link-local address.
+    pub allowed_hosts: Vec<String>,
+}
+
+impl Default for ConfigWebhook {
+    fn default() -> Self {
+        Self {
+            max_per_user: 10,
+            max_attempts: 8,
+            retry_base_sec: 30,
+            timeout_sec: 10,
+            delivery_retention_days: 30,
+            allowed_hosts: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigTls {
+    /// Serve HTTPS, and HTTP/2 negotiated with ALPN, instead of plain HTTP.
+    pub enabled: bool,
+
+    /// Path of the PEM certificate chain, leaf first.
+    pub cert: String,
+
+    /// Path of the PEM private key, PKCS#8 or PKCS#1 RSA.
+    pub key: String,
+
+    /// Seconds between checks of the certificate and key files for changes.
+    pub reload_interval_sec: u64,
+
+    /// Path of the PEM CA certificates client certificates are verified with,
+    /// enables mutual TLS.
+    pub client_ca: Option<String>,
+
+    /// Reject clients without a certificate, otherwise it is optional.
+    pub client_auth_required: bool,
+}
+
+impl Default for ConfigTls {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cert: String::new(),
+            key: String::new(),
+            reload_interval_sec: 60,
+            client_ca: None,
+            client_auth_required: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigCors {
+    /// Cross-origin requests are only handled when enabled at startup.
+    pub enabled: bool,
+
+    /// Origins like `https://app.example.com`, or `https://*.example.com`
+    /// for any subdomain of `example.com`.
+    pub allowed_origins: Vec<String>,
+
+    pub allowed_methods: Vec<String>,
+
+    pub allowed_headers: Vec<String>,
+
+    /// Allow cookies and the `Authorization` header to be sent along.
+    pub allow_credentials: bool,
+
+    /// Seconds browsers may cache the result of a preflight request.
+    pub max_age_sec: usize,
+}
+
+impl Default for ConfigCors {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            allowed_origins: Vec::new(),
+            allowed_methods: vec!["GET".to_owned(), "POST".to_owned()],
+            allowed_headers: vec![
+                "Authorization".to_owned(),
+                "Content-Type".to_owned(),
+                "X-Request-Id".to_owned(),
+                "traceparent".to_owned(),
+                "tracestate".to_owned(),
+            ],
+            allow_credentials: false,
+            max_age_sec: 3600,
+        }
+    }
+}
+
+impl ConfigCors {
+    fn validate(&self) -> std::result::Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        if self.allowed_origins.is_empty() {
+            return Err("cors.allowed_origins is empty".to_owned());
+        }
+        for origin in self.allowed_origins.iter() {
+            crate::cors::validate_origin(origin)
+                .map_err(|e| format!("cors.allowed_origins {}: {}", origin, e))?;
+        }
+        for method in self.allowed_methods.iter() {
+            actix_web::http::Method::from_bytes(method.as_bytes())
+                .map_err(|_| format!("cors.allowed_methods {} is not a method", method))?;
+        }
+        for header in self.allowed_headers.iter() {
+            actix_web::http::HeaderName::from_bytes(header.as_bytes())
+                .map_err(|_| format!("cors.allowed_headers {} is not a header name", header))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigSecurityHeaders {
+    /// `max-age` of the `Strict-Transport-Security` header, 0 leaves it out.
+    pub hsts_max_age_sec: u64,
+
+    pub hsts_include_subdomains: bool,
+}
+
+impl Default for ConfigSecurityHeaders {
+    fn default() -> Self {
+        Self {
+            hsts_max_age_sec: 31536000,
+            hsts_include_subdomains: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ConfigLog {
+    pub format: LogFormat,
+
+    /// Levels of module paths overriding `log_level`, e.g. `paper_impl`.
+    pub modules: HashMap<String, ModuleLevelFilter>,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -41,6 +975,22 @@ pub struct ConfigStorage {
     pub collection_user: String,
 
     pub collection_paper: String,
+
+    pub collection_comment: String,
+
+    pub collection_export: String,
+
+    pub collection_audit: String,
+
+    pub collection_webhook: String,
+
+    pub collection_webhook_delivery: String,
+
+    pub collection_notification: String,
+
+    pub collection_attachment: String,
+
+    pub collection_quota: String,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -58,3 +1008,166 @@ pub struct ConfigGoogleAuth {
 
     pub redirect_uri: String,
 }
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigRateLimit {
+    pub enabled: bool,
+
+    /// Take the client ip from `X-Forwarded-For`/`Forwarded`, only enable it
+    /// behind a trusted reverse proxy.
+    pub trust_proxy: bool,
+
+    /// Requests without a valid access token, keyed by client ip.
+    pub ip: ConfigTokenBucket,
+
+    /// Requests with a valid access token, keyed by user id.
+    pub user: ConfigTokenBucket,
+
+    /// `createAccessToken` calls, keyed by client ip.
+    pub auth: ConfigTokenBucket,
+}
+
+impl Default for ConfigRateLimit {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            trust_proxy: false,
+            ip: ConfigTokenBucket {
+                capacity: 300,
+                refill_per_sec: 5.0,
+            },
+            user: ConfigTokenBucket {
+                capacity: 600,
+                refill_per_sec: 10.0,
+            },
+            auth: ConfigTokenBucket {
+                capacity: 10,
+                refill_per_sec: 1.0 / 60.0,
+            },
+        }
+    }
+}
+
+/// A bucket holds at most `capacity` tokens and regains `refill_per_sec`
+/// tokens every second, every request takes one token.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ConfigTokenBucket {
+    pub capacity: u32,
+
+    pub refill_per_sec: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigQueryLimits {
+    /// Maximum nesting of selection sets.
+    pub max_depth: usize,
+
+    /// Maximum cost of a query, every field costs one and the cost of a
+    /// connection is multiplied by its page size.
+    pub max_complexity: u64,
+
+    /// Maximum `first`/`last` of a connection.
+    pub max_page_size: u64,
+}
+
+impl Default for ConfigQueryLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 12,
+            max_complexity: 10_000,
+            max_page_size: 100,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigPersistedQueries {
+    /// Accept Apollo automatic persisted queries.
+    pub enabled: bool,
+
+    /// Only execute the queries of the manifest.
+    pub allow_list_only: bool,
+
+    /// Path of a JSON object mapping sha256 hashes to queries, loaded at
+    /// startup.
+    pub manifest: Option<String>,
+
+    /// Maximum number of queries registered by clients.
+    pub cache_size: usize,
+
+    /// `max-age` of the `Cache-Control` header of anonymous `GET` responses.
+    pub get_max_age_sec: u64,
+}
+
+impl Default for ConfigPersistedQueries {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            allow_list_only: false,
+            manifest: None,
+            cache_size: 1000,
+            get_max_age_sec: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ConfigTelemetry {
+    pub enabled: bool,
+
+    pub exporter: TelemetryExporter,
+
+    /// gRPC endpoint of the OTLP collector.
+    pub endpoint: String,
+
+    pub service_name: String,
+
+    /// Fraction of the traces started by this service that are recorded,
+    /// traces continued from a caller follow its sampling decision.
+    pub sample_ratio: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TelemetryExporter {
+    Otlp,
+    Stdout,
+}
+
+impl Default for ConfigTelemetry {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            exporter: TelemetryExporter::Otlp,
+            endpoint: "http://localhost:4317".to_owned(),
+            service_name: "paper".to_owned(),
+            sample_ratio: 1.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(key: &str) -> Vec<String> {
+        key.split('.').map(str::to_owned).collect()
+    }
+
+    #[test]
+    fn check_keys() {
+        assert_eq!(check_key(&path("port")), Ok(()));
+        assert_eq!(check_key(&path("access_token.secret")), Ok(()));
+        assert_eq!(check_key(&path("query_limits.max_depth")), Ok(()));
+
+        // Set by Kubernetes for a service named paper, were the prefix single.
+        assert_eq!(
+            check_key(&path("service_host")),
+            Err("service_host is not a config key".to_owned())
+        );
+    }
+}
@@ -1,24 +1,124 @@
 use std::sync::Arc;
 
-use juniper::{EmptySubscription, RootNode};
-use paper::auth::AccessTokenPayload;
+use juniper::RootNode;
+use paper::{
+    audit::{AuditEventInput, AuditService},
+    auth::AccessTokenPayload,
+    user::{UserIdentifier, UserService},
+};
+use paper_impl::auth::AccessTokenConfigInterface;
+use shaku::{HasComponent, HasProvider};
 
-use crate::*;
+use crate::{loader::Loaders, metrics::WebSocketConnection, rate_limit::RateLimiter, *};
 
-pub type Schema = RootNode<'static, Query, Mutation, EmptySubscription<Context>>;
+pub type Schema = RootNode<'static, Query, Mutation, Subscription>;
 
 pub struct Context {
     pub module: Arc<Module>,
     pub access_token: Option<String>,
+    pub client_ip: String,
+    pub user_agent: Option<String>,
+    pub rate_limiter: Arc<RateLimiter>,
+    pub loaders: Loaders,
+    max_page_size: u64,
+    websocket: Option<WebSocketConnection>,
 }
 
 impl juniper::Context for Context {}
 
 impl Context {
-    pub fn access_token(&self) -> Result<AccessTokenPayload> {
-        use paper_impl::auth::AccessTokenConfigInterface;
-        use shaku::HasComponent;
+    pub fn new(
+        module: Arc<Module>,
+        access_token: Option<String>,
+        client_ip: String,
+        user_agent: Option<String>,
+        rate_limiter: Arc<RateLimiter>,
+    ) -> Self {
+        let access_token_config: &dyn AccessTokenConfigInterface = module.resolve_ref();
+        let viewer_id = access_token
+            .as_ref()
+            .and_then(|x| AccessTokenPayload::decode(x, &access_token_config.secret).ok())
+            .map(|x| x.sub);
+
+        Self {
+            loaders: Loaders::new(module.clone(), viewer_id),
+            module,
+            access_token,
+            client_ip,
+            user_agent,
+            rate_limiter,
+            max_page_size: ConfigQueryLimits::default().max_page_size,
+            websocket: None,
+        }
+    }
+
+    /// Cap the page sizes of connections to `max_page_size`.
+    pub fn with_max_page_size(mut self, max_page_size: u64) -> Self {
+        self.max_page_size = max_page_size;
+        self
+    }
+
+    /// The `first` or `last` of a connection capped to the maximum page size,
+    /// whatever the limits checked before execution have seen.
+    pub fn page_size(&self, size: i32) -> Result<u64> {
+        match size {
+            x if x < 0 => Err(Error::invalid_input(format!("Page size {} is negative", x))),
+            x => Ok((x as u64).min(self.max_page_size)),
+        }
+    }
+
+    /// The `skip` of a connection.
+    pub fn page_skip(&self, skip: Option<i32>) -> Result<Option<u64>> {
+        match skip {
+            Some(x) if x < 0 => Err(Error::invalid_input(format!("Skip {} is negative", x))),
+            x => Ok(x.map(|x| x as u64)),
+        }
+    }
+
+    /// Count the context as an active subscription connection while it lives.
+    pub fn with_websocket_connection(mut self) -> Self {
+        self.websocket = Some(WebSocketConnection::open());
+        self
+    }
 
+    /// The access token of the request, its user must be able to sign in and
+    /// it must not be issued before their sessions have been revoked.
+    pub async fn access_token(&self) -> Result<AccessTokenPayload> {
+        let payload = self.decode_access_token()?;
+
+        let user = self
+            .loaders
+            .user
+            .load(payload.sub.to_owned())
+            .await?
+            .ok_or_else(|| Error::unauthorized("User not found".to_owned()))?;
+        user.can_sign_in(Some(payload.iat))?;
+
+        Ok(payload)
+    }
+
+    /// The access token of the request, its user is selected again instead of
+    /// being taken from the loader. A subscription connection keeps its
+    /// loader, the user it has cached may have been disabled since.
+    pub async fn verify_access_token(&self) -> Result<AccessTokenPayload> {
+        let payload = self.decode_access_token()?;
+
+        let user_service: Box<dyn UserService> = self.module.provide().unwrap();
+        let user = user_service
+            .select_account(UserIdentifier::Id(payload.sub.to_owned()))
+            .await
+            .map_err(|e| match e.kind {
+                paper::ErrorKind::NotFound => Error::unauthorized("User not found".to_owned()),
+                _ => e.into(),
+            })?;
+        user.can_sign_in(Some(payload.iat))?;
+
+        Ok(payload)
+    }
+
+    /// The access token of the request without checking the state of its
+    /// user, for logging and auditing.
+    pub fn decode_access_token(&self) -> Result<AccessTokenPayload> {
         let access_token_config: &dyn AccessTokenConfigInterface = self.module.resolve_ref();
         self.access_token
             .as_ref()
@@ -29,4 +129,22 @@ impl Context {
                     .map_err(|e| Error::unauthorized(e.to_string()))
             })
     }
+
+    /// Record an audit event of this request, the viewer is the actor unless
+    /// `input` names one. A failure is logged and does not fail the request.
+    pub async fn audit(&self, mut input: AuditEventInput) {
+        if input.actor_id.is_none() {
+            if let Ok(access_token) = self.decode_access_token() {
+                input.actor_id = Some(access_token.sub);
+                input.impersonator_id = access_token.act;
+            }
+        }
+        input.ip = Some(self.client_ip.to_owned());
+        input.user_agent = self.user_agent.to_owned();
+
+        let audit_service: Box<dyn AuditService> = self.module.provide().unwrap();
+        if let Err(e) = audit_service.record(input).await {
+            log::error!("Failed to record audit event: {}", e);
+        }
+    }
 }
@@ -0,0 +1,83 @@
+use std::sync::Arc;
+
+use actix_cors::Cors;
+
+use crate::{
+    config::{Config, ConfigCors},
+    reload::Reloadable,
+};
+
+/// CORS for the `cors` section of the config, origins are looked up in the
+/// current config so that they follow reloads.
+pub fn cors(config: &ConfigCors, current: Arc<Reloadable<Config>>) -> Cors {
+    let cors = Cors::default()
+        .allowed_origin_fn(move |origin, _| {
+            let config = current.load();
+            config.cors.enabled
+                && origin
+                    .to_str()
+                    .map(|x| is_origin_allowed(&config.cors.allowed_origins, x))
+                    .unwrap_or(false)
+        })
+        .allowed_methods(config.allowed_methods.iter().map(String::as_str))
+        .allowed_headers(config.allowed_headers.iter().map(String::as_str))
+        .expose_headers(vec!["X-Request-Id"])
+        .max_age(config.max_age_sec);
+
+    match config.allow_credentials {
+        true => cors.supports_credentials(),
+        false => cors,
+    }
+}
+
+/// Whether `origin` equals one of `allowed_origins`, or is a subdomain of a
+/// wildcard one like `https://*.example.com`.
+pub fn is_origin_allowed(allowed_origins: &[String], origin: &str) -> bool {
+    allowed_origins.iter().any(|x| origin_matches(x, origin))
+}
+
+fn origin_matches(pattern: &str, origin: &str) -> bool {
+    if pattern.eq_ignore_ascii_case(origin) {
+        return true;
+    }
+
+    let index = match pattern.find("://*.") {
+        Some(index) => index,
+        None => return false,
+    };
+    let scheme = pattern[..index + 3].as_bytes();
+    let suffix = pattern[index + 4..].as_bytes();
+    let origin = origin.as_bytes();
+    if origin.len() <= scheme.len() + suffix.len() {
+        return false;
+    }
+
+    let subdomain = &origin[scheme.len()..origin.len() - suffix.len()];
+
+    origin[..scheme.len()].eq_ignore_ascii_case(scheme)
+        && origin[origin.len() - suffix.len()..].eq_ignore_ascii_case(suffix)
+        && subdomain.first() != Some(&b'.')
+        && subdomain
+            .iter()
+            .all(|x| x.is_ascii_alphanumeric() || *x == b'-' || *x == b'.')
+}
+
+/// Check an allowed origin is a scheme and a host, the host may start with a
+/// `*.` wildcard.
+pub fn validate_origin(origin: &str) -> std::result::Result<(), String> {
+    let host = origin
+        .strip_prefix("https://")
+        .or_else(|| origin.strip_prefix("http://"))
+        .ok_or_else(|| "must start with http:// or https://".to_owned())?;
+    let host = host.strip_prefix("*.").unwrap_or(host);
+
+    let valid = !host.is_empty()
+        && !host.starts_with('.')
+        && host
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-.:[]".contains(c));
+    match valid {
+        true => Ok(()),
+        false => Err("must be a host with an optional port and no path".to_owned()),
+    }
+}
@@ -0,0 +1,65 @@
+use std::convert::TryFrom;
+
+use chrono::{SecondsFormat, TimeZone, Utc};
+use juniper::GraphQLInputObject;
+
+/// Milliseconds since the unix epoch, represented as an RFC 3339 string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DateTime(pub u64);
+
+impl DateTime {
+    pub fn to_rfc3339(&self) -> String {
+        Utc.timestamp_millis(self.0 as i64)
+            .to_rfc3339_opts(SecondsFormat::Millis, true)
+    }
+
+    pub fn parse_rfc3339(v: &str) -> Option<Self> {
+        chrono::DateTime::parse_from_rfc3339(v)
+            .ok()
+            .and_then(|x| u64::try_from(x.timestamp_millis()).ok())
+            .map(Self)
+    }
+}
+
+impl From<u64> for DateTime {
+    fn from(v: u64) -> Self {
+        Self(v)
+    }
+}
+
+#[juniper::graphql_scalar(description = "RFC 3339 date time, e.g. 2021-06-01T08:00:00.000Z")]
+impl<S> GraphQLScalar for DateTime
+where
+    S: ScalarValue,
+{
+    fn resolve(&self) -> Value {
+        juniper::Value::scalar(self.to_rfc3339())
+    }
+
+    fn from_input_value(v: &InputValue) -> Option<Self> {
+        v.as_scalar_value()
+            .and_then(|v| v.as_str())
+            .and_then(Self::parse_rfc3339)
+    }
+
+    fn from_str<'a>(value: ScalarToken<'a>) -> juniper::ParseScalarResult<'a, S> {
+        <String as juniper::ParseScalarValue<S>>::from_str(value)
+    }
+}
+
+/// `from` is inclusive and `to` is exclusive, an absent bound is unbounded.
+#[derive(GraphQLInputObject)]
+pub struct DateTimeRange {
+    pub from: Option<DateTime>,
+
+    pub to: Option<DateTime>,
+}
+
+impl Into<paper::TimeRange> for DateTimeRange {
+    fn into(self) -> paper::TimeRange {
+        paper::TimeRange {
+            from: self.from.map(|x| x.0),
+            to: self.to.map(|x| x.0),
+        }
+    }
+}
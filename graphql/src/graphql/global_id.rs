@@ -0,0 +1,37 @@
+use juniper::ID;
+
+/// Relay global object identifier, encoded as the base64 of `<type>:<id>` so
+/// ids of different types never collide.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalId {
+    pub type_name: String,
+
+    pub id: String,
+}
+
+impl GlobalId {
+    pub fn new(type_name: impl Into<String>, id: impl Into<String>) -> Self {
+        Self {
+            type_name: type_name.into(),
+            id: id.into(),
+        }
+    }
+
+    pub fn encode(&self) -> ID {
+        base64::encode_config(format!("{}:{}", self.type_name, self.id), base64::URL_SAFE).into()
+    }
+
+    pub fn decode(v: &str) -> Option<Self> {
+        let v = base64::decode_config(v, base64::URL_SAFE)
+            .ok()
+            .and_then(|v| String::from_utf8(v).ok())?;
+
+        let mut parts = v.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            (Some(type_name), Some(id)) if !type_name.is_empty() && !id.is_empty() => {
+                Some(Self::new(type_name, id))
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,847 @@
+use std::{collections::HashMap, iter::Peekable, str::Chars};
+
+use crate::ConfigQueryLimits;
+
+/// A query rejected before execution.
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryLimitError {
+    Syntax(String),
+
+    TooDeep { depth: usize, max: usize },
+
+    TooComplex { complexity: u64, max: u64 },
+
+    PageTooLarge { field: String, size: u64, max: u64 },
+
+    NegativePageSize { field: String, size: i64 },
+}
+
+impl std::fmt::Display for QueryLimitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryLimitError::Syntax(message) => write!(f, "Invalid query: {}", message),
+            QueryLimitError::TooDeep { depth, max } => {
+                write!(f, "Query depth {} exceeds the maximum of {}", depth, max)
+            }
+            QueryLimitError::TooComplex { complexity, max } => write!(
+                f,
+                "Query complexity {} exceeds the maximum of {}",
+                complexity, max
+            ),
+            QueryLimitError::PageTooLarge { field, size, max } => write!(
+                f,
+                "Page size {} of {} exceeds the maximum of {}",
+                size, field, max
+            ),
+            QueryLimitError::NegativePageSize { field, size } => {
+                write!(f, "Page size {} of {} is negative", size, field)
+            }
+        }
+    }
+}
+
+impl std::error::Error for QueryLimitError {}
+
+/// Check depth, complexity and page sizes of the operation of `query` that
+/// would be executed. Every field costs one, the cost of the sub selection of
+/// a field with a `first` or `last` argument is multiplied by the page size.
+/// Introspection fields are not counted.
+pub fn check_query_limits(
+    limits: &ConfigQueryLimits,
+    query: &str,
+    operation_name: Option<&str>,
+    variables: Option<&serde_json::Value>,
+) -> Result<(), QueryLimitError> {
+    let document = Parser::new(query).parse_document()?;
+    let operation = document.operation(operation_name)?;
+
+    let mut measure = Measure {
+        limits,
+        fragments: &document.fragments,
+        variables,
+        measured_fragments: HashMap::new(),
+    };
+
+    let (depth, complexity) = measure.selections(&operation.selections, 1, &mut vec![])?;
+
+    if depth > limits.max_depth {
+        return Err(QueryLimitError::TooDeep {
+            depth,
+            max: limits.max_depth,
+        });
+    }
+
+    if complexity > limits.max_complexity {
+        return Err(QueryLimitError::TooComplex {
+            complexity,
+            max: limits.max_complexity,
+        });
+    }
+
+    Ok(())
+}
+
+struct Measure<'a> {
+    limits: &'a ConfigQueryLimits,
+
+    fragments: &'a HashMap<String, Vec<Selection>>,
+
+    variables: Option<&'a serde_json::Value>,
+
+    /// The depth below where they are spread and the complexity of the
+    /// fragments measured so far, a fragment is walked once however many
+    /// times it is spread.
+    measured_fragments: HashMap<&'a str, (usize, u64)>,
+}
+
+impl<'a> Measure<'a> {
+    /// Returns the depth and the complexity of a selection set at `depth`.
+    /// The walk stops as soon as a selection set goes over the maximum
+    /// complexity, even one under a page size of 0.
+    fn selections(
+        &mut self,
+        selections: &'a [Selection],
+        depth: usize,
+        spreads: &mut Vec<&'a str>,
+    ) -> Result<(usize, u64), QueryLimitError> {
+        if depth > self.limits.max_depth {
+            return Err(QueryLimitError::TooDeep {
+                depth,
+                max: self.limits.max_depth,
+            });
+        }
+
+        let mut max_depth = depth;
+        let mut complexity: u64 = 0;
+
+        for selection in selections {
+            let (d, c) = match selection {
+                Selection::Field {
+                    name,
+                    arguments,
+                    selections,
+                } => {
+                    if name.starts_with("__") {
+                        continue;
+                    }
+
+                    let page_size = self.page_size(name, arguments)?;
+                    let (d, c) = match selections.is_empty() {
+                        true => (depth, 0),
+                        false => self.selections(selections, depth + 1, spreads)?,
+                    };
+
+                    (
+                        d,
+                        c.saturating_mul(page_size.unwrap_or(1)).saturating_add(1),
+                    )
+                }
+                Selection::InlineFragment { selections } => {
+                    self.selections(selections, depth, spreads)?
+                }
+                Selection::FragmentSpread { name } => {
+                    if let Some(&(below, c)) = self.measured_fragments.get(name.as_str()) {
+                        let d = depth + below;
+                        if d > self.limits.max_depth {
+                            return Err(QueryLimitError::TooDeep {
+                                depth: d,
+                                max: self.limits.max_depth,
+                            });
+                        }
+                        (d, c)
+                    } else if spreads.contains(&name.as_str()) {
+                        return Err(QueryLimitError::Syntax(format!(
+                            "Fragment {} spreads itself",
+                            name
+                        )));
+                    } else {
+                        let fragments = self.fragments;
+                        let selections = fragments.get(name).ok_or_else(|| {
+                            QueryLimitError::Syntax(format!("Unknown fragment {}", name))
+                        })?;
+
+                        spreads.push(name.as_str());
+                        let (d, c) = self.selections(selections, depth, spreads)?;
+                        spreads.pop();
+
+                        self.measured_fragments
+                            .insert(name.as_str(), (d - depth, c));
+                        (d, c)
+                    }
+                }
+            };
+
+            max_depth = max_depth.max(d);
+            complexity = complexity.saturating_add(c);
+
+            if complexity > self.limits.max_complexity {
+                return Err(QueryLimitError::TooComplex {
+                    complexity,
+                    max: self.limits.max_complexity,
+                });
+            }
+        }
+
+        Ok((max_depth, complexity))
+    }
+
+    fn page_size(
+        &self,
+        field: &str,
+        arguments: &[(String, Value)],
+    ) -> Result<Option<u64>, QueryLimitError> {
+        let mut page_size = None;
+
+        for (name, value) in arguments {
+            if name != "first" && name != "last" {
+                continue;
+            }
+
+            // A variable which is not given may have a default in the query,
+            // it is counted as the largest page, resolvers cap it anyway.
+            let size = match value {
+                Value::Int(v) => Some(*v),
+                Value::Variable(v) => Some(
+                    self.variables
+                        .and_then(|x| x.get(v))
+                        .and_then(|x| x.as_i64())
+                        .unwrap_or(self.limits.max_page_size as i64),
+                ),
+                Value::Other => None,
+            };
+
+            if let Some(size) = size {
+                if size < 0 {
+                    return Err(QueryLimitError::NegativePageSize {
+                        field: field.to_owned(),
+                        size,
+                    });
+                }
+                let size = size as u64;
+                if size > self.limits.max_page_size {
+                    return Err(QueryLimitError::PageTooLarge {
+                        field: field.to_owned(),
+                        size,
+                        max: self.limits.max_page_size,
+                    });
+                }
+                page_size = Some(page_size.unwrap_or(0).max(size));
+            }
+        }
+
+        Ok(page_size)
+    }
+}
+
+/// Type of the operation of `query` that would be executed.
+pub fn operation_type(
+    query: &str,
+    operation_name: Option<&str>,
+) -> Result<OperationType, QueryLimitError> {
+    let document = Parser::new(query).parse_document()?;
+
+    document.operation(operation_name).map(|x| x.operation_type)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationType {
+    Query,
+    Mutation,
+    Subscription,
+}
+
+struct Document {
+    operations: Vec<Operation>,
+
+    fragments: HashMap<String, Vec<Selection>>,
+}
+
+impl Document {
+    fn operation(&self, operation_name: Option<&str>) -> Result<&Operation, QueryLimitError> {
+        match operation_name {
+            Some(name) => self
+                .operations
+                .iter()
+                .find(|x| x.name.as_deref() == Some(name)),
+            None if self.operations.len() == 1 => self.operations.first(),
+            None => None,
+        }
+        .ok_or_else(|| QueryLimitError::Syntax("Unknown operation".to_owned()))
+    }
+}
+
+struct Operation {
+    operation_type: OperationType,
+
+    name: Option<String>,
+
+    selections: Vec<Selection>,
+}
+
+enum Selection {
+    Field {
+        name: String,
+        arguments: Vec<(String, Value)>,
+        selections: Vec<Selection>,
+    },
+    FragmentSpread {
+        name: String,
+    },
+    InlineFragment {
+        selections: Vec<Selection>,
+    },
+}
+
+enum Value {
+    Int(i64),
+    Variable(String),
+    Other,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Punctuator(char),
+    Spread,
+    Name(String),
+    Int(i64),
+    Float,
+    String,
+}
+
+/// Nesting of selection sets and values the parser accepts, deeper documents
+/// are rejected before they can exhaust the stack.
+const MAX_NESTING: usize = 128;
+
+/// A minimal parser of GraphQL executable documents, it keeps only what is
+/// needed to measure a query and leaves validation to juniper.
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+
+    peeked: Option<Token>,
+
+    nesting: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            chars: source.chars().peekable(),
+            peeked: None,
+            nesting: 0,
+        }
+    }
+
+    fn parse_document(&mut self) -> Result<Document, QueryLimitError> {
+        let mut document = Document {
+            operations: vec![],
+            fragments: HashMap::new(),
+        };
+
+        while let Some(token) = self.peek()? {
+            match token {
+                Token::Punctuator('{') => document.operations.push(Operation {
+                    operation_type: OperationType::Query,
+                    name: None,
+                    selections: self.selection_set()?,
+                }),
+                Token::Name(keyword) if keyword == "fragment" => {
+                    self.next()?;
+                    let name = self.name()?;
+                    self.keyword("on")?;
+                    self.name()?;
+                    self.directives()?;
+                    document.fragments.insert(name, self.selection_set()?);
+                }
+                Token::Name(keyword)
+                    if keyword == "query" || keyword == "mutation" || keyword == "subscription" =>
+                {
+                    self.next()?;
+                    let operation_type = match keyword.as_str() {
+                        "mutation" => OperationType::Mutation,
+                        "subscription" => OperationType::Subscription,
+                        _ => OperationType::Query,
+                    };
+                    let name = match self.peek()? {
+                        Some(Token::Name(_)) => Some(self.name()?),
+                        _ => None,
+                    };
+                    if self.peek()? == Some(Token::Punctuator('(')) {
+                        self.variable_definitions()?;
+                    }
+                    self.directives()?;
+                    document.operations.push(Operation {
+                        operation_type,
+                        name,
+                        selections: self.selection_set()?,
+                    });
+                }
+                token => return Err(unexpected(&token)),
+            }
+        }
+
+        Ok(document)
+    }
+
+    fn selection_set(&mut self) -> Result<Vec<Selection>, QueryLimitError> {
+        self.punctuator('{')?;
+        self.enter()?;
+
+        let mut selections = vec![];
+        loop {
+            match self.next()? {
+                Token::Punctuator('}') => break,
+                Token::Spread => match self.peek()? {
+                    Some(Token::Name(name)) if name != "on" => {
+                        let name = self.name()?;
+                        self.directives()?;
+                        selections.push(Selection::FragmentSpread { name });
+                    }
+                    _ => {
+                        if self.peek()? == Some(Token::Name("on".to_owned())) {
+                            self.next()?;
+                            self.name()?;
+                        }
+                        self.directives()?;
+                        selections.push(Selection::InlineFragment {
+                            selections: self.selection_set()?,
+                        });
+                    }
+                },
+                Token::Name(alias_or_name) => {
+                    let name = match self.peek()? {
+                        Some(Token::Punctuator(':')) => {
+                            self.next()?;
+                            self.name()?
+                        }
+                        _ => alias_or_name,
+                    };
+                    let arguments = match self.peek()? {
+                        Some(Token::Punctuator('(')) => self.arguments()?,
+                        _ => vec![],
+                    };
+                    self.directives()?;
+                    let selections_of_field = match self.peek()? {
+                        Some(Token::Punctuator('{')) => self.selection_set()?,
+                        _ => vec![],
+                    };
+                    selections.push(Selection::Field {
+                        name,
+                        arguments,
+                        selections: selections_of_field,
+                    });
+                }
+                token => return Err(unexpected(&token)),
+            }
+        }
+
+        self.nesting -= 1;
+        Ok(selections)
+    }
+
+    fn arguments(&mut self) -> Result<Vec<(String, Value)>, QueryLimitError> {
+        self.punctuator('(')?;
+
+        let mut arguments = vec![];
+        loop {
+            match self.next()? {
+                Token::Punctuator(')') => break,
+                Token::Name(name) => {
+                    self.punctuator(':')?;
+                    arguments.push((name, self.value()?));
+                }
+                token => return Err(unexpected(&token)),
+            }
+        }
+
+        Ok(arguments)
+    }
+
+    fn value(&mut self) -> Result<Value, QueryLimitError> {
+        match self.next()? {
+            Token::Punctuator('$') => Ok(Value::Variable(self.name()?)),
+            Token::Int(v) => Ok(Value::Int(v)),
+            Token::Float | Token::String | Token::Name(_) => Ok(Value::Other),
+            Token::Punctuator('[') => {
+                self.enter()?;
+                while self.peek()? != Some(Token::Punctuator(']')) {
+                    self.value()?;
+                }
+                self.next()?;
+                self.nesting -= 1;
+                Ok(Value::Other)
+            }
+            Token::Punctuator('{') => {
+                self.enter()?;
+                while self.peek()? != Some(Token::Punctuator('}')) {
+                    self.name()?;
+                    self.punctuator(':')?;
+                    self.value()?;
+                }
+                self.next()?;
+                self.nesting -= 1;
+                Ok(Value::Other)
+            }
+            token => Err(unexpected(&token)),
+        }
+    }
+
+    fn variable_definitions(&mut self) -> Result<(), QueryLimitError> {
+        self.punctuator('(')?;
+
+        while self.peek()? != Some(Token::Punctuator(')')) {
+            self.punctuator('$')?;
+            self.name()?;
+            self.punctuator(':')?;
+            self.type_reference()?;
+            if self.peek()? == Some(Token::Punctuator('=')) {
+                self.next()?;
+                self.value()?;
+            }
+            self.directives()?;
+        }
+        self.next()?;
+
+        Ok(())
+    }
+
+    fn type_reference(&mut self) -> Result<(), QueryLimitError> {
+        match self.next()? {
+            Token::Name(_) => {}
+            Token::Punctuator('[') => {
+                self.enter()?;
+                self.type_reference()?;
+                self.punctuator(']')?;
+                self.nesting -= 1;
+            }
+            token => return Err(unexpected(&token)),
+        }
+
+        if self.peek()? == Some(Token::Punctuator('!')) {
+            self.next()?;
+        }
+
+        Ok(())
+    }
+
+    fn directives(&mut self) -> Result<(), QueryLimitError> {
+        while self.peek()? == Some(Token::Punctuator('@')) {
+            self.next()?;
+            self.name()?;
+            if self.peek()? == Some(Token::Punctuator('(')) {
+                self.arguments()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn enter(&mut self) -> Result<(), QueryLimitError> {
+        self.nesting += 1;
+        if self.nesting > MAX_NESTING {
+            return Err(QueryLimitError::Syntax(
+                "Query is nested too deeply".to_owned(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn name(&mut self) -> Result<String, QueryLimitError> {
+        match self.next()? {
+            Token::Name(name) => Ok(name),
+            token => Err(unexpected(&token)),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), QueryLimitError> {
+        match self.next()? {
+            Token::Name(name) if name == keyword => Ok(()),
+            token => Err(unexpected(&token)),
+        }
+    }
+
+    fn punctuator(&mut self, c: char) -> Result<(), QueryLimitError> {
+        match self.next()? {
+            Token::Punctuator(p) if p == c => Ok(()),
+            token => Err(unexpected(&token)),
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<Token>, QueryLimitError> {
+        if self.peeked.is_none() {
+            self.peeked = self.lex()?;
+        }
+        Ok(self.peeked.clone())
+    }
+
+    fn next(&mut self) -> Result<Token, QueryLimitError> {
+        match self.peeked.take() {
+            Some(token) => Ok(token),
+            None => self
+                .lex()?
+                .ok_or_else(|| QueryLimitError::Syntax("Unexpected end of query".to_owned())),
+        }
+    }
+
+    fn lex(&mut self) -> Result<Option<Token>, QueryLimitError> {
+        loop {
+            let c = match self.chars.next() {
+                Some(c) => c,
+                None => return Ok(None),
+            };
+
+            match c {
+                c if c.is_whitespace() || c == ',' || c == '\u{feff}' => continue,
+                '#' => {
+                    for c in self.chars.by_ref() {
+                        if c == '\n' || c == '\r' {
+                            break;
+                        }
+                    }
+                }
+                '.' => {
+                    if self.chars.next() == Some('.') && self.chars.next() == Some('.') {
+                        return Ok(Some(Token::Spread));
+                    }
+                    return Err(QueryLimitError::Syntax("Unexpected .".to_owned()));
+                }
+                '{' | '}' | '(' | ')' | '[' | ']' | ':' | '=' | '@' | '$' | '!' | '|' | '&' => {
+                    return Ok(Some(Token::Punctuator(c)))
+                }
+                '"' => {
+                    self.string()?;
+                    return Ok(Some(Token::String));
+                }
+                c if c == '_' || c.is_ascii_alphabetic() => {
+                    let mut name = c.to_string();
+                    while let Some(&c) = self.chars.peek() {
+                        if c == '_' || c.is_ascii_alphanumeric() {
+                            name.push(c);
+                            self.chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    return Ok(Some(Token::Name(name)));
+                }
+                c if c == '-' || c.is_ascii_digit() => {
+                    let mut number = c.to_string();
+                    let mut float = false;
+                    while let Some(&c) = self.chars.peek() {
+                        if c.is_ascii_digit() {
+                            number.push(c);
+                        } else if c == '.' || c == 'e' || c == 'E' || c == '+' || c == '-' {
+                            float = true;
+                            number.push(c);
+                        } else {
+                            break;
+                        }
+                        self.chars.next();
+                    }
+                    if float {
+                        return Ok(Some(Token::Float));
+                    }
+                    return number.parse().map(|x| Some(Token::Int(x))).map_err(|_| {
+                        QueryLimitError::Syntax(format!("Invalid number {}", number))
+                    });
+                }
+                c => return Err(QueryLimitError::Syntax(format!("Unexpected {}", c))),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<(), QueryLimitError> {
+        let block = self.chars.peek() == Some(&'"') && {
+            self.chars.next();
+            if self.chars.peek() == Some(&'"') {
+                self.chars.next();
+                true
+            } else {
+                // An empty string `""`.
+                return Ok(());
+            }
+        };
+
+        let mut quotes = 0;
+        while let Some(c) = self.chars.next() {
+            match c {
+                '\\' => {
+                    self.chars.next();
+                    quotes = 0;
+                }
+                '"' if !block => return Ok(()),
+                '"' => {
+                    quotes += 1;
+                    if quotes == 3 {
+                        return Ok(());
+                    }
+                }
+                _ => quotes = 0,
+            }
+        }
+
+        Err(QueryLimitError::Syntax("Unterminated string".to_owned()))
+    }
+}
+
+fn unexpected(token: &Token) -> QueryLimitError {
+    QueryLimitError::Syntax(format!("Unexpected {:?}", token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> ConfigQueryLimits {
+        ConfigQueryLimits {
+            max_depth: 5,
+            max_complexity: 100,
+            max_page_size: 10,
+        }
+    }
+
+    fn check(query: &str) -> Result<(), QueryLimitError> {
+        check_query_limits(&limits(), query, None, None)
+    }
+
+    /// The depth and complexity of the only operation of `query`.
+    fn measure(query: &str) -> (usize, u64) {
+        let document = Parser::new(query).parse_document().unwrap();
+        let mut measure = Measure {
+            limits: &ConfigQueryLimits {
+                max_depth: 100,
+                max_complexity: u64::MAX,
+                max_page_size: 100,
+            },
+            fragments: &document.fragments,
+            variables: None,
+            measured_fragments: HashMap::new(),
+        };
+        measure
+            .selections(
+                &document.operation(None).unwrap().selections,
+                1,
+                &mut vec![],
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn skip_strings() {
+        assert_eq!(measure(r#"{ a(x: "} { b c") d(y: "") }"#), (1, 2));
+        assert_eq!(
+            check(r#"{ a(x: "unterminated) }"#),
+            Err(QueryLimitError::Syntax("Unterminated string".to_owned()))
+        );
+    }
+
+    #[test]
+    fn skip_block_strings() {
+        assert_eq!(
+            measure("{ a(x: \"\"\"line \" and \"\" }\n{ line\"\"\") b }"),
+            (1, 2)
+        );
+    }
+
+    #[test]
+    fn skip_escapes() {
+        assert_eq!(
+            measure(r#"{ a(x: "\" } \\", y: "é \n", z: """\""" }""") b }"#),
+            (1, 2)
+        );
+    }
+
+    #[test]
+    fn count_variable_page_sizes() {
+        let query = "query Q($n: Int = 3, $ids: [ID!]!) { list(first: $n) { a b } }";
+
+        let variables = serde_json::json!({ "n": 4 });
+        assert_eq!(
+            check_query_limits(&limits(), query, None, Some(&variables)),
+            Ok(())
+        );
+
+        let variables = serde_json::json!({ "n": 11 });
+        assert_eq!(
+            check_query_limits(&limits(), query, None, Some(&variables)),
+            Err(QueryLimitError::PageTooLarge {
+                field: "list".to_owned(),
+                size: 11,
+                max: 10,
+            })
+        );
+
+        let variables = serde_json::json!({ "n": -1 });
+        assert_eq!(
+            check_query_limits(&limits(), query, None, Some(&variables)),
+            Err(QueryLimitError::NegativePageSize {
+                field: "list".to_owned(),
+                size: -1,
+            })
+        );
+
+        // Not given, counted as the largest page.
+        assert_eq!(measure(query), (2, 201));
+    }
+
+    #[test]
+    fn measure_inline_fragments() {
+        assert_eq!(
+            measure("{ node { ... on User { a b } ... @include(if: true) { c } } }"),
+            (2, 4)
+        );
+    }
+
+    #[test]
+    fn measure_named_fragments() {
+        let query = "
+            query { a { ...F } b: c { ...F } }
+            fragment F on X { d e { f } }
+        ";
+        assert_eq!(measure(query), (3, 8));
+        assert_eq!(operation_type(query, None), Ok(OperationType::Query));
+    }
+
+    #[test]
+    fn refuse_fragment_cycles() {
+        let query = "
+            { ...A }
+            fragment A on X { a { ...B } }
+            fragment B on X { ...A }
+        ";
+        assert_eq!(
+            check(query),
+            Err(QueryLimitError::Syntax(
+                "Fragment A spreads itself".to_owned()
+            ))
+        );
+    }
+
+    /// Every fragment spreads the next one twice, a walk without the
+    /// measures of the fragments would take 2^63 steps.
+    fn fragment_chain(length: usize) -> String {
+        let mut query = "{ ...F0 }".to_owned();
+        for i in 0..length {
+            query.push_str(&format!(
+                " fragment F{} on X {{ ...F{} ...F{} }}",
+                i,
+                i + 1,
+                i + 1
+            ));
+        }
+        query.push_str(&format!(" fragment F{} on X {{ a }}", length));
+        query
+    }
+
+    #[test]
+    fn measure_fragment_chain_once() {
+        assert_eq!(measure(&fragment_chain(63)), (1, 1_u64 << 63));
+    }
+
+    #[test]
+    fn refuse_fragment_chain_early() {
+        assert!(matches!(
+            check(&fragment_chain(63)),
+            Err(QueryLimitError::TooComplex { max: 100, .. })
+        ));
+    }
+}
@@ -0,0 +1,38 @@
+/// 64 bit integer, represented as a decimal string since `Int` is limited to
+/// 32 bits. Both strings and `Int` are accepted as input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Long(pub i64);
+
+impl From<u64> for Long {
+    fn from(v: u64) -> Self {
+        Self(v as i64)
+    }
+}
+
+#[juniper::graphql_scalar(description = "64 bit integer, represented as a decimal string")]
+impl<S> GraphQLScalar for Long
+where
+    S: ScalarValue,
+{
+    fn resolve(&self) -> Value {
+        juniper::Value::scalar(self.0.to_string())
+    }
+
+    fn from_input_value(v: &InputValue) -> Option<Self> {
+        v.as_scalar_value().and_then(|v| {
+            v.as_str()
+                .and_then(|s| s.parse().ok())
+                .or_else(|| v.as_int().map(i64::from))
+                .map(Self)
+        })
+    }
+
+    fn from_str<'a>(value: ScalarToken<'a>) -> juniper::ParseScalarResult<'a, S> {
+        match value {
+            juniper::parser::ScalarToken::Int(_) => {
+                <i32 as juniper::ParseScalarValue<S>>::from_str(value)
+            }
+            _ => <String as juniper::ParseScalarValue<S>>::from_str(value),
+        }
+    }
+}
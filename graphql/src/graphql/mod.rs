@@ -1,9 +1,19 @@
 mod cursor;
+mod date_time;
+mod global_id;
+mod limits;
+mod long;
 mod order;
 mod page_info;
+mod request;
 mod result;
 
 pub use cursor::Cursor;
+pub use date_time::{DateTime, DateTimeRange};
+pub use global_id::GlobalId;
+pub use limits::{check_query_limits, operation_type, OperationType, QueryLimitError};
+pub use long::Long;
 pub use order::OrderDirection;
 pub use page_info::PageInfo;
+pub use request::{GraphQLOperation, GraphQLPayload};
 pub use result::{Error, ErrorKind, Result};
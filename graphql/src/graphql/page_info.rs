@@ -3,4 +3,5 @@ pub struct PageInfo {
     pub start_cursor: Option<String>,
     pub end_cursor: Option<String>,
     pub has_next_page: bool,
+    pub has_previous_page: bool,
 }
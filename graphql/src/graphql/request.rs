@@ -0,0 +1,173 @@
+use juniper::{
+    http::{GraphQLBatchRequest, GraphQLRequest},
+    InputValue,
+};
+use serde::Deserialize;
+
+use crate::{persisted_query::PersistedQueries, ConfigQueryLimits};
+
+use super::{check_query_limits, operation_type, Error, OperationType, QueryLimitError, Result};
+
+/// Body of a GraphQL http request, a single operation or a batch of them.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum GraphQLPayload {
+    Single(GraphQLOperation),
+
+    Batch(Vec<GraphQLOperation>),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GraphQLOperation {
+    /// Absent when the operation refers to a persisted query by its hash.
+    pub query: Option<String>,
+
+    #[serde(rename = "operationName")]
+    pub operation_name: Option<String>,
+
+    pub variables: Option<serde_json::Value>,
+
+    pub extensions: Option<serde_json::Value>,
+}
+
+/// Query string of a `GET` request, `variables` and `extensions` are JSON
+/// encoded.
+#[derive(Debug, Deserialize)]
+struct GraphQLQueryString {
+    query: Option<String>,
+
+    #[serde(rename = "operationName")]
+    operation_name: Option<String>,
+
+    variables: Option<String>,
+
+    extensions: Option<String>,
+}
+
+impl GraphQLPayload {
+    /// Parse the body of a `application/json` or `application/graphql` request.
+    pub fn parse(content_type: Option<&str>, body: &[u8]) -> Result<Self> {
+        match content_type {
+            Some(content_type) if content_type.starts_with("application/graphql") => {
+                let query =
+                    String::from_utf8(body.to_vec()).map_err(|e| Error::unknown(e.to_string()))?;
+
+                Ok(Self::Single(GraphQLOperation {
+                    query: Some(query),
+                    operation_name: None,
+                    variables: None,
+                    extensions: None,
+                }))
+            }
+            _ => serde_json::from_slice(body).map_err(|e| Error::unknown(e.to_string())),
+        }
+    }
+
+    /// Replace the persisted query hashes of the operations with their query.
+    pub fn resolve_persisted_queries(
+        &mut self,
+        persisted_queries: &PersistedQueries,
+    ) -> Result<()> {
+        match self {
+            Self::Single(operation) => persisted_queries.resolve(operation),
+            Self::Batch(operations) => operations
+                .iter_mut()
+                .try_for_each(|x| persisted_queries.resolve(x)),
+        }
+    }
+
+    pub fn operations(&self) -> &[GraphQLOperation] {
+        match self {
+            Self::Single(operation) => std::slice::from_ref(operation),
+            Self::Batch(operations) => operations,
+        }
+    }
+
+    pub fn check_limits(
+        &self,
+        limits: &ConfigQueryLimits,
+    ) -> std::result::Result<(), QueryLimitError> {
+        self.operations()
+            .iter()
+            .try_for_each(|x| x.check_limits(limits))
+    }
+
+    pub fn into_request(self) -> Result<GraphQLBatchRequest> {
+        Ok(match self {
+            Self::Single(operation) => GraphQLBatchRequest::Single(operation.into_request()?),
+            Self::Batch(operations) => GraphQLBatchRequest::Batch(
+                operations
+                    .into_iter()
+                    .map(GraphQLOperation::into_request)
+                    .collect::<Result<_>>()?,
+            ),
+        })
+    }
+}
+
+impl GraphQLOperation {
+    /// Parse the query string of a `GET` request.
+    pub fn from_query_string(query_string: &str) -> Result<Self> {
+        let params: GraphQLQueryString =
+            serde_urlencoded::from_str(query_string).map_err(|e| Error::unknown(e.to_string()))?;
+
+        let parse_json = |x: Option<String>| {
+            x.map(|x| serde_json::from_str(&x))
+                .transpose()
+                .map_err(|e| Error::unknown(e.to_string()))
+        };
+
+        Ok(Self {
+            query: params.query,
+            operation_name: params.operation_name,
+            variables: parse_json(params.variables)?,
+            extensions: parse_json(params.extensions)?,
+        })
+    }
+
+    /// The `sha256Hash` of the Apollo `persistedQuery` extension.
+    pub fn persisted_query_hash(&self) -> Option<&str> {
+        self.extensions
+            .as_ref()
+            .and_then(|x| x.get("persistedQuery"))
+            .and_then(|x| x.get("sha256Hash"))
+            .and_then(|x| x.as_str())
+    }
+
+    pub fn operation_type(&self) -> std::result::Result<OperationType, QueryLimitError> {
+        operation_type(self.query()?, self.operation_name.as_deref())
+    }
+
+    pub fn check_limits(
+        &self,
+        limits: &ConfigQueryLimits,
+    ) -> std::result::Result<(), QueryLimitError> {
+        check_query_limits(
+            limits,
+            self.query()?,
+            self.operation_name.as_deref(),
+            self.variables.as_ref(),
+        )
+    }
+
+    fn query(&self) -> std::result::Result<&str, QueryLimitError> {
+        self.query
+            .as_deref()
+            .ok_or_else(|| QueryLimitError::Syntax("Query is not present".to_owned()))
+    }
+
+    pub fn into_request(self) -> Result<GraphQLRequest> {
+        let variables = self
+            .variables
+            .filter(|x| !x.is_null())
+            .map(serde_json::from_value::<InputValue>)
+            .transpose()
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        let query = self
+            .query
+            .ok_or_else(|| Error::unknown("Query is not present".to_owned()))?;
+
+        Ok(GraphQLRequest::new(query, self.operation_name, variables))
+    }
+}
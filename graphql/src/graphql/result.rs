@@ -1,4 +1,8 @@
-use juniper::{graphql_value, FieldError, IntoFieldError, ScalarValue};
+use juniper::{FieldError, IntoFieldError, Object, ScalarValue, Value};
+
+use crate::metrics;
+
+use super::QueryLimitError;
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -8,6 +12,12 @@ pub enum ErrorKind {
     Unauthorized,
     Forbidden,
     NotFound,
+    QuotaExceeded,
+    InvalidInput,
+    RateLimited,
+    QueryLimitExceeded,
+    PersistedQueryNotFound,
+    PersistedQueryNotSupported,
     Unknown,
 }
 
@@ -16,6 +26,9 @@ pub struct Error {
     pub kind: ErrorKind,
 
     pub message: Option<String>,
+
+    /// Seconds to wait before retrying, sent as `extensions.retryAfter`.
+    pub retry_after_sec: Option<u64>,
 }
 
 impl std::fmt::Display for Error {
@@ -38,9 +51,22 @@ impl From<paper::Error> for Error {
                 paper::ErrorKind::Unaurhorized => ErrorKind::Unauthorized,
                 paper::ErrorKind::Forbidden => ErrorKind::Forbidden,
                 paper::ErrorKind::NotFound => ErrorKind::NotFound,
+                paper::ErrorKind::QuotaExceeded => ErrorKind::QuotaExceeded,
+                paper::ErrorKind::InvalidInput => ErrorKind::InvalidInput,
                 paper::ErrorKind::Unknown => ErrorKind::Unknown,
             },
             message: e.message,
+            retry_after_sec: None,
+        }
+    }
+}
+
+impl From<QueryLimitError> for Error {
+    fn from(e: QueryLimitError) -> Self {
+        Self {
+            kind: ErrorKind::QueryLimitExceeded,
+            message: Some(e.to_string()),
+            retry_after_sec: None,
         }
     }
 }
@@ -50,6 +76,7 @@ impl Error {
         Self {
             kind: ErrorKind::Unauthorized,
             message: message.into(),
+            retry_after_sec: None,
         }
     }
 
@@ -57,6 +84,51 @@ impl Error {
         Self {
             kind: ErrorKind::Forbidden,
             message: message.into(),
+            retry_after_sec: None,
+        }
+    }
+
+    pub fn not_found<T: Into<Option<String>>>(message: T) -> Self {
+        Self {
+            kind: ErrorKind::NotFound,
+            message: message.into(),
+            retry_after_sec: None,
+        }
+    }
+
+    pub fn invalid_input<T: Into<Option<String>>>(message: T) -> Self {
+        Self {
+            kind: ErrorKind::InvalidInput,
+            message: message.into(),
+            retry_after_sec: None,
+        }
+    }
+
+    pub fn rate_limited(retry_after_sec: u64) -> Self {
+        Self {
+            kind: ErrorKind::RateLimited,
+            message: Some(format!(
+                "Too many requests, retry after {} seconds",
+                retry_after_sec
+            )),
+            retry_after_sec: Some(retry_after_sec),
+        }
+    }
+
+    /// Apollo clients retry with the full query when they see this message.
+    pub fn persisted_query_not_found() -> Self {
+        Self {
+            kind: ErrorKind::PersistedQueryNotFound,
+            message: Some("PersistedQueryNotFound".to_owned()),
+            retry_after_sec: None,
+        }
+    }
+
+    pub fn persisted_query_not_supported() -> Self {
+        Self {
+            kind: ErrorKind::PersistedQueryNotSupported,
+            message: Some("PersistedQueryNotSupported".to_owned()),
+            retry_after_sec: None,
         }
     }
 
@@ -64,19 +136,76 @@ impl Error {
         Self {
             kind: ErrorKind::Unknown,
             message: message.into(),
+            retry_after_sec: None,
         }
     }
 }
 
+impl Error {
+    /// A GraphQL response body carrying only this error, for requests
+    /// rejected before execution.
+    pub fn to_response_body(&self) -> serde_json::Value {
+        metrics::inc_graphql_error(&self.kind.to_string());
+
+        let mut extensions = serde_json::json!({ "type": self.kind.to_string() });
+        if let Some(retry_after_sec) = self.retry_after_sec {
+            extensions["retryAfter"] = retry_after_sec.into();
+        }
+
+        serde_json::json!({
+            "errors": [{
+                "message": self.message.as_ref().map(String::as_ref).unwrap_or("None"),
+                "extensions": extensions,
+            }],
+        })
+    }
+}
+
 impl<S: ScalarValue> IntoFieldError<S> for Error {
     fn into_field_error(self) -> FieldError<S> {
         let kind = self.kind.to_string();
+        metrics::inc_graphql_error(&kind);
+
+        let mut extensions = Object::with_capacity(2);
+        extensions.add_field("type", Value::scalar(kind));
+        // The wait is at most an hour, it fits a GraphQL `Int`.
+        if let Some(retry_after_sec) = self.retry_after_sec {
+            extensions.add_field("retryAfter", Value::scalar(retry_after_sec as i32));
+        }
 
         FieldError::new(
             self.message.as_ref().map(String::as_ref).unwrap_or("None"),
-            graphql_value!({
-                "type": kind,
-            }),
+            Value::object(extensions),
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use juniper::{graphql_value, DefaultScalarValue};
+
+    use super::*;
+
+    #[test]
+    fn send_retry_after() {
+        let error = Error::rate_limited(30);
+
+        assert_eq!(
+            error.to_response_body()["errors"][0]["extensions"],
+            serde_json::json!({ "type": "RATE_LIMITED", "retryAfter": 30 })
+        );
+
+        let field_error: FieldError<DefaultScalarValue> = error.into_field_error();
+        assert_eq!(
+            field_error.extensions(),
+            &graphql_value!({ "type": "RATE_LIMITED", "retryAfter": 30 })
+        );
+
+        let field_error: FieldError<DefaultScalarValue> =
+            Error::not_found("Paper not found".to_owned()).into_field_error();
+        assert_eq!(
+            field_error.extensions(),
+            &graphql_value!({ "type": "NOT_FOUND" })
+        );
+    }
+}
@@ -1,19 +1,32 @@
 mod config;
 mod context;
 mod graphql;
+mod loader;
 mod module;
 mod mutation;
 mod query;
+mod subscription;
 
+pub mod cors;
 pub mod logger;
+pub mod metrics;
 pub mod models;
+pub mod persisted_query;
+pub mod rate_limit;
+pub mod reload;
+pub mod security_headers;
+pub mod shutdown;
+pub mod telemetry;
+pub mod tls;
 
 pub use config::*;
 pub use context::{Context, Schema};
 pub use graphql::*;
+pub use loader::{BatchLoader, Loaders, Permission};
 pub use module::*;
 pub use mutation::Mutation;
 pub use query::Query;
+pub use subscription::Subscription;
 
 #[macro_export]
 macro_rules! shaku_storage_collection_config {
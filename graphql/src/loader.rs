@@ -0,0 +1,233 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    hash::Hash,
+    sync::{Arc, Mutex},
+};
+
+use futures::future::{BoxFuture, FutureExt, Shared};
+use paper::{
+    paper::{Paper, PaperId, PaperService},
+    user::{User, UserId, UserService},
+    ErrorKind,
+};
+use shaku::HasProvider;
+use tracing::Instrument;
+
+use crate::*;
+
+type Fetch<K, V> = Arc<dyn Fn(Vec<K>) -> BoxFuture<'static, Result<HashMap<K, V>>> + Send + Sync>;
+
+type Batch<K, V> = Shared<BoxFuture<'static, Result<Arc<HashMap<K, V>>>>>;
+
+/// Collects the keys requested by sibling resolvers and loads them with a
+/// single fetch, loaded values are cached for the lifetime of the loader.
+pub struct BatchLoader<K, V> {
+    name: &'static str,
+
+    fetch: Fetch<K, V>,
+
+    state: Mutex<BatchLoaderState<K, V>>,
+}
+
+struct BatchLoaderState<K, V> {
+    /// The batch every requested key has been assigned to.
+    batches: HashMap<K, Batch<K, V>>,
+
+    /// The latest batch, it accepts new keys until its fetch starts.
+    open: Option<(Arc<Mutex<Option<Vec<K>>>>, Batch<K, V>)>,
+}
+
+impl<K, V> BatchLoader<K, V>
+where
+    K: Clone + Eq + Hash + Send + Sync + 'static,
+    V: Clone + Send + Sync + 'static,
+{
+    /// `name` identifies the loader in the spans of its fetches.
+    pub fn new<F, Fut>(name: &'static str, fetch: F) -> Self
+    where
+        F: Fn(Vec<K>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<HashMap<K, V>>> + Send + 'static,
+    {
+        Self {
+            name,
+            fetch: Arc::new(move |keys| fetch(keys).boxed()),
+            state: Mutex::new(BatchLoaderState {
+                batches: HashMap::new(),
+                open: None,
+            }),
+        }
+    }
+
+    pub async fn load(&self, key: K) -> Result<Option<V>> {
+        let batch = self.batch(&key);
+
+        batch.await.map(|x| x.get(&key).cloned())
+    }
+
+    fn batch(&self, key: &K) -> Batch<K, V> {
+        let mut state = self.state.lock().expect("Loader lock poisoned");
+
+        if let Some(batch) = state.batches.get(key) {
+            return batch.clone();
+        }
+
+        let joined = state.open.as_ref().and_then(|(keys, batch)| {
+            keys.lock()
+                .expect("Loader lock poisoned")
+                .as_mut()
+                .map(|keys| {
+                    keys.push(key.clone());
+                    batch.clone()
+                })
+        });
+
+        let batch = match joined {
+            Some(batch) => batch,
+            None => {
+                let keys = Arc::new(Mutex::new(Some(vec![key.clone()])));
+                let fetch = self.fetch.clone();
+                let pending = keys.clone();
+                let span = tracing::info_span!(
+                    "loader",
+                    otel.name = self.name,
+                    loader.keys = tracing::field::Empty,
+                );
+
+                let batch = async move {
+                    // Let the sibling resolvers queue their keys first.
+                    tokio::task::yield_now().await;
+
+                    let keys = pending
+                        .lock()
+                        .expect("Loader lock poisoned")
+                        .take()
+                        .unwrap_or_default();
+
+                    tracing::Span::current().record("loader.keys", &keys.len());
+
+                    fetch(keys).await.map(Arc::new)
+                }
+                .instrument(span)
+                .boxed()
+                .shared();
+
+                state.open = Some((keys, batch.clone()));
+                batch
+            }
+        };
+
+        state.batches.insert(key.clone(), batch.clone());
+        batch
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Permission {
+    ReadUser,
+    WriteUser,
+    AdministerUser,
+    WritePaper,
+}
+
+/// Request scoped loaders, they batch the user and paper lookups of a query
+/// and memoize the permission checks of the viewer.
+pub struct Loaders {
+    pub user: BatchLoader<UserId, User>,
+
+    pub paper: BatchLoader<PaperId, Paper>,
+
+    permissions: Mutex<HashMap<(Permission, String), bool>>,
+}
+
+impl Loaders {
+    pub fn new(module: Arc<Module>, viewer_id: Option<UserId>) -> Self {
+        let user_module = module.clone();
+        let user = BatchLoader::new("loader.user", move |user_ids: Vec<UserId>| {
+            let user_service: Box<dyn UserService> = user_module.provide().unwrap();
+
+            async move {
+                Ok(user_service
+                    .select_users(user_ids)
+                    .await?
+                    .into_iter()
+                    .map(|x| (x.id.to_owned(), x))
+                    .collect())
+            }
+        });
+
+        let paper = BatchLoader::new("loader.paper", move |paper_ids: Vec<PaperId>| {
+            let paper_service: Box<dyn PaperService> = module.provide().unwrap();
+            let viewer_id = viewer_id.to_owned();
+
+            async move {
+                let viewer_id = viewer_id
+                    .ok_or_else(|| Error::unauthorized("AccessToken is not present".to_owned()))?;
+
+                Ok(paper_service
+                    .select_papers(viewer_id, paper_ids)
+                    .await?
+                    .into_iter()
+                    .map(|x| (x.id.to_owned(), x))
+                    .collect())
+            }
+        });
+
+        Self {
+            user,
+            paper,
+            permissions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn user(&self, user_id: UserId) -> Result<User> {
+        self.user
+            .load(user_id)
+            .await?
+            .ok_or_else(|| Error::not_found("User not found".to_owned()))
+    }
+
+    pub async fn paper(&self, paper_id: PaperId) -> Result<Paper> {
+        self.paper
+            .load(paper_id)
+            .await?
+            .ok_or_else(|| Error::not_found("Paper not found".to_owned()))
+    }
+
+    /// Run the permission `check` for the object `id` once per request, a
+    /// forbidden error is memoized as `false`.
+    pub async fn permission<T, F>(&self, permission: Permission, id: &str, check: F) -> Result<bool>
+    where
+        F: Future<Output = paper::Result<T>>,
+    {
+        let key = (permission, id.to_owned());
+
+        if let Some(allowed) = self
+            .permissions
+            .lock()
+            .expect("Loader lock poisoned")
+            .get(&key)
+        {
+            return Ok(*allowed);
+        }
+
+        let span = tracing::info_span!(
+            "permission",
+            otel.name = format!("permission.{:?}", permission).as_str(),
+            permission.object_id = id,
+        );
+
+        let allowed = match check.instrument(span).await {
+            Ok(_) => true,
+            Err(e) if e.kind == ErrorKind::Forbidden => false,
+            Err(e) => return Err(e.into()),
+        };
+
+        self.permissions
+            .lock()
+            .expect("Loader lock poisoned")
+            .insert(key, allowed);
+
+        Ok(allowed)
+    }
+}
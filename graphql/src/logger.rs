@@ -1,31 +1,170 @@
-use log::{LevelFilter, Log, Metadata, Record, SetLoggerError};
+use std::{
+    io::Write,
+    sync::RwLock,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    http::{HeaderName, HeaderValue},
+    Error, HttpRequest,
+};
+use chrono::{SecondsFormat, Utc};
+use futures::future::{ok, LocalBoxFuture, Ready};
+use lazy_static::lazy_static;
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
 use serde::Deserialize;
 
-/// A stdio logger.
-pub struct Logger;
+use crate::config::ConfigLog;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+lazy_static! {
+    static ref LOGGER: Logger = Logger {
+        state: RwLock::new(LoggerState {
+            format: LogFormat::Text,
+            level: LevelFilter::Info,
+            modules: Vec::new(),
+        }),
+    };
+}
+
+/// Target of the access log lines.
+pub const ACCESS_LOG_TARGET: &str = "access";
+
+/// A stdio logger, writing text or JSON lines tagged with the id of the
+/// current request.
+pub struct Logger {
+    state: RwLock<LoggerState>,
+}
+
+struct LoggerState {
+    format: LogFormat,
+
+    level: LevelFilter,
+
+    /// Module prefixes and their level, the longest prefix first.
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl LoggerState {
+    fn level(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| {
+                target == module
+                    || (target.starts_with(module.as_str())
+                        && target[module.len()..].starts_with("::"))
+            })
+            .map(|(_, level)| *level)
+            .unwrap_or(self.level)
+    }
+}
 
 impl Log for Logger {
-    fn enabled(&self, _metadata: &Metadata) -> bool {
-        true
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let state = self.state.read().expect("Logger lock poisoned");
+        metadata.level() <= state.level(metadata.target())
     }
 
     fn log(&self, record: &Record) {
         if self.enabled(record.metadata()) {
-            println!("{}: {}", record.level(), record.args());
+            self.write(
+                record.level(),
+                record.target(),
+                &record.args().to_string(),
+                Vec::new(),
+            );
         }
     }
 
-    fn flush(&self) {}
+    fn flush(&self) {
+        let _ = std::io::stdout().flush();
+    }
 }
 
 impl Logger {
     /// Initialize logger.
-    pub fn init(level: LevelFilter) -> Result<(), SetLoggerError> {
-        log::set_max_level(level);
-        log::set_logger(&Logger)
+    pub fn init(level: LevelFilter, config: &ConfigLog) -> Result<(), SetLoggerError> {
+        Self::configure(level, config);
+        log::set_logger(&*LOGGER)
+    }
+
+    /// Replace the format and levels of the logger.
+    pub fn configure(level: LevelFilter, config: &ConfigLog) {
+        let mut modules: Vec<(String, LevelFilter)> = config
+            .modules
+            .iter()
+            .map(|(module, level)| (module.to_owned(), level.0))
+            .collect();
+        modules.sort_by(|a, b| b.0.len().cmp(&a.0.len()));
+
+        let max_level = modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(level, std::cmp::max);
+
+        *LOGGER.state.write().expect("Logger lock poisoned") = LoggerState {
+            format: config.format,
+            level,
+            modules,
+        };
+        log::set_max_level(max_level);
+    }
+
+    fn write(
+        &self,
+        level: Level,
+        target: &str,
+        message: &str,
+        fields: Vec<(&str, serde_json::Value)>,
+    ) {
+        let timestamp = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+        let request_id = REQUEST_ID.try_with(|x| x.to_owned()).ok();
+        let format = self.state.read().expect("Logger lock poisoned").format;
+
+        let line = match format {
+            LogFormat::Text => {
+                let mut line = format!("{} {:5} {}", timestamp, level, target);
+                if let Some(request_id) = request_id {
+                    line.push_str(&format!(" [{}]", request_id));
+                }
+                line.push_str(": ");
+                line.push_str(message);
+                for (key, value) in fields {
+                    line.push_str(&format!(" {}={}", key, value));
+                }
+                line
+            }
+            LogFormat::Json => {
+                let mut line = serde_json::Map::new();
+                line.insert("timestamp".to_owned(), timestamp.into());
+                line.insert("level".to_owned(), level.to_string().into());
+                line.insert("target".to_owned(), target.into());
+                line.insert("message".to_owned(), message.into());
+                if let Some(request_id) = request_id {
+                    line.insert("request_id".to_owned(), request_id.into());
+                }
+                for (key, value) in fields {
+                    line.insert(key.to_owned(), value);
+                }
+                serde_json::Value::Object(line).to_string()
+            }
+        };
+
+        let stdout = std::io::stdout();
+        let _ = writeln!(stdout.lock(), "{}", line);
     }
 }
 
+/// The id of the request being handled by the current task.
+pub fn request_id() -> Option<String> {
+    REQUEST_ID.try_with(|x| x.to_owned()).ok()
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(remote = "log::LevelFilter", rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum LogLevelFilter {
@@ -36,3 +175,157 @@ pub enum LogLevelFilter {
     Debug,
     Trace,
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl Default for LogFormat {
+    fn default() -> Self {
+        Self::Text
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct ModuleLevelFilter(#[serde(with = "LogLevelFilter")] pub LevelFilter);
+
+/// Details of a request only known to its handler, they are added to its
+/// access log line.
+#[derive(Debug, Clone, Default)]
+pub struct AccessLogFields {
+    pub operation_name: Option<String>,
+
+    pub user_id: Option<String>,
+
+    /// The administrator impersonating `user_id`.
+    pub actor_id: Option<String>,
+}
+
+impl AccessLogFields {
+    pub fn insert(self, req: &HttpRequest) {
+        req.extensions_mut().insert(self);
+    }
+}
+
+/// Assigns every request an id, taken from `X-Request-Id` when present, and
+/// writes an access log line once it has been handled.
+pub struct RequestLog;
+
+impl<S, B> Transform<S> for RequestLog
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RequestLogMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RequestLogMiddleware { service })
+    }
+}
+
+pub struct RequestLogMiddleware<S> {
+    service: S,
+}
+
+impl<S, B> Service for RequestLogMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let request_id = req
+            .headers()
+            .get("X-Request-Id")
+            .and_then(|x| x.to_str().ok())
+            .filter(|x| !x.is_empty() && x.len() <= 128)
+            .map(str::to_owned)
+            .unwrap_or_else(|| mongodb::bson::oid::ObjectId::new().to_hex());
+
+        let started_at = Instant::now();
+        let method = req.method().to_string();
+        let path = req.path().to_owned();
+        let fut = self.service.call(req);
+
+        Box::pin(REQUEST_ID.scope(request_id.to_owned(), async move {
+            let result = fut.await;
+
+            let (status, fields) = match &result {
+                Ok(res) => (
+                    res.status(),
+                    res.request()
+                        .extensions()
+                        .get::<AccessLogFields>()
+                        .cloned()
+                        .unwrap_or_default(),
+                ),
+                Err(e) => (
+                    e.as_response_error().status_code(),
+                    AccessLogFields::default(),
+                ),
+            };
+
+            access_log(&method, &path, status.as_u16(), started_at, fields);
+
+            result.map(|mut res| {
+                if let Ok(value) = HeaderValue::from_str(&request_id) {
+                    res.headers_mut()
+                        .insert(HeaderName::from_static("x-request-id"), value);
+                }
+                res
+            })
+        }))
+    }
+}
+
+fn access_log(method: &str, path: &str, status: u16, started_at: Instant, fields: AccessLogFields) {
+    // Probes and scrapes are only interesting while debugging.
+    let level = match path {
+        "/healthz" | "/readyz" | "/metrics" => Level::Debug,
+        _ => Level::Info,
+    };
+
+    let metadata = Metadata::builder()
+        .level(level)
+        .target(ACCESS_LOG_TARGET)
+        .build();
+    if level > log::max_level() || !LOGGER.enabled(&metadata) {
+        return;
+    }
+
+    LOGGER.write(
+        level,
+        ACCESS_LOG_TARGET,
+        &format!("{} {} {}", method, path, status),
+        vec![
+            ("method", method.into()),
+            ("path", path.into()),
+            ("status", status.into()),
+            (
+                "duration_ms",
+                (started_at.elapsed().as_secs_f64() * 1000.0).into(),
+            ),
+            ("operation_name", serde_json::json!(fields.operation_name)),
+            ("user_id", serde_json::json!(fields.user_id)),
+            ("actor_id", serde_json::json!(fields.actor_id)),
+        ],
+    );
+}
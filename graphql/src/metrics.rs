@@ -0,0 +1,135 @@
+use std::{collections::HashSet, sync::Mutex, time::Duration};
+
+use lazy_static::lazy_static;
+use mongodb::event::command::{CommandEventHandler, CommandFailedEvent, CommandSucceededEvent};
+use prometheus::{
+    register_histogram_vec, register_int_counter_vec, register_int_gauge, Encoder, HistogramVec,
+    IntCounterVec, IntGauge, TextEncoder,
+};
+
+/// Distinct operation names get a label of their own up to this number, the
+/// others are counted as `other`.
+const MAX_OPERATION_NAMES: usize = 200;
+
+lazy_static! {
+    static ref GRAPHQL_REQUESTS: IntCounterVec = register_int_counter_vec!(
+        "paper_graphql_requests_total",
+        "GraphQL operations executed, by operation name",
+        &["operation"]
+    )
+    .unwrap();
+    static ref GRAPHQL_REQUEST_DURATION: HistogramVec = register_histogram_vec!(
+        "paper_graphql_request_duration_seconds",
+        "Latency of GraphQL requests, by operation name",
+        &["operation"]
+    )
+    .unwrap();
+    static ref GRAPHQL_ERRORS: IntCounterVec = register_int_counter_vec!(
+        "paper_graphql_errors_total",
+        "GraphQL errors, by error kind",
+        &["kind"]
+    )
+    .unwrap();
+    static ref MONGODB_COMMAND_DURATION: HistogramVec = register_histogram_vec!(
+        "paper_mongodb_command_duration_seconds",
+        "Latency of MongoDB commands, by command name and outcome",
+        &["command", "status"],
+        vec![0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]
+    )
+    .unwrap();
+    static ref WEBSOCKET_CONNECTIONS: IntGauge = register_int_gauge!(
+        "paper_websocket_connections",
+        "Active GraphQL subscription connections"
+    )
+    .unwrap();
+    static ref OPERATION_NAMES: Mutex<HashSet<String>> = Mutex::new(HashSet::new());
+}
+
+/// Record a GraphQL operation, `None` is an anonymous operation.
+pub fn observe_graphql_request(operation_name: Option<&str>, duration: Duration) {
+    let operation = operation_label(operation_name);
+
+    GRAPHQL_REQUESTS.with_label_values(&[&operation]).inc();
+    GRAPHQL_REQUEST_DURATION
+        .with_label_values(&[&operation])
+        .observe(duration.as_secs_f64());
+}
+
+pub fn inc_graphql_error(kind: &str) {
+    GRAPHQL_ERRORS.with_label_values(&[kind]).inc();
+}
+
+/// Operation names are chosen by clients, so only valid names are used as a
+/// label and their number is capped.
+fn operation_label(operation_name: Option<&str>) -> String {
+    let name = match operation_name {
+        Some(name) => name,
+        None => return "anonymous".to_owned(),
+    };
+
+    let valid = name.len() <= 64
+        && name
+            .chars()
+            .enumerate()
+            .all(|(i, c)| c == '_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit()));
+    if !valid {
+        return "invalid".to_owned();
+    }
+
+    let mut names = OPERATION_NAMES.lock().expect("Metrics lock poisoned");
+    if names.contains(name) {
+        return name.to_owned();
+    }
+    if names.len() < MAX_OPERATION_NAMES {
+        names.insert(name.to_owned());
+        return name.to_owned();
+    }
+    "other".to_owned()
+}
+
+/// Counts an open subscription connection until dropped.
+pub struct WebSocketConnection(());
+
+impl WebSocketConnection {
+    pub fn open() -> Self {
+        WEBSOCKET_CONNECTIONS.inc();
+        Self(())
+    }
+}
+
+impl Drop for WebSocketConnection {
+    fn drop(&mut self) {
+        WEBSOCKET_CONNECTIONS.dec();
+    }
+}
+
+/// Records the latency of the commands of a MongoDB client.
+pub struct MongoCommandMetrics;
+
+impl CommandEventHandler for MongoCommandMetrics {
+    fn handle_command_succeeded_event(&self, event: CommandSucceededEvent) {
+        MONGODB_COMMAND_DURATION
+            .with_label_values(&[&event.command_name, "succeeded"])
+            .observe(event.duration.as_secs_f64());
+    }
+
+    fn handle_command_failed_event(&self, event: CommandFailedEvent) {
+        MONGODB_COMMAND_DURATION
+            .with_label_values(&[&event.command_name, "failed"])
+            .observe(event.duration.as_secs_f64());
+    }
+}
+
+/// All metrics in the Prometheus text format, along with its content type.
+pub fn render() -> (String, String) {
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    encoder
+        .encode(&prometheus::gather(), &mut buffer)
+        .expect("Encode metrics error");
+
+    (
+        encoder.format_type().to_owned(),
+        String::from_utf8(buffer).expect("Metrics are not utf8"),
+    )
+}
@@ -0,0 +1,201 @@
+use juniper::ID;
+use paper::{
+    admin::AdminService,
+    audit::{AuditEventInput, AuditEventKind},
+    user::UserFilter,
+    Pagination,
+};
+use shaku::HasProvider;
+
+use crate::{
+    models::{
+        audit::{AuditEventConnection, AuditEventCursor, AuditEventFilter},
+        auth::AccessToken,
+        node::decode_user_id,
+        user::{Role, User, UserConnection, UserCursor},
+    },
+    *,
+};
+
+/// The site administration, moderators and admins only.
+pub struct Admin;
+
+#[juniper::graphql_object(context = Context)]
+impl Admin {
+    async fn users(
+        ctx: &Context,
+        search: Option<String>,
+        disabled: Option<bool>,
+        first: i32,
+        after: Option<UserCursor>,
+    ) -> Result<UserConnection> {
+        telemetry::resolver("Admin.users", async {
+            let admin_service: Box<dyn AdminService> = ctx.module.provide().unwrap();
+
+            admin_service
+                .select_user_page(
+                    Pagination::After {
+                        after: after.map(|x| x.into()),
+                        skip: None,
+                        first: ctx.page_size(first)?,
+                    },
+                    UserFilter { search, disabled },
+                )
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+
+    /// The audit log of the whole site, newest first.
+    async fn audit_events(
+        ctx: &Context,
+        first: i32,
+        after: Option<AuditEventCursor>,
+        filter: Option<AuditEventFilter>,
+    ) -> Result<AuditEventConnection> {
+        telemetry::resolver("Admin.auditEvents", async {
+            let admin_service: Box<dyn AdminService> = ctx.module.provide().unwrap();
+
+            admin_service
+                .select_audit_event_page(
+                    Pagination::After {
+                        after: after.map(|x| x.into()),
+                        skip: None,
+                        first: ctx.page_size(first)?,
+                    },
+                    filter
+                        .map(|x| x.try_into_filter())
+                        .transpose()?
+                        .unwrap_or_default(),
+                )
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+
+    async fn statistics(ctx: &Context) -> Result<Statistics> {
+        telemetry::resolver("Admin.statistics", async {
+            let admin_service: Box<dyn AdminService> = ctx.module.provide().unwrap();
+
+            admin_service
+                .select_statistics()
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+}
+
+/// The site administration mutations, admins only.
+pub struct AdminMutation;
+
+#[juniper::graphql_object(context = Context)]
+impl AdminMutation {
+    async fn disable_user(ctx: &Context, user_id: ID) -> Result<User> {
+        telemetry::resolver("AdminMutation.disableUser", async {
+            set_user_disabled(ctx, &user_id, true).await
+        })
+        .await
+    }
+
+    async fn enable_user(ctx: &Context, user_id: ID) -> Result<User> {
+        telemetry::resolver("AdminMutation.enableUser", async {
+            set_user_disabled(ctx, &user_id, false).await
+        })
+        .await
+    }
+
+    async fn set_user_role(ctx: &Context, user_id: ID, role: Role) -> Result<User> {
+        telemetry::resolver("AdminMutation.setUserRole", async {
+            let admin_service: Box<dyn AdminService> = ctx.module.provide().unwrap();
+
+            let role: paper::user::Role = role.into();
+            let user = admin_service
+                .set_user_role(decode_user_id(&user_id)?, role)
+                .await?;
+
+            ctx.audit(AuditEventInput {
+                user_id: Some(user.id.to_owned()),
+                details: Some(serde_json::json!({ "role": role })),
+                ..AuditEventInput::new(AuditEventKind::UserRoleChange)
+            })
+            .await;
+
+            Ok(user.into())
+        })
+        .await
+    }
+
+    async fn impersonate_user(ctx: &Context, user_id: ID, reason: String) -> Result<AccessToken> {
+        telemetry::resolver("AdminMutation.impersonateUser", async {
+            let admin_service: Box<dyn AdminService> = ctx.module.provide().unwrap();
+
+            // The impersonation is audited by the service.
+            admin_service
+                .impersonate_user(
+                    ctx.access_token().await?.sub,
+                    decode_user_id(&user_id)?,
+                    reason,
+                )
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+}
+
+async fn set_user_disabled(ctx: &Context, user_id: &ID, disabled: bool) -> Result<User> {
+    let admin_service: Box<dyn AdminService> = ctx.module.provide().unwrap();
+
+    let user = admin_service
+        .set_user_disabled(decode_user_id(user_id)?, disabled)
+        .await?;
+
+    ctx.audit(AuditEventInput {
+        user_id: Some(user.id.to_owned()),
+        ..AuditEventInput::new(match disabled {
+            true => AuditEventKind::UserDisable,
+            false => AuditEventKind::UserEnable,
+        })
+    })
+    .await;
+
+    Ok(user.into())
+}
+
+pub struct Statistics(paper::admin::Statistics);
+
+impl From<paper::admin::Statistics> for Statistics {
+    fn from(v: paper::admin::Statistics) -> Self {
+        Self(v)
+    }
+}
+
+#[juniper::graphql_object(context = Context)]
+impl Statistics {
+    fn users(&self) -> i32 {
+        self.0.users as i32
+    }
+
+    fn disabled_users(&self) -> i32 {
+        self.0.disabled_users as i32
+    }
+
+    fn admins(&self) -> i32 {
+        self.0.admins as i32
+    }
+
+    fn papers(&self) -> i32 {
+        self.0.papers as i32
+    }
+
+    fn deleted_papers(&self) -> i32 {
+        self.0.deleted_papers as i32
+    }
+}
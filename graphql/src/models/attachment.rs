@@ -0,0 +1,185 @@
+use juniper::{GraphQLEnum, GraphQLObject, ID};
+use paper::{attachment::AttachmentId, user::UserId};
+use serde::{Deserialize, Serialize};
+use shaku::{Component, HasComponent};
+
+use crate::{models::user::User, *};
+
+#[derive(GraphQLEnum)]
+pub enum AttachmentStatus {
+    /// Waiting for its content to be uploaded.
+    Pending,
+
+    Ready,
+}
+
+pub struct Attachment(paper::attachment::Attachment);
+
+impl From<paper::attachment::Attachment> for Attachment {
+    fn from(v: paper::attachment::Attachment) -> Self {
+        Self(v)
+    }
+}
+
+#[juniper::graphql_object(context = Context)]
+impl Attachment {
+    fn id(&self) -> ID {
+        self.0.id.to_string().into()
+    }
+
+    fn name(&self) -> &String {
+        &self.0.name
+    }
+
+    fn content_type(&self) -> &String {
+        &self.0.content_type
+    }
+
+    /// Bytes of the content.
+    fn size(&self) -> i32 {
+        self.0.size as i32
+    }
+
+    fn status(&self) -> AttachmentStatus {
+        match self.0.status {
+            paper::attachment::AttachmentStatus::Pending => AttachmentStatus::Pending,
+            paper::attachment::AttachmentStatus::Ready => AttachmentStatus::Ready,
+        }
+    }
+
+    /// Deleted users are null.
+    async fn uploader(&self, ctx: &Context) -> Result<Option<User>> {
+        telemetry::resolver("Attachment.uploader", async {
+            Ok(ctx
+                .loaders
+                .user
+                .load(self.0.uploader_id.to_owned())
+                .await?
+                .map(|x| x.into()))
+        })
+        .await
+    }
+
+    fn created_at(&self) -> DateTime {
+        self.0.created_at.into()
+    }
+
+    fn uploaded_at(&self) -> Option<DateTime> {
+        self.0.uploaded_at.map(DateTime::from)
+    }
+
+    /// A signed URL of the content once it is uploaded, it expires shortly,
+    /// query it again for a fresh one. The paper is checked to be readable by
+    /// the viewer when it is downloaded.
+    async fn download_url(&self, ctx: &Context) -> Result<Option<String>> {
+        telemetry::resolver("Attachment.downloadUrl", async {
+            if self.0.status != paper::attachment::AttachmentStatus::Ready {
+                return Ok(None);
+            }
+
+            let viewer_id = ctx.access_token().await?.sub;
+
+            Ok(Some(attachment_url(ctx, &self.0.id, viewer_id, false)))
+        })
+        .await
+    }
+}
+
+/// An attachment waiting for its content, `PUT` it as the body of a request
+/// to `uploadUrl` before the URL expires.
+#[derive(GraphQLObject)]
+#[graphql(context = Context)]
+pub struct AttachmentUpload {
+    pub attachment: Attachment,
+
+    pub upload_url: String,
+}
+
+/// The signed URL of an upload or a download of the attachment on behalf of
+/// `viewer_id`.
+pub fn attachment_url(
+    ctx: &Context,
+    attachment_id: &AttachmentId,
+    viewer_id: UserId,
+    upload: bool,
+) -> String {
+    let config: &dyn AttachmentTokenConfigInterface = ctx.module.resolve_ref();
+
+    let token = AttachmentTokenPayload::new(
+        attachment_id.to_owned(),
+        viewer_id,
+        upload,
+        config.expires_in_sec,
+    )
+    .encode(&config.secret);
+
+    format!("{}/attachments/{}", config.base_url, token)
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AttachmentTokenPayload {
+    pub iat: u64,
+
+    pub exp: u64,
+
+    pub sub: AttachmentId,
+
+    /// Whose permissions on the paper are checked when the token is used.
+    pub viewer: UserId,
+
+    /// Whether the token uploads the content, otherwise it downloads it.
+    pub upload: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Component)]
+#[shaku(interface = AttachmentTokenConfigInterface)]
+pub struct AttachmentTokenConfig {
+    pub expires_in_sec: u64,
+
+    pub secret: String,
+
+    /// Prepended to the `/attachments/` path of the upload and download URLs.
+    pub base_url: String,
+}
+
+paper_impl::shaku_deref_self_interface!(AttachmentTokenConfigInterface, AttachmentTokenConfig);
+
+impl AttachmentTokenPayload {
+    pub fn new(
+        attachment_id: AttachmentId,
+        viewer_id: UserId,
+        upload: bool,
+        expires_in_sec: u64,
+    ) -> Self {
+        let now_sec = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("Invalid system time")
+            .as_secs();
+
+        Self {
+            iat: now_sec,
+            exp: now_sec + expires_in_sec,
+            sub: attachment_id,
+            viewer: viewer_id,
+            upload,
+        }
+    }
+
+    pub fn encode(&self, secret: impl AsRef<[u8]>) -> String {
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &self,
+            &jsonwebtoken::EncodingKey::from_secret(secret.as_ref()),
+        )
+        .expect("Encode jsonwebtoken error")
+    }
+
+    pub fn decode(token: &str, secret: impl AsRef<[u8]>) -> jsonwebtoken::errors::Result<Self> {
+        jsonwebtoken::decode::<Self>(
+            token,
+            &jsonwebtoken::DecodingKey::from_secret(secret.as_ref()),
+            &jsonwebtoken::Validation::default(),
+        )
+        .map(|x| x.claims)
+    }
+}
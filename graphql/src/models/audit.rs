@@ -0,0 +1,301 @@
+use juniper::{GraphQLEnum, GraphQLInputObject, ID};
+use paper::{audit::AuditEventId, PaginationList};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::{
+        node::{decode_paper_id, decode_user_id, paper_global_id, user_global_id},
+        user::User,
+    },
+    *,
+};
+
+#[derive(GraphQLEnum, Clone, Copy)]
+pub enum AuditEventKind {
+    SignIn,
+
+    TokenRefresh,
+
+    AuthFailure,
+
+    UserRename,
+
+    UserDisable,
+
+    UserEnable,
+
+    UserRoleChange,
+
+    Impersonation,
+
+    TokenMint,
+
+    AccountDelete,
+
+    AccountRestore,
+
+    DataExport,
+
+    PaperCreate,
+
+    PaperUpdate,
+
+    PaperDelete,
+
+    PaperRestore,
+}
+
+impl From<paper::audit::AuditEventKind> for AuditEventKind {
+    fn from(v: paper::audit::AuditEventKind) -> Self {
+        use paper::audit::AuditEventKind as Kind;
+
+        match v {
+            Kind::SignIn => Self::SignIn,
+            Kind::TokenRefresh => Self::TokenRefresh,
+            Kind::AuthFailure => Self::AuthFailure,
+            Kind::UserRename => Self::UserRename,
+            Kind::UserDisable => Self::UserDisable,
+            Kind::UserEnable => Self::UserEnable,
+            Kind::UserRoleChange => Self::UserRoleChange,
+            Kind::Impersonation => Self::Impersonation,
+            Kind::TokenMint => Self::TokenMint,
+            Kind::AccountDelete => Self::AccountDelete,
+            Kind::AccountRestore => Self::AccountRestore,
+            Kind::DataExport => Self::DataExport,
+            Kind::PaperCreate => Self::PaperCreate,
+            Kind::PaperUpdate => Self::PaperUpdate,
+            Kind::PaperDelete => Self::PaperDelete,
+            Kind::PaperRestore => Self::PaperRestore,
+        }
+    }
+}
+
+impl Into<paper::audit::AuditEventKind> for AuditEventKind {
+    fn into(self) -> paper::audit::AuditEventKind {
+        use paper::audit::AuditEventKind as Kind;
+
+        match self {
+            Self::SignIn => Kind::SignIn,
+            Self::TokenRefresh => Kind::TokenRefresh,
+            Self::AuthFailure => Kind::AuthFailure,
+            Self::UserRename => Kind::UserRename,
+            Self::UserDisable => Kind::UserDisable,
+            Self::UserEnable => Kind::UserEnable,
+            Self::UserRoleChange => Kind::UserRoleChange,
+            Self::Impersonation => Kind::Impersonation,
+            Self::TokenMint => Kind::TokenMint,
+            Self::AccountDelete => Kind::AccountDelete,
+            Self::AccountRestore => Kind::AccountRestore,
+            Self::DataExport => Kind::DataExport,
+            Self::PaperCreate => Kind::PaperCreate,
+            Self::PaperUpdate => Kind::PaperUpdate,
+            Self::PaperDelete => Kind::PaperDelete,
+            Self::PaperRestore => Kind::PaperRestore,
+        }
+    }
+}
+
+#[derive(GraphQLInputObject)]
+pub struct AuditEventFilter {
+    pub kind: Option<AuditEventKind>,
+
+    pub actor_id: Option<ID>,
+
+    pub user_id: Option<ID>,
+
+    pub paper_id: Option<ID>,
+}
+
+impl AuditEventFilter {
+    pub fn try_into_filter(self) -> Result<paper::audit::AuditEventFilter> {
+        Ok(paper::audit::AuditEventFilter {
+            kind: self.kind.map(Into::into),
+            actor_id: self.actor_id.as_ref().map(decode_user_id).transpose()?,
+            user_id: self.user_id.as_ref().map(decode_user_id).transpose()?,
+            paper_id: self.paper_id.as_ref().map(decode_paper_id).transpose()?,
+        })
+    }
+}
+
+pub struct AuditEvent(paper::audit::AuditEvent);
+
+#[juniper::graphql_object(context = Context)]
+impl AuditEvent {
+    fn id(&self) -> ID {
+        self.0.id.to_string().into()
+    }
+
+    fn created_at(&self) -> DateTime {
+        self.0.created_at.into()
+    }
+
+    fn kind(&self) -> AuditEventKind {
+        self.0.kind.into()
+    }
+
+    /// Who did it, deleted users are null.
+    async fn actor(&self, ctx: &Context) -> Result<Option<User>> {
+        telemetry::resolver("AuditEvent.actor", async {
+            self._user(ctx, &self.0.actor_id).await
+        })
+        .await
+    }
+
+    fn actor_id(&self) -> Option<ID> {
+        self.0.actor_id.as_ref().map(user_global_id)
+    }
+
+    /// The admin impersonating the actor.
+    fn impersonator_id(&self) -> Option<ID> {
+        self.0.impersonator_id.as_ref().map(user_global_id)
+    }
+
+    /// The user it has been done to, deleted users are null.
+    async fn user(&self, ctx: &Context) -> Result<Option<User>> {
+        telemetry::resolver("AuditEvent.user", async {
+            self._user(ctx, &self.0.user_id).await
+        })
+        .await
+    }
+
+    fn user_id(&self) -> Option<ID> {
+        self.0.user_id.as_ref().map(user_global_id)
+    }
+
+    fn paper_id(&self) -> Option<ID> {
+        self.0.paper_id.as_ref().map(paper_global_id)
+    }
+
+    fn ip(&self) -> Option<&String> {
+        self.0.ip.as_ref()
+    }
+
+    fn user_agent(&self) -> Option<&String> {
+        self.0.user_agent.as_ref()
+    }
+
+    /// The specifics of the kind of event, as a JSON string.
+    fn details(&self) -> Option<String> {
+        self.0.details.as_ref().map(|x| x.to_string())
+    }
+}
+
+impl AuditEvent {
+    async fn _user(
+        &self,
+        ctx: &Context,
+        user_id: &Option<paper::user::UserId>,
+    ) -> Result<Option<User>> {
+        match user_id {
+            Some(user_id) => Ok(ctx
+                .loaders
+                .user
+                .load(user_id.to_owned())
+                .await?
+                .map(|x| x.into())),
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct AuditEventCursor {
+    pub id: AuditEventId,
+}
+
+impl Cursor for AuditEventCursor {}
+
+impl From<&paper::audit::AuditEvent> for AuditEventCursor {
+    fn from(v: &paper::audit::AuditEvent) -> Self {
+        Self {
+            id: v.id.to_owned(),
+        }
+    }
+}
+
+impl Into<paper::audit::AuditEventCursor> for AuditEventCursor {
+    fn into(self) -> paper::audit::AuditEventCursor {
+        paper::audit::AuditEventCursor { id: self.id }
+    }
+}
+
+#[juniper::graphql_scalar(description = "Audit Event Cursor")]
+impl<S> GraphQLScalar for AuditEventCursor
+where
+    S: ScalarValue,
+{
+    fn resolve(&self) -> Value {
+        juniper::Value::scalar(self.encode())
+    }
+
+    fn from_input_value(v: &InputValue) -> Option<Self> {
+        v.as_scalar_value()
+            .and_then(|v| v.as_str())
+            .and_then(|s| Self::decode(s))
+    }
+
+    fn from_str<'a>(value: ScalarToken<'a>) -> juniper::ParseScalarResult<'a, S> {
+        <String as juniper::ParseScalarValue<S>>::from_str(value)
+    }
+}
+
+pub struct AuditEventEdge(paper::audit::AuditEvent);
+
+#[juniper::graphql_object(context = Context)]
+impl AuditEventEdge {
+    fn node(&self) -> AuditEvent {
+        AuditEvent(self.0.clone())
+    }
+
+    fn cursor(&self) -> AuditEventCursor {
+        AuditEventCursor::from(&self.0)
+    }
+}
+
+pub struct AuditEventConnection(PaginationList<paper::audit::AuditEvent>);
+
+impl From<PaginationList<paper::audit::AuditEvent>> for AuditEventConnection {
+    fn from(v: PaginationList<paper::audit::AuditEvent>) -> Self {
+        Self(v)
+    }
+}
+
+#[juniper::graphql_object(context = Context)]
+impl AuditEventConnection {
+    async fn edges(&self) -> Vec<AuditEventEdge> {
+        self.0
+            .list
+            .iter()
+            .map(|x| AuditEventEdge(x.to_owned()))
+            .collect()
+    }
+
+    async fn nodes(&self) -> Vec<AuditEvent> {
+        self.0
+            .list
+            .iter()
+            .map(|x| AuditEvent(x.to_owned()))
+            .collect()
+    }
+
+    async fn page_info(&self) -> PageInfo {
+        PageInfo {
+            start_cursor: self
+                .0
+                .list
+                .first()
+                .map(|x| AuditEventCursor::from(x).encode()),
+            end_cursor: self
+                .0
+                .list
+                .last()
+                .map(|x| AuditEventCursor::from(x).encode()),
+            has_next_page: self.0.has_next_page,
+            has_previous_page: self.0.has_previous_page,
+        }
+    }
+
+    async fn total(&self) -> i32 {
+        self.0.total as i32
+    }
+}
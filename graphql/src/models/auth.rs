@@ -90,8 +90,9 @@ impl AccessToken {
         &self.0.token_type
     }
 
-    fn expires_in(&self) -> String {
-        self.0.expires_in.to_string()
+    /// Seconds until the access token expires.
+    fn expires_in(&self) -> Long {
+        self.0.expires_in.into()
     }
 
     fn refresh_token(&self) -> &str {
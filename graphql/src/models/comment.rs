@@ -0,0 +1,254 @@
+use std::convert::TryFrom;
+
+use juniper::{GraphQLInputObject, GraphQLObject, ID};
+use paper::{
+    comment::{CommentId, CommentService},
+    user::UserId,
+    PaginationList,
+};
+use serde::{Deserialize, Serialize};
+use shaku::HasProvider;
+
+use crate::{
+    models::{node::user_global_id, paper::Paper, user::User},
+    *,
+};
+
+/// A range of the content of the paper, in characters.
+#[derive(GraphQLObject)]
+pub struct CommentAnchor {
+    pub start: i32,
+
+    pub end: i32,
+}
+
+#[derive(GraphQLInputObject)]
+pub struct CommentAnchorInput {
+    pub start: i32,
+
+    pub end: i32,
+}
+
+impl CommentAnchorInput {
+    /// The offsets are converted without a cast, a negative one is refused
+    /// rather than wrapped around.
+    pub fn try_into_anchor(self) -> Result<paper::comment::CommentAnchor> {
+        let offset = |x: i32| {
+            u64::try_from(x)
+                .map_err(|_| Error::invalid_input(format!("The anchor offset {} is negative", x)))
+        };
+
+        Ok(paper::comment::CommentAnchor {
+            start: offset(self.start)?,
+            end: offset(self.end)?,
+        })
+    }
+}
+
+pub struct Comment(paper::comment::Comment);
+
+impl From<paper::comment::Comment> for Comment {
+    fn from(v: paper::comment::Comment) -> Self {
+        Self(v)
+    }
+}
+
+#[juniper::graphql_object(context = Context)]
+impl Comment {
+    fn id(&self) -> ID {
+        self.0.id.to_string().into()
+    }
+
+    async fn paper(&self, ctx: &Context) -> Result<Paper> {
+        telemetry::resolver("Comment.paper", async {
+            ctx.loaders
+                .paper(self.0.paper_id.to_owned())
+                .await
+                .map(|x| x.into())
+        })
+        .await
+    }
+
+    /// Deleted users are null.
+    async fn author(&self, ctx: &Context) -> Result<Option<User>> {
+        telemetry::resolver("Comment.author", async {
+            self._user(ctx, &Some(self.0.author_id.to_owned())).await
+        })
+        .await
+    }
+
+    fn author_id(&self) -> ID {
+        user_global_id(&self.0.author_id)
+    }
+
+    /// Null once the comment is deleted.
+    fn body(&self) -> Option<&String> {
+        match self.0.deleted_at {
+            Some(_) => None,
+            None => Some(&self.0.body),
+        }
+    }
+
+    /// Anchors are given as `i32`, stored offsets fit.
+    fn anchor(&self) -> Option<CommentAnchor> {
+        self.0.anchor.map(|x| CommentAnchor {
+            start: i32::try_from(x.start).unwrap_or(i32::MAX),
+            end: i32::try_from(x.end).unwrap_or(i32::MAX),
+        })
+    }
+
+    /// The root comment of the thread, null for the root itself.
+    fn thread_id(&self) -> Option<ID> {
+        self.0.thread_id.as_ref().map(|x| x.to_string().into())
+    }
+
+    fn created_at(&self) -> DateTime {
+        self.0.created_at.into()
+    }
+
+    fn updated_at(&self) -> DateTime {
+        self.0.updated_at.into()
+    }
+
+    fn deleted_at(&self) -> Option<DateTime> {
+        self.0.deleted_at.map(DateTime::from)
+    }
+
+    /// Whether the thread of a root comment is resolved.
+    fn resolved(&self) -> bool {
+        self.0.resolved_at.is_some()
+    }
+
+    fn resolved_at(&self) -> Option<DateTime> {
+        self.0.resolved_at.map(DateTime::from)
+    }
+
+    async fn resolved_by(&self, ctx: &Context) -> Result<Option<User>> {
+        telemetry::resolver("Comment.resolvedBy", async {
+            self._user(ctx, &self.0.resolved_by).await
+        })
+        .await
+    }
+
+    /// The replies of a root comment, oldest first.
+    async fn replies(&self, ctx: &Context) -> Result<Vec<Comment>> {
+        telemetry::resolver("Comment.replies", async {
+            if self.0.thread_id.is_some() {
+                return Ok(vec![]);
+            }
+
+            let comment_service: Box<dyn CommentService> = ctx.module.provide().unwrap();
+
+            comment_service
+                .select_replies(ctx.access_token().await?.sub, self.0.id.to_owned())
+                .await
+                .map(|x| x.into_iter().map(Into::into).collect())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+}
+
+impl Comment {
+    async fn _user(&self, ctx: &Context, user_id: &Option<UserId>) -> Result<Option<User>> {
+        match user_id {
+            Some(user_id) => Ok(ctx
+                .loaders
+                .user
+                .load(user_id.to_owned())
+                .await?
+                .map(|x| x.into())),
+            None => Ok(None),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct CommentCursor {
+    pub id: CommentId,
+}
+
+impl Cursor for CommentCursor {}
+
+impl From<&paper::comment::Comment> for CommentCursor {
+    fn from(v: &paper::comment::Comment) -> Self {
+        Self {
+            id: v.id.to_owned(),
+        }
+    }
+}
+
+impl Into<paper::comment::CommentCursor> for CommentCursor {
+    fn into(self) -> paper::comment::CommentCursor {
+        paper::comment::CommentCursor { id: self.id }
+    }
+}
+
+#[juniper::graphql_scalar(description = "Comment Cursor")]
+impl<S> GraphQLScalar for CommentCursor
+where
+    S: ScalarValue,
+{
+    fn resolve(&self) -> Value {
+        juniper::Value::scalar(self.encode())
+    }
+
+    fn from_input_value(v: &InputValue) -> Option<Self> {
+        v.as_scalar_value()
+            .and_then(|v| v.as_str())
+            .and_then(|s| Self::decode(s))
+    }
+
+    fn from_str<'a>(value: ScalarToken<'a>) -> juniper::ParseScalarResult<'a, S> {
+        <String as juniper::ParseScalarValue<S>>::from_str(value)
+    }
+}
+
+pub struct CommentEdge(paper::comment::Comment);
+
+#[juniper::graphql_object(context = Context)]
+impl CommentEdge {
+    fn node(&self) -> Comment {
+        Comment(self.0.clone())
+    }
+
+    fn cursor(&self) -> CommentCursor {
+        CommentCursor::from(&self.0)
+    }
+}
+
+pub struct CommentConnection(PaginationList<paper::comment::Comment>);
+
+impl From<PaginationList<paper::comment::Comment>> for CommentConnection {
+    fn from(v: PaginationList<paper::comment::Comment>) -> Self {
+        Self(v)
+    }
+}
+
+#[juniper::graphql_object(context = Context)]
+impl CommentConnection {
+    async fn edges(&self) -> Vec<CommentEdge> {
+        self.0
+            .list
+            .iter()
+            .map(|x| CommentEdge(x.to_owned()))
+            .collect()
+    }
+
+    async fn nodes(&self) -> Vec<Comment> {
+        self.0.list.iter().map(|x| Comment(x.to_owned())).collect()
+    }
+
+    async fn page_info(&self) -> PageInfo {
+        PageInfo {
+            start_cursor: self.0.list.first().map(|x| CommentCursor::from(x).encode()),
+            end_cursor: self.0.list.last().map(|x| CommentCursor::from(x).encode()),
+            has_next_page: self.0.has_next_page,
+            has_previous_page: self.0.has_previous_page,
+        }
+    }
+
+    async fn total(&self) -> i32 {
+        self.0.total as i32
+    }
+}
@@ -0,0 +1,126 @@
+use juniper::{GraphQLEnum, ID};
+use paper::export::ExportId;
+use serde::{Deserialize, Serialize};
+use shaku::{Component, HasComponent};
+
+use crate::*;
+
+#[derive(GraphQLEnum)]
+pub enum ExportStatus {
+    Pending,
+
+    Ready,
+
+    Failed,
+}
+
+pub struct DataExport(paper::export::DataExport);
+
+impl From<paper::export::DataExport> for DataExport {
+    fn from(v: paper::export::DataExport) -> Self {
+        Self(v)
+    }
+}
+
+#[juniper::graphql_object(context = Context)]
+impl DataExport {
+    fn id(&self) -> ID {
+        self.0.id.to_string().into()
+    }
+
+    fn status(&self) -> ExportStatus {
+        match self.0.status {
+            paper::export::ExportStatus::Pending => ExportStatus::Pending,
+            paper::export::ExportStatus::Ready => ExportStatus::Ready,
+            paper::export::ExportStatus::Failed => ExportStatus::Failed,
+        }
+    }
+
+    fn created_at(&self) -> DateTime {
+        self.0.created_at.into()
+    }
+
+    fn expires_at(&self) -> DateTime {
+        self.0.expires_at.into()
+    }
+
+    fn error(&self) -> Option<&String> {
+        self.0.error.as_ref()
+    }
+
+    /// A signed URL of the zip archive once the export is ready, it expires
+    /// shortly, query it again for a fresh one.
+    fn download_url(&self, ctx: &Context) -> Option<String> {
+        if self.0.status != paper::export::ExportStatus::Ready {
+            return None;
+        }
+
+        let config: &dyn ExportTokenConfigInterface = ctx.module.resolve_ref();
+
+        let token = ExportTokenPayload::new(
+            self.0.id.to_owned(),
+            config.expires_in_sec,
+            self.0.expires_at / 1000,
+        )
+        .encode(&config.secret);
+
+        Some(format!("{}/exports/{}", config.base_url, token))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ExportTokenPayload {
+    pub iat: u64,
+
+    pub exp: u64,
+
+    pub sub: ExportId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Component)]
+#[shaku(interface = ExportTokenConfigInterface)]
+pub struct ExportTokenConfig {
+    pub expires_in_sec: u64,
+
+    pub secret: String,
+
+    /// Prepended to the `/exports/` path of the download URLs.
+    pub base_url: String,
+}
+
+paper_impl::shaku_deref_self_interface!(ExportTokenConfigInterface, ExportTokenConfig);
+
+impl ExportTokenPayload {
+    /// A token of `export_id` for `expires_in_sec`, it does not outlive the
+    /// export expiring at `export_expires_at_sec`.
+    pub fn new(export_id: ExportId, expires_in_sec: u64, export_expires_at_sec: u64) -> Self {
+        let now_sec = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("Invalid system time")
+            .as_secs();
+
+        Self {
+            iat: now_sec,
+            exp: (now_sec + expires_in_sec).min(export_expires_at_sec),
+            sub: export_id,
+        }
+    }
+
+    pub fn encode(&self, secret: impl AsRef<[u8]>) -> String {
+        jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &self,
+            &jsonwebtoken::EncodingKey::from_secret(secret.as_ref()),
+        )
+        .expect("Encode jsonwebtoken error")
+    }
+
+    pub fn decode(token: &str, secret: impl AsRef<[u8]>) -> jsonwebtoken::errors::Result<Self> {
+        jsonwebtoken::decode::<Self>(
+            token,
+            &jsonwebtoken::DecodingKey::from_secret(secret.as_ref()),
+            &jsonwebtoken::Validation::default(),
+        )
+        .map(|x| x.claims)
+    }
+}
@@ -1,3 +1,12 @@
+pub mod admin;
+pub mod attachment;
+pub mod audit;
 pub mod auth;
+pub mod comment;
+pub mod export;
+pub mod node;
+pub mod notification;
 pub mod paper;
+pub mod quota;
 pub mod user;
+pub mod webhook;
@@ -0,0 +1,70 @@
+use juniper::ID;
+use paper::{paper::PaperId, user::UserId};
+
+use crate::{
+    models::{paper::Paper, user::User},
+    *,
+};
+
+/// Relay object identification, every node can be refetched by its global id
+/// with the `node` query.
+#[juniper::graphql_interface(for = [User, Paper], context = Context)]
+pub trait Node {
+    fn id(&self) -> ID;
+}
+
+pub const USER_TYPE: &str = "User";
+
+pub const PAPER_TYPE: &str = "Paper";
+
+pub fn user_global_id(user_id: &UserId) -> ID {
+    GlobalId::new(USER_TYPE, user_id.as_ref()).encode()
+}
+
+pub fn paper_global_id(paper_id: &PaperId) -> ID {
+    GlobalId::new(PAPER_TYPE, paper_id.as_ref()).encode()
+}
+
+pub fn decode_user_id(id: &ID) -> Result<UserId> {
+    decode(id, USER_TYPE).map(|x| x.into())
+}
+
+pub fn decode_paper_id(id: &ID) -> Result<PaperId> {
+    decode(id, PAPER_TYPE).map(|x| x.into())
+}
+
+fn decode(id: &ID, type_name: &str) -> Result<String> {
+    GlobalId::decode(id)
+        .filter(|x| x.type_name == type_name)
+        .map(|x| x.id)
+        .ok_or_else(|| Error::invalid_input(format!("Invalid {} id", type_name)))
+}
+
+/// Fetch the node of a global id, `None` if it does not exist or the viewer
+/// is not allowed to read it.
+pub async fn node(ctx: &Context, id: &ID) -> Result<Option<NodeValue>> {
+    let global_id = match GlobalId::decode(id) {
+        Some(global_id) => global_id,
+        None => return Ok(None),
+    };
+
+    let node = match global_id.type_name.as_str() {
+        USER_TYPE => ctx
+            .loaders
+            .user(global_id.id.into())
+            .await
+            .map(|x| NodeValue::from(User::from(x))),
+        PAPER_TYPE => ctx
+            .loaders
+            .paper(global_id.id.into())
+            .await
+            .map(|x| NodeValue::from(Paper::from(x))),
+        _ => return Ok(None),
+    };
+
+    match node {
+        Ok(node) => Ok(Some(node)),
+        Err(e) if e.kind == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
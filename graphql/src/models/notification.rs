@@ -0,0 +1,221 @@
+use juniper::{GraphQLEnum, ID};
+use paper::{
+    comment::CommentService,
+    notification::{NotificationId, NotificationService},
+    user::UserId,
+    PaginationList,
+};
+use serde::{Deserialize, Serialize};
+use shaku::HasProvider;
+
+use crate::{
+    models::{comment::Comment, paper::Paper, user::User},
+    *,
+};
+
+#[derive(GraphQLEnum)]
+pub enum NotificationKind {
+    Mention,
+
+    Reply,
+
+    PaperShared,
+}
+
+pub struct Notification(paper::notification::Notification);
+
+impl From<paper::notification::Notification> for Notification {
+    fn from(v: paper::notification::Notification) -> Self {
+        Self(v)
+    }
+}
+
+#[juniper::graphql_object(context = Context)]
+impl Notification {
+    fn id(&self) -> ID {
+        self.0.id.to_string().into()
+    }
+
+    fn kind(&self) -> NotificationKind {
+        match self.0.kind {
+            paper::notification::NotificationKind::Mention => NotificationKind::Mention,
+            paper::notification::NotificationKind::Reply => NotificationKind::Reply,
+            paper::notification::NotificationKind::PaperShared => NotificationKind::PaperShared,
+        }
+    }
+
+    /// Who caused it, deleted users are null.
+    async fn actor(&self, ctx: &Context) -> Result<Option<User>> {
+        telemetry::resolver("Notification.actor", async {
+            match &self.0.actor_id {
+                Some(actor_id) => Ok(ctx
+                    .loaders
+                    .user
+                    .load(actor_id.to_owned())
+                    .await?
+                    .map(|x| x.into())),
+                None => Ok(None),
+            }
+        })
+        .await
+    }
+
+    /// Null once the paper is deleted or can no longer be read.
+    async fn paper(&self, ctx: &Context) -> Option<Paper> {
+        match &self.0.paper_id {
+            Some(paper_id) => ctx
+                .loaders
+                .paper(paper_id.to_owned())
+                .await
+                .ok()
+                .map(Into::into),
+            None => None,
+        }
+    }
+
+    /// Null once the comment is purged or can no longer be read.
+    async fn comment(&self, ctx: &Context) -> Result<Option<Comment>> {
+        telemetry::resolver("Notification.comment", async {
+            let comment_id = match &self.0.comment_id {
+                Some(comment_id) => comment_id.to_owned(),
+                None => return Ok(None),
+            };
+
+            let comment_service: Box<dyn CommentService> = ctx.module.provide().unwrap();
+
+            Ok(comment_service
+                .select_comment(ctx.access_token().await?.sub, comment_id)
+                .await
+                .ok()
+                .map(Into::into))
+        })
+        .await
+    }
+
+    fn created_at(&self) -> DateTime {
+        self.0.created_at.into()
+    }
+
+    fn read(&self) -> bool {
+        self.0.read_at.is_some()
+    }
+
+    fn read_at(&self) -> Option<DateTime> {
+        self.0.read_at.map(DateTime::from)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NotificationCursor {
+    pub id: NotificationId,
+}
+
+impl Cursor for NotificationCursor {}
+
+impl From<&paper::notification::Notification> for NotificationCursor {
+    fn from(v: &paper::notification::Notification) -> Self {
+        Self {
+            id: v.id.to_owned(),
+        }
+    }
+}
+
+impl Into<paper::notification::NotificationCursor> for NotificationCursor {
+    fn into(self) -> paper::notification::NotificationCursor {
+        paper::notification::NotificationCursor { id: self.id }
+    }
+}
+
+#[juniper::graphql_scalar(description = "Notification Cursor")]
+impl<S> GraphQLScalar for NotificationCursor
+where
+    S: ScalarValue,
+{
+    fn resolve(&self) -> Value {
+        juniper::Value::scalar(self.encode())
+    }
+
+    fn from_input_value(v: &InputValue) -> Option<Self> {
+        v.as_scalar_value()
+            .and_then(|v| v.as_str())
+            .and_then(|s| Self::decode(s))
+    }
+
+    fn from_str<'a>(value: ScalarToken<'a>) -> juniper::ParseScalarResult<'a, S> {
+        <String as juniper::ParseScalarValue<S>>::from_str(value)
+    }
+}
+
+pub struct NotificationEdge(paper::notification::Notification);
+
+#[juniper::graphql_object(context = Context)]
+impl NotificationEdge {
+    fn node(&self) -> Notification {
+        Notification(self.0.clone())
+    }
+
+    fn cursor(&self) -> NotificationCursor {
+        NotificationCursor::from(&self.0)
+    }
+}
+
+/// The notifications of `user_id`.
+pub struct NotificationConnection {
+    pub user_id: UserId,
+
+    pub list: PaginationList<paper::notification::Notification>,
+}
+
+#[juniper::graphql_object(context = Context)]
+impl NotificationConnection {
+    async fn edges(&self) -> Vec<NotificationEdge> {
+        self.list
+            .list
+            .iter()
+            .map(|x| NotificationEdge(x.to_owned()))
+            .collect()
+    }
+
+    async fn nodes(&self) -> Vec<Notification> {
+        self.list
+            .list
+            .iter()
+            .map(|x| Notification(x.to_owned()))
+            .collect()
+    }
+
+    async fn page_info(&self) -> PageInfo {
+        PageInfo {
+            start_cursor: self
+                .list
+                .list
+                .first()
+                .map(|x| NotificationCursor::from(x).encode()),
+            end_cursor: self
+                .list
+                .list
+                .last()
+                .map(|x| NotificationCursor::from(x).encode()),
+            has_next_page: self.list.has_next_page,
+            has_previous_page: self.list.has_previous_page,
+        }
+    }
+
+    async fn total(&self) -> i32 {
+        self.list.total as i32
+    }
+
+    /// The unread notifications, whatever the filter of the connection.
+    async fn unread_count(&self, ctx: &Context) -> Result<i32> {
+        telemetry::resolver("NotificationConnection.unreadCount", async {
+            let notification_service: Box<dyn NotificationService> = ctx.module.provide().unwrap();
+
+            notification_service
+                .count_unread_notifications(ctx.access_token().await?.sub, self.user_id.to_owned())
+                .await
+                .map(|x| x as i32)
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+}
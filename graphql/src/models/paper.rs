@@ -1,13 +1,23 @@
-use juniper::{GraphQLEnum, GraphQLInputObject};
+use juniper::{GraphQLEnum, GraphQLInputObject, ID};
 use paper::{
-    paper::{PaperId, PaperService},
-    user::{UserId, UserIdentifier, UserService},
-    ErrorKind, Pagination, PaginationList,
+    attachment::AttachmentService,
+    comment::{CommentFilter, CommentService},
+    paper::{PaperEvent, PaperEventKind, PaperId, PaperService},
+    user::UserId,
+    Pagination, PaginationList,
 };
 use serde::{Deserialize, Serialize};
 use shaku::{Component, HasComponent, HasProvider};
 
-use crate::{models::user::User, *};
+use crate::{
+    models::{
+        attachment::Attachment,
+        comment::{CommentConnection, CommentCursor},
+        node::{paper_global_id, Node, NodeValue},
+        user::User,
+    },
+    *,
+};
 
 #[derive(GraphQLInputObject)]
 pub struct PaperOrder {
@@ -16,6 +26,46 @@ pub struct PaperOrder {
     direction: OrderDirection,
 }
 
+#[derive(GraphQLInputObject)]
+pub struct PaperFilter {
+    /// Defaults to `true` when `deletedAt` is present and `false` otherwise.
+    deleted: Option<bool>,
+
+    created_at: Option<DateTimeRange>,
+
+    updated_at: Option<DateTimeRange>,
+
+    deleted_at: Option<DateTimeRange>,
+}
+
+/// Fields which are absent are left unchanged.
+#[derive(GraphQLInputObject)]
+pub struct UpdatePaperInput {
+    pub title: Option<String>,
+
+    pub tags: Option<Vec<String>>,
+}
+
+impl Into<paper::paper::UpdatePaperInput> for UpdatePaperInput {
+    fn into(self) -> paper::paper::UpdatePaperInput {
+        paper::paper::UpdatePaperInput {
+            title: self.title,
+            tags: self.tags,
+        }
+    }
+}
+
+impl Into<paper::paper::PaperFilter> for PaperFilter {
+    fn into(self) -> paper::paper::PaperFilter {
+        paper::paper::PaperFilter {
+            deleted: self.deleted.unwrap_or_else(|| self.deleted_at.is_some()),
+            created_at: self.created_at.map(|x| x.into()).unwrap_or_default(),
+            updated_at: self.updated_at.map(|x| x.into()).unwrap_or_default(),
+            deleted_at: self.deleted_at.map(|x| x.into()).unwrap_or_default(),
+        }
+    }
+}
+
 #[derive(GraphQLEnum)]
 pub enum PaperOrderField {
     Id,
@@ -44,28 +94,32 @@ impl From<paper::paper::Paper> for Paper {
     }
 }
 
-#[juniper::graphql_object(context = Context)]
+#[juniper::graphql_object(context = Context, impl = NodeValue)]
 impl Paper {
-    fn id(&self) -> String {
-        self.0.id.to_string()
+    fn id(&self) -> ID {
+        paper_global_id(&self.0.id)
     }
 
     async fn user(&self, ctx: &Context) -> Result<User> {
-        let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
+        telemetry::resolver("Paper.user", async {
+            ctx.loaders
+                .user(self.0.user_id.to_owned())
+                .await
+                .map(|x| x.into())
+        })
+        .await
+    }
 
-        user_service
-            .select_user(UserIdentifier::Id(self.0.user_id.to_owned()))
-            .await
-            .map(|x| x.into())
-            .map_err(|e| e.into())
+    fn created_at(&self) -> DateTime {
+        self.0.created_at.into()
     }
 
-    fn created_at(&self) -> String {
-        self.0.created_at.to_string()
+    fn updated_at(&self) -> DateTime {
+        self.0.updated_at.into()
     }
 
-    fn updated_at(&self) -> String {
-        self.0.updated_at.to_string()
+    fn deleted_at(&self) -> Option<DateTime> {
+        self.0.deleted_at.map(DateTime::from)
     }
 
     fn title(&self) -> Option<&String> {
@@ -76,28 +130,90 @@ impl Paper {
         self.0.tags.as_ref()
     }
 
+    /// The attached files, oldest first.
+    async fn attachments(&self, ctx: &Context) -> Result<Vec<Attachment>> {
+        telemetry::resolver("Paper.attachments", async {
+            let attachment_service: Box<dyn AttachmentService> = ctx.module.provide().unwrap();
+
+            attachment_service
+                .select_attachments(
+                    ctx.access_token().await?.sub,
+                    self.0.user_id.to_owned(),
+                    self.0.id.to_owned(),
+                )
+                .await
+                .map(|x| x.into_iter().map(Into::into).collect())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+
+    /// The comment threads, oldest first, open and resolved ones unless
+    /// `resolved` is given.
+    async fn comments(
+        &self,
+        ctx: &Context,
+        first: i32,
+        after: Option<CommentCursor>,
+        resolved: Option<bool>,
+    ) -> Result<CommentConnection> {
+        telemetry::resolver("Paper.comments", async {
+            let comment_service: Box<dyn CommentService> = ctx.module.provide().unwrap();
+
+            comment_service
+                .select_thread_page(
+                    ctx.access_token().await?.sub,
+                    self.0.user_id.to_owned(),
+                    self.0.id.to_owned(),
+                    Pagination::After {
+                        after: after.map(|x| x.into()),
+                        skip: None,
+                        first: ctx.page_size(first)?,
+                    },
+                    CommentFilter { resolved },
+                )
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+
     async fn can_viewer_write_paper(&self, ctx: &Context) -> Result<bool> {
-        self._can_viewer_write_paper(ctx).await
+        telemetry::resolver("Paper.canViewerWritePaper", async {
+            self._can_viewer_write_paper(ctx).await
+        })
+        .await
     }
 
     async fn token(&self, ctx: &Context) -> Result<PaperToken> {
-        let writable = self._can_viewer_write_paper(ctx).await?;
+        telemetry::resolver("Paper.token", async {
+            let writable = self._can_viewer_write_paper(ctx).await?;
 
-        let config: &dyn PaperTokenConfigInterface = ctx.module.resolve_ref();
+            let config: &dyn PaperTokenConfigInterface = ctx.module.resolve_ref();
 
-        let access_token = PaperTokenPayload::new(
-            ctx.access_token()?.sub,
-            config.expires_in_sec,
-            self.0.id.to_owned(),
-            Some(writable),
-        )
-        .encode(&config.secret);
+            let access_token = PaperTokenPayload::new(
+                ctx.access_token().await?.sub,
+                config.expires_in_sec,
+                self.0.id.to_owned(),
+                Some(writable),
+            )
+            .encode(&config.secret);
 
-        Ok(PaperToken {
-            access_token,
-            token_type: String::from("Bearer"),
-            expires_in: config.expires_in_sec.to_string(),
+            Ok(PaperToken {
+                access_token,
+                token_type: String::from("Bearer"),
+                expires_in: config.expires_in_sec.into(),
+            })
         })
+        .await
+    }
+}
+
+#[juniper::graphql_interface]
+impl Node for Paper {
+    fn id(&self) -> ID {
+        paper_global_id(&self.0.id)
     }
 }
 
@@ -105,20 +221,17 @@ impl Paper {
     async fn _can_viewer_write_paper(&self, ctx: &Context) -> Result<bool> {
         let paper_service: Box<dyn PaperService> = ctx.module.provide().unwrap();
 
-        paper_service
-            .can_viewer_write_paper(
-                ctx.access_token()?.sub,
-                self.0.user_id.to_owned(),
-                self.0.id.to_owned(),
+        ctx.loaders
+            .permission(
+                Permission::WritePaper,
+                self.0.id.as_ref(),
+                paper_service.can_viewer_write_paper(
+                    ctx.access_token().await?.sub,
+                    self.0.user_id.to_owned(),
+                    self.0.id.to_owned(),
+                ),
             )
             .await
-            .map_or_else(
-                |e| match e.kind {
-                    ErrorKind::Forbidden => Ok(false),
-                    _ => Err(e.into()),
-                },
-                |_| Ok(true),
-            )
     }
 }
 
@@ -128,7 +241,8 @@ pub struct PaperToken {
 
     pub token_type: String,
 
-    pub expires_in: String,
+    /// Seconds until the access token expires.
+    pub expires_in: Long,
 }
 
 #[derive(Serialize)]
@@ -188,10 +302,33 @@ impl PaperTokenPayload {
 #[derive(Serialize, Deserialize)]
 pub struct PaperCursor {
     pub id: PaperId,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<u64>,
 }
 
 impl Cursor for PaperCursor {}
 
+impl From<&paper::paper::Paper> for PaperCursor {
+    fn from(v: &paper::paper::Paper) -> Self {
+        let cursor = paper::paper::PaperCursor::from(v);
+
+        Self {
+            id: cursor.id,
+            updated_at: cursor.updated_at,
+        }
+    }
+}
+
+impl Into<paper::paper::PaperCursor> for PaperCursor {
+    fn into(self) -> paper::paper::PaperCursor {
+        paper::paper::PaperCursor {
+            id: self.id,
+            updated_at: self.updated_at,
+        }
+    }
+}
+
 #[juniper::graphql_scalar(description = "Paper Cursor")]
 impl<S> GraphQLScalar for PaperCursor
 where
@@ -227,9 +364,7 @@ impl PaperEdge {
     }
 
     fn cursor(&self) -> PaperCursor {
-        PaperCursor {
-            id: self.0.id.to_owned(),
-        }
+        PaperCursor::from(&self.0)
     }
 }
 
@@ -243,9 +378,9 @@ impl PaperConnection {
     pub async fn new(
         ctx: &Context,
         kind: PaperConnectionKind,
-        pagination: Pagination<PaperId>,
+        pagination: Pagination<paper::paper::PaperCursor>,
         order_by: Option<PaperOrder>,
-        deleted: Option<bool>,
+        filter: paper::paper::PaperFilter,
     ) -> Result<Self> {
         let paper_service: Box<dyn PaperService> = ctx.module.provide().unwrap();
 
@@ -253,7 +388,7 @@ impl PaperConnection {
             PaperConnectionKind::User { user_id } => {
                 paper_service
                     .select_paper_page_of_repository(
-                        ctx.access_token()?.sub,
+                        ctx.access_token().await?.sub,
                         user_id.to_owned(),
                         pagination,
                         order_by
@@ -262,7 +397,7 @@ impl PaperConnection {
                                 direction: OrderDirection::Asc,
                             })
                             .into(),
-                        deleted.unwrap_or(false),
+                        filter,
                     )
                     .await?
             }
@@ -283,19 +418,10 @@ impl PaperConnection {
 
     async fn page_info(&self) -> PageInfo {
         PageInfo {
-            start_cursor: self.0.list.first().map(|x| {
-                PaperCursor {
-                    id: x.id.to_owned(),
-                }
-                .encode()
-            }),
-            end_cursor: self.0.list.last().map(|x| {
-                PaperCursor {
-                    id: x.id.to_owned(),
-                }
-                .encode()
-            }),
+            start_cursor: self.0.list.first().map(|x| PaperCursor::from(x).encode()),
+            end_cursor: self.0.list.last().map(|x| PaperCursor::from(x).encode()),
             has_next_page: self.0.has_next_page,
+            has_previous_page: self.0.has_previous_page,
         }
     }
 
@@ -314,17 +440,49 @@ impl From<paper::paper::Paper> for DeletePaperPayload {
 
 #[juniper::graphql_object(context = Context)]
 impl DeletePaperPayload {
-    fn id(&self) -> String {
-        self.0.id.to_string()
+    fn id(&self) -> ID {
+        paper_global_id(&self.0.id)
     }
 
     async fn user(&self, ctx: &Context) -> Result<User> {
-        let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
+        telemetry::resolver("DeletePaperPayload.user", async {
+            ctx.loaders
+                .user(self.0.user_id.to_owned())
+                .await
+                .map(|x| x.into())
+        })
+        .await
+    }
+}
 
-        user_service
-            .select_user(UserIdentifier::Id(self.0.user_id.to_owned()))
-            .await
-            .map(|x| x.into())
-            .map_err(|e| e.into())
+#[derive(GraphQLEnum)]
+pub enum PaperChangeKind {
+    Created,
+
+    Updated,
+
+    Deleted,
+}
+
+pub struct PaperChangedPayload(PaperEvent);
+
+impl From<PaperEvent> for PaperChangedPayload {
+    fn from(v: PaperEvent) -> Self {
+        Self(v)
+    }
+}
+
+#[juniper::graphql_object(context = Context)]
+impl PaperChangedPayload {
+    fn kind(&self) -> PaperChangeKind {
+        match self.0.kind {
+            PaperEventKind::Created => PaperChangeKind::Created,
+            PaperEventKind::Updated => PaperChangeKind::Updated,
+            PaperEventKind::Deleted => PaperChangeKind::Deleted,
+        }
+    }
+
+    fn paper(&self) -> Paper {
+        self.0.paper.clone().into()
     }
 }
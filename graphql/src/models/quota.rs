@@ -0,0 +1,44 @@
+use juniper::GraphQLObject;
+
+use crate::*;
+
+/// How much of the limits of their plan a user has used, limits are null when
+/// unlimited. Going over one fails with `QUOTA_EXCEEDED`.
+#[derive(GraphQLObject)]
+pub struct Usage {
+    pub plan: String,
+
+    /// Papers not deleted.
+    pub papers: i32,
+
+    pub max_papers: Option<i32>,
+
+    /// Bytes of the attachments of the papers of the user.
+    pub attachment_storage: Long,
+
+    pub max_attachment_storage: Option<Long>,
+
+    /// Requests to the API since the start of the UTC day.
+    pub api_requests_today: i32,
+
+    pub max_api_requests_per_day: Option<i32>,
+}
+
+impl From<paper::quota::Usage> for Usage {
+    fn from(v: paper::quota::Usage) -> Self {
+        let limit = |x: u64| match x {
+            0 => None,
+            x => Some(x),
+        };
+
+        Self {
+            plan: v.plan,
+            papers: v.papers as i32,
+            max_papers: limit(v.limits.max_papers).map(|x| x as i32),
+            attachment_storage: v.attachment_storage.into(),
+            max_attachment_storage: limit(v.limits.max_attachment_storage).map(Long::from),
+            api_requests_today: v.api_requests_today as i32,
+            max_api_requests_per_day: limit(v.limits.max_api_requests_per_day).map(|x| x as i32),
+        }
+    }
+}
@@ -1,17 +1,37 @@
 use std::convert::TryInto;
 
-use juniper::GraphQLInputObject;
-use paper::{paper::PaperService, user::UserService, Pagination};
+use juniper::{GraphQLEnum, GraphQLInputObject, ID};
+use paper::{
+    audit::AuditService,
+    export::ExportService,
+    notification::{NotificationFilter, NotificationService},
+    paper::PaperService,
+    quota::QuotaService,
+    user::UserService,
+    webhook::WebhookService,
+    Pagination, PaginationList,
+};
+use serde::{Deserialize, Serialize};
 use shaku::HasProvider;
 
 use crate::{
-    models::paper::{Paper, PaperConnection, PaperConnectionKind, PaperCursor, PaperOrder},
+    models::{
+        audit::{AuditEventConnection, AuditEventCursor},
+        export::DataExport,
+        node::{decode_paper_id, decode_user_id, user_global_id, Node, NodeValue},
+        notification::{NotificationConnection, NotificationCursor},
+        paper::{
+            Paper, PaperConnection, PaperConnectionKind, PaperCursor, PaperFilter, PaperOrder,
+        },
+        quota::Usage,
+        webhook::Webhook,
+    },
     *,
 };
 
 #[derive(GraphQLInputObject)]
 pub struct UserIdentifier {
-    pub id: Option<String>,
+    pub id: Option<ID>,
 
     pub name: Option<String>,
 }
@@ -21,7 +41,7 @@ impl TryInto<paper::user::UserIdentifier> for UserIdentifier {
 
     fn try_into(self) -> Result<paper::user::UserIdentifier> {
         if let Some(id) = self.id {
-            Ok(paper::user::UserIdentifier::Id(id.into()))
+            Ok(paper::user::UserIdentifier::Id(decode_user_id(&id)?))
         } else if let Some(name) = self.name {
             Ok(paper::user::UserIdentifier::Name(name))
         } else {
@@ -41,6 +61,31 @@ impl Into<paper::user::UpdateUserInput> for UpdateUserInput {
     }
 }
 
+pub struct DeleteAccountPayload(paper::auth::AccountDeletion);
+
+impl From<paper::auth::AccountDeletion> for DeleteAccountPayload {
+    fn from(v: paper::auth::AccountDeletion) -> Self {
+        Self(v)
+    }
+}
+
+#[juniper::graphql_object(context = Context)]
+impl DeleteAccountPayload {
+    fn user(&self) -> User {
+        self.0.user.clone().into()
+    }
+
+    /// When the account and its papers are removed for good.
+    fn purge_at(&self) -> Option<DateTime> {
+        self.0.user.purge_at.map(Into::into)
+    }
+
+    /// Undoes the deletion with `restoreAccount` until `purgeAt`.
+    fn restore_token(&self) -> &str {
+        &self.0.restore_token
+    }
+}
+
 pub struct User(paper::user::User);
 
 impl From<paper::user::User> for User {
@@ -49,20 +94,30 @@ impl From<paper::user::User> for User {
     }
 }
 
-#[juniper::graphql_object(context = Context)]
+#[juniper::graphql_object(context = Context, impl = NodeValue)]
 impl User {
-    fn id(&self) -> &str {
-        self.0.id.as_ref()
+    fn id(&self) -> ID {
+        user_global_id(&self.0.id)
     }
 
-    fn created_at(&self) -> String {
-        self.0.created_at.to_string()
+    fn created_at(&self) -> DateTime {
+        self.0.created_at.into()
     }
 
     fn name(&self) -> &str {
         &self.0.name
     }
 
+    fn role(&self) -> Role {
+        self.0.role.into()
+    }
+
+    fn disabled(&self) -> bool {
+        self.0.disabled
+    }
+
+    /// `deleted` is superseded by `filter.deleted`, they can not be given
+    /// together.
     async fn papers(
         &self,
         ctx: &Context,
@@ -73,94 +128,341 @@ impl User {
         last: Option<i32>,
         order_by: Option<PaperOrder>,
         deleted: Option<bool>,
+        filter: Option<PaperFilter>,
     ) -> Result<PaperConnection> {
-        let pagination = match (first, last) {
-            (Some(first), _) => Pagination::After {
-                after: after.map(|x| x.id),
-                skip: skip.map(|x| x as u64),
-                first: first as u64,
-            },
-            (_, Some(last)) => Pagination::Before {
-                before: before.map(|x| x.id),
-                skip: skip.map(|x| x as u64),
-                last: last as u64,
-            },
-            _ => {
-                return Err(Error::unknown(
-                    "Missing required parameter first or last".to_owned(),
-                ))
-            }
-        };
-
-        PaperConnection::new(
-            ctx,
-            PaperConnectionKind::User {
-                user_id: self.0.id.to_owned(),
-            },
-            pagination,
-            order_by,
-            deleted,
-        )
-        .await
-        .map_err(|e| e.into())
-    }
-
-    async fn paper(&self, ctx: &Context, paper_id: String) -> Result<Paper> {
-        let paper_service: Box<dyn PaperService> = ctx.module.provide().unwrap();
+        telemetry::resolver("User.papers", async {
+            let pagination = match (first, last) {
+                (Some(first), _) => Pagination::After {
+                    after: after.map(|x| x.into()),
+                    skip: ctx.page_skip(skip)?,
+                    first: ctx.page_size(first)?,
+                },
+                (_, Some(last)) => Pagination::Before {
+                    before: before.map(|x| x.into()),
+                    skip: ctx.page_skip(skip)?,
+                    last: ctx.page_size(last)?,
+                },
+                _ => {
+                    return Err(Error::invalid_input(
+                        "Missing required parameter first or last".to_owned(),
+                    ))
+                }
+            };
 
-        paper_service
-            .select_paper(
-                ctx.access_token()?.sub,
-                self.0.id.to_owned(),
-                paper_id.into(),
+            PaperConnection::new(
+                ctx,
+                PaperConnectionKind::User {
+                    user_id: self.0.id.to_owned(),
+                },
+                pagination,
+                order_by,
+                match (filter, deleted) {
+                    (Some(_), Some(_)) => {
+                        return Err(Error::invalid_input(
+                            "Give either deleted or filter.deleted".to_owned(),
+                        ))
+                    }
+                    (Some(filter), None) => filter.into(),
+                    (None, deleted) => paper::paper::PaperFilter {
+                        deleted: deleted.unwrap_or(false),
+                        ..Default::default()
+                    },
+                },
             )
             .await
-            .map(|x| x.into())
             .map_err(|e| e.into())
+        })
+        .await
+    }
+
+    /// The data exports requested with `requestDataExport`, newest first.
+    async fn data_exports(&self, ctx: &Context) -> Result<Vec<DataExport>> {
+        telemetry::resolver("User.dataExports", async {
+            let export_service: Box<dyn ExportService> = ctx.module.provide().unwrap();
+
+            export_service
+                .select_exports(ctx.access_token().await?.sub, self.0.id.to_owned())
+                .await
+                .map(|x| x.into_iter().map(Into::into).collect())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+
+    /// The webhooks registered with `createWebhook`, oldest first.
+    async fn webhooks(&self, ctx: &Context) -> Result<Vec<Webhook>> {
+        telemetry::resolver("User.webhooks", async {
+            let webhook_service: Box<dyn WebhookService> = ctx.module.provide().unwrap();
+
+            webhook_service
+                .select_webhooks(ctx.access_token().await?.sub, self.0.id.to_owned())
+                .await
+                .map(|x| x.into_iter().map(Into::into).collect())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+
+    /// The notifications of mentions and replies, newest first, read and
+    /// unread ones unless `unread` is given.
+    async fn notifications(
+        &self,
+        ctx: &Context,
+        first: i32,
+        after: Option<NotificationCursor>,
+        unread: Option<bool>,
+    ) -> Result<NotificationConnection> {
+        telemetry::resolver("User.notifications", async {
+            let notification_service: Box<dyn NotificationService> = ctx.module.provide().unwrap();
+
+            notification_service
+                .select_notification_page(
+                    ctx.access_token().await?.sub,
+                    self.0.id.to_owned(),
+                    Pagination::After {
+                        after: after.map(|x| x.into()),
+                        skip: None,
+                        first: ctx.page_size(first)?,
+                    },
+                    NotificationFilter { unread },
+                )
+                .await
+                .map(|list| NotificationConnection {
+                    user_id: self.0.id.to_owned(),
+                    list,
+                })
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+
+    /// The plan of the user and how much of its limits is used.
+    async fn usage(&self, ctx: &Context) -> Result<Usage> {
+        telemetry::resolver("User.usage", async {
+            let quota_service: Box<dyn QuotaService> = ctx.module.provide().unwrap();
+
+            quota_service
+                .select_usage(ctx.access_token().await?.sub, self.0.id.to_owned())
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+
+    /// The audit events the user did or underwent, newest first.
+    async fn audit_events(
+        &self,
+        ctx: &Context,
+        first: i32,
+        after: Option<AuditEventCursor>,
+    ) -> Result<AuditEventConnection> {
+        telemetry::resolver("User.auditEvents", async {
+            let audit_service: Box<dyn AuditService> = ctx.module.provide().unwrap();
+
+            audit_service
+                .select_audit_event_page(
+                    ctx.access_token().await?.sub,
+                    self.0.id.to_owned(),
+                    Pagination::After {
+                        after: after.map(|x| x.into()),
+                        skip: None,
+                        first: ctx.page_size(first)?,
+                    },
+                )
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+
+    async fn paper(&self, ctx: &Context, paper_id: ID) -> Result<Paper> {
+        telemetry::resolver("User.paper", async {
+            let paper_service: Box<dyn PaperService> = ctx.module.provide().unwrap();
+
+            paper_service
+                .select_paper(
+                    ctx.access_token().await?.sub,
+                    self.0.id.to_owned(),
+                    decode_paper_id(&paper_id)?,
+                )
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
     }
 
     async fn can_viewer_read_user(&self, ctx: &Context) -> Result<bool> {
-        let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
+        telemetry::resolver("User.canViewerReadUser", async {
+            let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
 
-        user_service
-            .can_viewer_read_user(ctx.access_token()?.sub, self.0.id.to_owned())
-            .await
-            .map_or_else(
-                |e| match e.kind {
-                    paper::ErrorKind::Forbidden => Ok(false),
-                    _ => Err(e.into()),
-                },
-                |_| Ok(true),
-            )
+            ctx.loaders
+                .permission(
+                    Permission::ReadUser,
+                    self.0.id.as_ref(),
+                    user_service
+                        .can_viewer_read_user(ctx.access_token().await?.sub, self.0.id.to_owned()),
+                )
+                .await
+        })
+        .await
     }
 
     async fn can_viewer_write_user(&self, ctx: &Context) -> Result<bool> {
-        let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
+        telemetry::resolver("User.canViewerWriteUser", async {
+            let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
 
-        user_service
-            .can_viewer_write_user(ctx.access_token()?.sub, self.0.id.to_owned())
-            .await
-            .map_or_else(
-                |e| match e.kind {
-                    paper::ErrorKind::Forbidden => Ok(false),
-                    _ => Err(e.into()),
-                },
-                |_| Ok(true),
-            )
+            ctx.loaders
+                .permission(
+                    Permission::WriteUser,
+                    self.0.id.as_ref(),
+                    user_service
+                        .can_viewer_write_user(ctx.access_token().await?.sub, self.0.id.to_owned()),
+                )
+                .await
+        })
+        .await
     }
 
     async fn can_viewer_administer_user(&self, ctx: &Context) -> Result<bool> {
-        let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
+        telemetry::resolver("User.canViewerAdministerUser", async {
+            let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
 
-        user_service
-            .can_viewer_administer_user(ctx.access_token()?.sub, self.0.id.to_owned())
-            .await
-            .map_or_else(
-                |e| match e.kind {
-                    paper::ErrorKind::Forbidden => Ok(false),
-                    _ => Err(e.into()),
-                },
-                |_| Ok(true),
-            )
+            ctx.loaders
+                .permission(
+                    Permission::AdministerUser,
+                    self.0.id.as_ref(),
+                    user_service.can_viewer_administer_user(
+                        ctx.access_token().await?.sub,
+                        self.0.id.to_owned(),
+                    ),
+                )
+                .await
+        })
+        .await
+    }
+}
+
+#[juniper::graphql_interface]
+impl Node for User {
+    fn id(&self) -> ID {
+        user_global_id(&self.0.id)
+    }
+}
+
+#[derive(GraphQLEnum)]
+pub enum Role {
+    Member,
+
+    Moderator,
+
+    Admin,
+}
+
+impl From<paper::user::Role> for Role {
+    fn from(v: paper::user::Role) -> Self {
+        match v {
+            paper::user::Role::Member => Self::Member,
+            paper::user::Role::Moderator => Self::Moderator,
+            paper::user::Role::Admin => Self::Admin,
+        }
+    }
+}
+
+impl Into<paper::user::Role> for Role {
+    fn into(self) -> paper::user::Role {
+        match self {
+            Self::Member => paper::user::Role::Member,
+            Self::Moderator => paper::user::Role::Moderator,
+            Self::Admin => paper::user::Role::Admin,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct UserCursor {
+    pub id: paper::user::UserId,
+}
+
+impl Cursor for UserCursor {}
+
+impl From<&paper::user::User> for UserCursor {
+    fn from(v: &paper::user::User) -> Self {
+        Self {
+            id: v.id.to_owned(),
+        }
+    }
+}
+
+impl Into<paper::user::UserCursor> for UserCursor {
+    fn into(self) -> paper::user::UserCursor {
+        paper::user::UserCursor { id: self.id }
+    }
+}
+
+#[juniper::graphql_scalar(description = "User Cursor")]
+impl<S> GraphQLScalar for UserCursor
+where
+    S: ScalarValue,
+{
+    fn resolve(&self) -> Value {
+        juniper::Value::scalar(self.encode())
+    }
+
+    fn from_input_value(v: &InputValue) -> Option<Self> {
+        v.as_scalar_value()
+            .and_then(|v| v.as_str())
+            .and_then(|s| Self::decode(s))
+    }
+
+    fn from_str<'a>(value: ScalarToken<'a>) -> juniper::ParseScalarResult<'a, S> {
+        <String as juniper::ParseScalarValue<S>>::from_str(value)
+    }
+}
+
+pub struct UserEdge(paper::user::User);
+
+#[juniper::graphql_object(context = Context)]
+impl UserEdge {
+    fn node(&self) -> User {
+        self.0.clone().into()
+    }
+
+    fn cursor(&self) -> UserCursor {
+        UserCursor::from(&self.0)
+    }
+}
+
+pub struct UserConnection(PaginationList<paper::user::User>);
+
+impl From<PaginationList<paper::user::User>> for UserConnection {
+    fn from(v: PaginationList<paper::user::User>) -> Self {
+        Self(v)
+    }
+}
+
+#[juniper::graphql_object(context = Context)]
+impl UserConnection {
+    async fn edges(&self) -> Vec<UserEdge> {
+        self.0.list.iter().map(|x| UserEdge(x.to_owned())).collect()
+    }
+
+    async fn nodes(&self) -> Vec<User> {
+        self.0.list.iter().map(|x| x.to_owned().into()).collect()
+    }
+
+    async fn page_info(&self) -> PageInfo {
+        PageInfo {
+            start_cursor: self.0.list.first().map(|x| UserCursor::from(x).encode()),
+            end_cursor: self.0.list.last().map(|x| UserCursor::from(x).encode()),
+            has_next_page: self.0.has_next_page,
+            has_previous_page: self.0.has_previous_page,
+        }
+    }
+
+    async fn total(&self) -> i32 {
+        self.0.total as i32
     }
 }
@@ -0,0 +1,266 @@
+use juniper::{GraphQLEnum, ID};
+use paper::{
+    webhook::{WebhookDeliveryId, WebhookService},
+    Pagination, PaginationList,
+};
+use serde::{Deserialize, Serialize};
+use shaku::HasProvider;
+
+use crate::*;
+
+/// The events a webhook subscribes to, `PAPER_CREATED` is posted as
+/// `paper.created` and so on.
+#[derive(GraphQLEnum, Clone, Copy)]
+pub enum WebhookEventKind {
+    PaperCreated,
+
+    PaperUpdated,
+
+    PaperDeleted,
+
+    UserUpdated,
+}
+
+impl From<paper::webhook::WebhookEventKind> for WebhookEventKind {
+    fn from(v: paper::webhook::WebhookEventKind) -> Self {
+        use paper::webhook::WebhookEventKind as Kind;
+
+        match v {
+            Kind::PaperCreated => Self::PaperCreated,
+            Kind::PaperUpdated => Self::PaperUpdated,
+            Kind::PaperDeleted => Self::PaperDeleted,
+            Kind::UserUpdated => Self::UserUpdated,
+        }
+    }
+}
+
+impl Into<paper::webhook::WebhookEventKind> for WebhookEventKind {
+    fn into(self) -> paper::webhook::WebhookEventKind {
+        use paper::webhook::WebhookEventKind as Kind;
+
+        match self {
+            Self::PaperCreated => Kind::PaperCreated,
+            Self::PaperUpdated => Kind::PaperUpdated,
+            Self::PaperDeleted => Kind::PaperDeleted,
+            Self::UserUpdated => Kind::UserUpdated,
+        }
+    }
+}
+
+#[derive(GraphQLEnum)]
+pub enum WebhookDeliveryStatus {
+    Pending,
+
+    Succeeded,
+
+    Failed,
+}
+
+pub struct Webhook(paper::webhook::Webhook);
+
+impl From<paper::webhook::Webhook> for Webhook {
+    fn from(v: paper::webhook::Webhook) -> Self {
+        Self(v)
+    }
+}
+
+#[juniper::graphql_object(context = Context)]
+impl Webhook {
+    fn id(&self) -> ID {
+        self.0.id.to_string().into()
+    }
+
+    fn url(&self) -> &String {
+        &self.0.url
+    }
+
+    fn events(&self) -> Vec<WebhookEventKind> {
+        self.0.events.iter().map(|x| (*x).into()).collect()
+    }
+
+    fn created_at(&self) -> DateTime {
+        self.0.created_at.into()
+    }
+
+    /// The delivery log, newest first.
+    async fn deliveries(
+        &self,
+        ctx: &Context,
+        first: i32,
+        after: Option<WebhookDeliveryCursor>,
+    ) -> Result<WebhookDeliveryConnection> {
+        telemetry::resolver("Webhook.deliveries", async {
+            let webhook_service: Box<dyn WebhookService> = ctx.module.provide().unwrap();
+
+            webhook_service
+                .select_delivery_page(
+                    ctx.access_token().await?.sub,
+                    self.0.id.to_owned(),
+                    Pagination::After {
+                        after: after.map(|x| x.into()),
+                        skip: None,
+                        first: ctx.page_size(first)?,
+                    },
+                )
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+}
+
+pub struct WebhookDelivery(paper::webhook::WebhookDelivery);
+
+#[juniper::graphql_object(context = Context)]
+impl WebhookDelivery {
+    fn id(&self) -> ID {
+        self.0.id.to_string().into()
+    }
+
+    fn event(&self) -> WebhookEventKind {
+        self.0.event.into()
+    }
+
+    fn status(&self) -> WebhookDeliveryStatus {
+        match self.0.status {
+            paper::webhook::WebhookDeliveryStatus::Pending => WebhookDeliveryStatus::Pending,
+            paper::webhook::WebhookDeliveryStatus::Succeeded => WebhookDeliveryStatus::Succeeded,
+            paper::webhook::WebhookDeliveryStatus::Failed => WebhookDeliveryStatus::Failed,
+        }
+    }
+
+    /// The JSON body which is posted, its HMAC-SHA256 is sent in the
+    /// `X-Paper-Signature` header.
+    fn payload(&self) -> &String {
+        &self.0.payload
+    }
+
+    fn attempts(&self) -> i32 {
+        self.0.attempts as i32
+    }
+
+    fn created_at(&self) -> DateTime {
+        self.0.created_at.into()
+    }
+
+    fn next_attempt_at(&self) -> Option<DateTime> {
+        self.0.next_attempt_at.map(Into::into)
+    }
+
+    fn last_attempt_at(&self) -> Option<DateTime> {
+        self.0.last_attempt_at.map(Into::into)
+    }
+
+    /// HTTP status of the response to the last attempt.
+    fn response_status(&self) -> Option<i32> {
+        self.0.response_status.map(|x| x as i32)
+    }
+
+    /// Why the last attempt has failed.
+    fn error(&self) -> Option<&String> {
+        self.0.error.as_ref()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct WebhookDeliveryCursor {
+    pub id: WebhookDeliveryId,
+}
+
+impl Cursor for WebhookDeliveryCursor {}
+
+impl From<&paper::webhook::WebhookDelivery> for WebhookDeliveryCursor {
+    fn from(v: &paper::webhook::WebhookDelivery) -> Self {
+        Self {
+            id: v.id.to_owned(),
+        }
+    }
+}
+
+impl Into<paper::webhook::WebhookDeliveryCursor> for WebhookDeliveryCursor {
+    fn into(self) -> paper::webhook::WebhookDeliveryCursor {
+        paper::webhook::WebhookDeliveryCursor { id: self.id }
+    }
+}
+
+#[juniper::graphql_scalar(description = "Webhook Delivery Cursor")]
+impl<S> GraphQLScalar for WebhookDeliveryCursor
+where
+    S: ScalarValue,
+{
+    fn resolve(&self) -> Value {
+        juniper::Value::scalar(self.encode())
+    }
+
+    fn from_input_value(v: &InputValue) -> Option<Self> {
+        v.as_scalar_value()
+            .and_then(|v| v.as_str())
+            .and_then(|s| Self::decode(s))
+    }
+
+    fn from_str<'a>(value: ScalarToken<'a>) -> juniper::ParseScalarResult<'a, S> {
+        <String as juniper::ParseScalarValue<S>>::from_str(value)
+    }
+}
+
+pub struct WebhookDeliveryEdge(paper::webhook::WebhookDelivery);
+
+#[juniper::graphql_object(context = Context)]
+impl WebhookDeliveryEdge {
+    fn node(&self) -> WebhookDelivery {
+        WebhookDelivery(self.0.clone())
+    }
+
+    fn cursor(&self) -> WebhookDeliveryCursor {
+        WebhookDeliveryCursor::from(&self.0)
+    }
+}
+
+pub struct WebhookDeliveryConnection(PaginationList<paper::webhook::WebhookDelivery>);
+
+impl From<PaginationList<paper::webhook::WebhookDelivery>> for WebhookDeliveryConnection {
+    fn from(v: PaginationList<paper::webhook::WebhookDelivery>) -> Self {
+        Self(v)
+    }
+}
+
+#[juniper::graphql_object(context = Context)]
+impl WebhookDeliveryConnection {
+    async fn edges(&self) -> Vec<WebhookDeliveryEdge> {
+        self.0
+            .list
+            .iter()
+            .map(|x| WebhookDeliveryEdge(x.to_owned()))
+            .collect()
+    }
+
+    async fn nodes(&self) -> Vec<WebhookDelivery> {
+        self.0
+            .list
+            .iter()
+            .map(|x| WebhookDelivery(x.to_owned()))
+            .collect()
+    }
+
+    async fn page_info(&self) -> PageInfo {
+        PageInfo {
+            start_cursor: self
+                .0
+                .list
+                .first()
+                .map(|x| WebhookDeliveryCursor::from(x).encode()),
+            end_cursor: self
+                .0
+                .list
+                .last()
+                .map(|x| WebhookDeliveryCursor::from(x).encode()),
+            has_next_page: self.0.has_next_page,
+            has_previous_page: self.0.has_previous_page,
+        }
+    }
+
+    async fn total(&self) -> i32 {
+        self.0.total as i32
+    }
+}
@@ -1,6 +1,11 @@
-use paper_impl::{auth::*, paper::*, user::*};
+use paper_impl::{
+    admin::*, attachment::*, audit::*, auth::*, blob::*, comment::*, event::*, export::*,
+    notification::*, paper::*, quota::*, user::*, webhook::*,
+};
 
-use crate::models::paper::PaperTokenConfig;
+use crate::models::{
+    attachment::AttachmentTokenConfig, export::ExportTokenConfig, paper::PaperTokenConfig,
+};
 
 crate::shaku_storage_collection_config!(
     UserCollectionConfigInterface,
@@ -16,26 +21,124 @@ crate::shaku_storage_collection_config!(
     PaperCollectionImpl
 );
 
+crate::shaku_storage_collection_config!(
+    CommentCollectionConfigInterface,
+    CommentCollectionConfig,
+    CommentCollection,
+    CommentCollectionImpl
+);
+
+crate::shaku_storage_collection_config!(
+    ExportCollectionConfigInterface,
+    ExportCollectionConfig,
+    ExportCollection,
+    ExportCollectionImpl
+);
+
+crate::shaku_storage_collection_config!(
+    AuditCollectionConfigInterface,
+    AuditCollectionConfig,
+    AuditCollection,
+    AuditCollectionImpl
+);
+
+crate::shaku_storage_collection_config!(
+    WebhookCollectionConfigInterface,
+    WebhookCollectionConfig,
+    WebhookCollection,
+    WebhookCollectionImpl
+);
+
+crate::shaku_storage_collection_config!(
+    WebhookDeliveryCollectionConfigInterface,
+    WebhookDeliveryCollectionConfig,
+    WebhookDeliveryCollection,
+    WebhookDeliveryCollectionImpl
+);
+
+crate::shaku_storage_collection_config!(
+    NotificationCollectionConfigInterface,
+    NotificationCollectionConfig,
+    NotificationCollection,
+    NotificationCollectionImpl
+);
+
+crate::shaku_storage_collection_config!(
+    AttachmentCollectionConfigInterface,
+    AttachmentCollectionConfig,
+    AttachmentCollection,
+    AttachmentCollectionImpl
+);
+
+crate::shaku_storage_collection_config!(
+    QuotaCollectionConfigInterface,
+    QuotaCollectionConfig,
+    QuotaCollection,
+    QuotaCollectionImpl
+);
+
 shaku::module! {
     pub Module {
         components = [
             UserCollectionConfig,
             PaperCollectionConfig,
+            CommentCollectionConfig,
+            ExportCollectionConfig,
+            AuditCollectionConfig,
+            WebhookCollectionConfig,
+            WebhookDeliveryCollectionConfig,
+            NotificationCollectionConfig,
+            AttachmentCollectionConfig,
+            QuotaCollectionConfig,
 
             AccessTokenConfig,
             RefreshTokenConfig,
             PaperTokenConfig,
+            ExportTokenConfig,
+            AttachmentTokenConfig,
 
             GithubAuthConfig,
             GoogleAuthConfig,
+
+            EventBus,
+
+            StorageDatabase,
+            BlobStorage,
+
+            AdminConfig,
+            AccountConfig,
+            ExportConfig,
+            AuditConfig,
+            WebhookConfig,
+            NotificationConfig,
+            AttachmentConfig,
+            QuotaConfig,
         ],
         providers = [
             UserCollectionImpl,
             PaperCollectionImpl,
+            CommentCollectionImpl,
+            ExportCollectionImpl,
+            AuditCollectionImpl,
+            WebhookCollectionImpl,
+            WebhookDeliveryCollectionImpl,
+            NotificationCollectionImpl,
+            AttachmentCollectionImpl,
+            QuotaCollectionImpl,
+
+            EventPublisherImpl,
 
             AuthServiceImpl,
             UserServiceImpl,
             PaperServiceImpl,
+            CommentServiceImpl,
+            AdminServiceImpl,
+            ExportServiceImpl,
+            AuditServiceImpl,
+            WebhookServiceImpl,
+            NotificationServiceImpl,
+            AttachmentServiceImpl,
+            QuotaServiceImpl,
         ]
     }
 }
@@ -1,13 +1,31 @@
 use std::convert::TryInto;
 
-use paper::{auth::AuthService, paper::PaperService, user::UserService};
-use shaku::HasProvider;
+use juniper::ID;
+use paper::{
+    attachment::{AttachmentService, CreateAttachmentInput},
+    audit::{AuditEventInput, AuditEventKind},
+    auth::{AccessTokenPayload, AuthService},
+    comment::{CommentService, CreateCommentInput},
+    export::ExportService,
+    notification::NotificationService,
+    paper::PaperService,
+    user::{Role, UserService},
+    webhook::{CreateWebhookInput, WebhookService},
+};
+use paper_impl::auth::{AccessTokenConfigInterface, RefreshTokenConfigInterface};
+use shaku::{HasComponent, HasProvider};
 
 use crate::{
     models::{
+        admin::AdminMutation,
+        attachment::{attachment_url, Attachment, AttachmentUpload},
         auth::{AccessToken, CreateAccessTokenInput},
-        paper::{DeletePaperPayload, Paper},
-        user::{UpdateUserInput, User},
+        comment::{Comment, CommentAnchorInput},
+        export::DataExport,
+        node::{decode_paper_id, decode_user_id},
+        paper::{DeletePaperPayload, Paper, UpdatePaperInput},
+        user::{DeleteAccountPayload, UpdateUserInput, User},
+        webhook::{Webhook, WebhookEventKind},
     },
     *,
 };
@@ -20,59 +38,504 @@ impl Mutation {
         ctx: &Context,
         input: CreateAccessTokenInput,
     ) -> Result<AccessToken> {
-        let auth_service: Box<dyn AuthService> = ctx.module.provide().unwrap();
+        telemetry::resolver("Mutation.createAccessToken", async {
+            ctx.rate_limiter
+                .check_auth(&ctx.client_ip)
+                .map_err(Error::rate_limited)?;
+
+            let input: paper::auth::CreateAccessTokenInput = input.try_into()?;
+            let (kind, provider) = match &input {
+                paper::auth::CreateAccessTokenInput::Github { .. } => {
+                    (AuditEventKind::SignIn, "github")
+                }
+                paper::auth::CreateAccessTokenInput::Google { .. }
+                | paper::auth::CreateAccessTokenInput::GoogleAccessToken { .. } => {
+                    (AuditEventKind::SignIn, "google")
+                }
+                paper::auth::CreateAccessTokenInput::RefreshToken { .. } => {
+                    (AuditEventKind::TokenRefresh, "refresh_token")
+                }
+            };
+
+            // The user of a refresh token which is refused, e.g. because it has
+            // expired or its user is disabled.
+            let refresh_user_id = match &input {
+                paper::auth::CreateAccessTokenInput::RefreshToken { refresh_token } => {
+                    let refresh_token_config: &dyn RefreshTokenConfigInterface =
+                        ctx.module.resolve_ref();
+                    AccessTokenPayload::decode_expired(refresh_token, &refresh_token_config.secret)
+                        .ok()
+                        .map(|x| x.sub)
+                }
+                _ => None,
+            };
+
+            let auth_service: Box<dyn AuthService> = ctx.module.provide().unwrap();
+
+            match auth_service.create_access_token(input).await {
+                Ok(access_token) => {
+                    let access_token_config: &dyn AccessTokenConfigInterface =
+                        ctx.module.resolve_ref();
+                    let user_id = AccessTokenPayload::decode(
+                        &access_token.access_token,
+                        &access_token_config.secret,
+                    )
+                    .ok()
+                    .map(|x| x.sub);
+                    ctx.audit(AuditEventInput {
+                        actor_id: user_id.to_owned(),
+                        user_id,
+                        details: Some(serde_json::json!({ "provider": provider })),
+                        ..AuditEventInput::new(kind)
+                    })
+                    .await;
+                    Ok(access_token.into())
+                }
+                Err(e) => {
+                    ctx.audit(AuditEventInput {
+                        user_id: refresh_user_id,
+                        details: Some(serde_json::json!({
+                            "provider": provider,
+                            "reason": e.to_string(),
+                        })),
+                        ..AuditEventInput::new(AuditEventKind::AuthFailure)
+                    })
+                    .await;
+                    Err(e.into())
+                }
+            }
+        })
+        .await
+    }
+
+    async fn update_user(ctx: &Context, user_id: ID, input: UpdateUserInput) -> Result<User> {
+        telemetry::resolver("Mutation.updateUser", async {
+            let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
+
+            let input: paper::user::UpdateUserInput = input.into();
+            let name = input.name.to_owned();
+
+            let user = user_service
+                .update_user(
+                    ctx.access_token().await?.sub,
+                    decode_user_id(&user_id)?,
+                    input,
+                )
+                .await?;
+
+            if let Some(name) = name {
+                ctx.audit(AuditEventInput {
+                    user_id: Some(user.id.to_owned()),
+                    details: Some(serde_json::json!({ "name": name })),
+                    ..AuditEventInput::new(AuditEventKind::UserRename)
+                })
+                .await;
+            }
+
+            Ok(user.into())
+        })
+        .await
+    }
+
+    /// Delete the account of the viewer and sign them out everywhere.
+    async fn delete_account(ctx: &Context) -> Result<DeleteAccountPayload> {
+        telemetry::resolver("Mutation.deleteAccount", async {
+            let access_token = ctx.access_token().await?;
+            if access_token.act.is_some() {
+                return Err(Error::forbidden(
+                    "An impersonated account can not be deleted".to_owned(),
+                ));
+            }
+
+            let auth_service: Box<dyn AuthService> = ctx.module.provide().unwrap();
+
+            let deletion = auth_service.delete_account(access_token.sub).await?;
+
+            ctx.audit(AuditEventInput {
+                user_id: Some(deletion.user.id.to_owned()),
+                ..AuditEventInput::new(AuditEventKind::AccountDelete)
+            })
+            .await;
+
+            Ok(deletion.into())
+        })
+        .await
+    }
+
+    async fn restore_account(ctx: &Context, restore_token: String) -> Result<User> {
+        telemetry::resolver("Mutation.restoreAccount", async {
+            ctx.rate_limiter
+                .check_auth(&ctx.client_ip)
+                .map_err(Error::rate_limited)?;
+
+            let auth_service: Box<dyn AuthService> = ctx.module.provide().unwrap();
 
-        auth_service
-            .create_access_token(input.try_into()?)
-            .await
-            .map(|x| x.into())
-            .map_err(|e| e.into())
+            let user = auth_service.restore_account(restore_token).await?;
+
+            ctx.audit(AuditEventInput {
+                actor_id: Some(user.id.to_owned()),
+                user_id: Some(user.id.to_owned()),
+                ..AuditEventInput::new(AuditEventKind::AccountRestore)
+            })
+            .await;
+
+            Ok(user.into())
+        })
+        .await
+    }
+
+    /// Queue an export of everything held about the viewer, it is assembled
+    /// in the background, poll `User.dataExports` for its download URL.
+    async fn request_data_export(ctx: &Context) -> Result<DataExport> {
+        telemetry::resolver("Mutation.requestDataExport", async {
+            let access_token = ctx.access_token().await?;
+            if access_token.act.is_some() {
+                return Err(Error::forbidden(
+                    "The data of an impersonated account can not be exported".to_owned(),
+                ));
+            }
+
+            let export_service: Box<dyn ExportService> = ctx.module.provide().unwrap();
+
+            let export = export_service
+                .request_export(access_token.sub.to_owned(), access_token.sub)
+                .await?;
+
+            ctx.audit(AuditEventInput {
+                user_id: Some(export.user_id.to_owned()),
+                details: Some(serde_json::json!({ "export_id": export.id.to_string() })),
+                ..AuditEventInput::new(AuditEventKind::DataExport)
+            })
+            .await;
+
+            if export.status == paper::export::ExportStatus::Pending {
+                let module = ctx.module.clone();
+                let export_id = export.id.to_owned();
+                actix_web::rt::spawn(async move {
+                    let export_service: Box<dyn ExportService> = module.provide().unwrap();
+                    match export_service.build_export(export_id.to_owned()).await {
+                        // Already being assembled by a previous request.
+                        Err(e) if e.kind == paper::ErrorKind::NotFound => {}
+                        Err(e) => log::error!("Failed to build export {}: {}", export_id, e),
+                        Ok(_) => {}
+                    }
+                });
+            }
+
+            Ok(export.into())
+        })
+        .await
+    }
+
+    /// Register a webhook of the viewer. The events are posted to `url` as
+    /// JSON, with the hex HMAC-SHA256 of the body keyed by `secret` in the
+    /// `X-Paper-Signature: sha256=<signature>` header.
+    async fn create_webhook(
+        ctx: &Context,
+        url: String,
+        events: Vec<WebhookEventKind>,
+        secret: String,
+    ) -> Result<Webhook> {
+        telemetry::resolver("Mutation.createWebhook", async {
+            let access_token = ctx.access_token().await?;
+            if access_token.act.is_some() {
+                return Err(Error::forbidden(
+                    "Webhooks can not be created for an impersonated account".to_owned(),
+                ));
+            }
+
+            let webhook_service: Box<dyn WebhookService> = ctx.module.provide().unwrap();
+
+            webhook_service
+                .create_webhook(
+                    access_token.sub.to_owned(),
+                    access_token.sub,
+                    CreateWebhookInput {
+                        url,
+                        events: events.into_iter().map(Into::into).collect(),
+                        secret,
+                    },
+                )
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
     }
 
-    async fn update_user(ctx: &Context, user_id: String, input: UpdateUserInput) -> Result<User> {
-        let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
+    async fn delete_webhook(ctx: &Context, webhook_id: ID) -> Result<Webhook> {
+        telemetry::resolver("Mutation.deleteWebhook", async {
+            let webhook_service: Box<dyn WebhookService> = ctx.module.provide().unwrap();
 
-        user_service
-            .update_user(ctx.access_token()?.sub, user_id.into(), input.into())
-            .await
-            .map(|x| x.into())
-            .map_err(|e| e.into())
+            webhook_service
+                .delete_webhook(ctx.access_token().await?.sub, webhook_id.as_str().into())
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
     }
 
-    async fn create_paper(ctx: &Context, user_id: String) -> Result<Paper> {
-        let paper_service: Box<dyn PaperService> = ctx.module.provide().unwrap();
+    async fn create_paper(ctx: &Context, user_id: ID) -> Result<Paper> {
+        telemetry::resolver("Mutation.createPaper", async {
+            let paper_service: Box<dyn PaperService> = ctx.module.provide().unwrap();
+
+            let paper = paper_service
+                .create_paper(ctx.access_token().await?.sub, decode_user_id(&user_id)?)
+                .await?;
 
-        paper_service
-            .create_paper(ctx.access_token()?.sub, user_id.to_owned().into())
-            .await
-            .map(|x| x.into())
-            .map_err(|e| e.into())
+            ctx.audit(AuditEventInput {
+                user_id: Some(paper.user_id.to_owned()),
+                paper_id: Some(paper.id.to_owned()),
+                ..AuditEventInput::new(AuditEventKind::PaperCreate)
+            })
+            .await;
+
+            Ok(paper.into())
+        })
+        .await
     }
 
-    async fn delete_paper(
+    async fn update_paper(
         ctx: &Context,
-        user_id: String,
-        paper_id: String,
-    ) -> Result<DeletePaperPayload> {
-        let paper_service: Box<dyn PaperService> = ctx.module.provide().unwrap();
-
-        let payload = paper_service
-            .select_paper(
-                ctx.access_token()?.sub,
-                user_id.to_owned().into(),
-                paper_id.to_owned().into(),
-            )
-            .await
-            .map(|x| x.into())?;
-
-        paper_service
-            .delete_paper(
-                ctx.access_token()?.sub,
-                user_id.into(),
-                paper_id.to_owned().into(),
-            )
-            .await?;
-
-        Ok(payload)
+        user_id: ID,
+        paper_id: ID,
+        input: UpdatePaperInput,
+    ) -> Result<Paper> {
+        telemetry::resolver("Mutation.updatePaper", async {
+            let paper_service: Box<dyn PaperService> = ctx.module.provide().unwrap();
+
+            let paper = paper_service
+                .update_paper(
+                    ctx.access_token().await?.sub,
+                    decode_user_id(&user_id)?,
+                    decode_paper_id(&paper_id)?,
+                    input.into(),
+                )
+                .await?;
+
+            ctx.audit(AuditEventInput {
+                user_id: Some(paper.user_id.to_owned()),
+                paper_id: Some(paper.id.to_owned()),
+                ..AuditEventInput::new(AuditEventKind::PaperUpdate)
+            })
+            .await;
+
+            Ok(paper.into())
+        })
+        .await
+    }
+
+    async fn delete_paper(ctx: &Context, user_id: ID, paper_id: ID) -> Result<DeletePaperPayload> {
+        telemetry::resolver("Mutation.deletePaper", async {
+            let paper_service: Box<dyn PaperService> = ctx.module.provide().unwrap();
+
+            let user_id = decode_user_id(&user_id)?;
+            let paper_id = decode_paper_id(&paper_id)?;
+
+            let payload = paper_service
+                .select_paper(
+                    ctx.access_token().await?.sub,
+                    user_id.to_owned(),
+                    paper_id.to_owned(),
+                )
+                .await
+                .map(|x| x.into())?;
+
+            paper_service
+                .delete_paper(
+                    ctx.access_token().await?.sub,
+                    user_id.to_owned(),
+                    paper_id.to_owned(),
+                )
+                .await?;
+
+            ctx.audit(AuditEventInput {
+                user_id: Some(user_id),
+                paper_id: Some(paper_id),
+                ..AuditEventInput::new(AuditEventKind::PaperDelete)
+            })
+            .await;
+
+            Ok(payload)
+        })
+        .await
+    }
+
+    /// Attach a file of `size` bytes to the paper, its content is uploaded to
+    /// the returned URL. The size counts towards the storage quota of the
+    /// owner of the paper from now on.
+    async fn create_attachment(
+        ctx: &Context,
+        user_id: ID,
+        paper_id: ID,
+        name: String,
+        content_type: String,
+        size: i32,
+    ) -> Result<AttachmentUpload> {
+        telemetry::resolver("Mutation.createAttachment", async {
+            if size < 0 {
+                return Err(Error::invalid_input(
+                    "The size can not be negative".to_owned(),
+                ));
+            }
+
+            let viewer_id = ctx.access_token().await?.sub;
+
+            let attachment_service: Box<dyn AttachmentService> = ctx.module.provide().unwrap();
+
+            let attachment = attachment_service
+                .create_attachment(
+                    viewer_id.to_owned(),
+                    decode_user_id(&user_id)?,
+                    decode_paper_id(&paper_id)?,
+                    CreateAttachmentInput {
+                        name,
+                        content_type,
+                        size: size as u64,
+                    },
+                )
+                .await?;
+
+            Ok(AttachmentUpload {
+                upload_url: attachment_url(ctx, &attachment.id, viewer_id, true),
+                attachment: attachment.into(),
+            })
+        })
+        .await
+    }
+
+    async fn delete_attachment(ctx: &Context, attachment_id: ID) -> Result<Attachment> {
+        telemetry::resolver("Mutation.deleteAttachment", async {
+            let attachment_service: Box<dyn AttachmentService> = ctx.module.provide().unwrap();
+
+            attachment_service
+                .delete_attachment(ctx.access_token().await?.sub, attachment_id.as_str().into())
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+
+    /// Comment on the paper, or reply to the thread of `parent_id`. Only a
+    /// thread is anchored to a range of the content.
+    async fn add_comment(
+        ctx: &Context,
+        user_id: ID,
+        paper_id: ID,
+        body: String,
+        anchor: Option<CommentAnchorInput>,
+        parent_id: Option<ID>,
+    ) -> Result<Comment> {
+        telemetry::resolver("Mutation.addComment", async {
+            let comment_service: Box<dyn CommentService> = ctx.module.provide().unwrap();
+
+            comment_service
+                .create_comment(
+                    ctx.access_token().await?.sub,
+                    decode_user_id(&user_id)?,
+                    decode_paper_id(&paper_id)?,
+                    CreateCommentInput {
+                        body,
+                        anchor: anchor.map(|x| x.try_into_anchor()).transpose()?,
+                        parent_id: parent_id.map(|x| x.as_str().into()),
+                    },
+                )
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+
+    async fn edit_comment(ctx: &Context, comment_id: ID, body: String) -> Result<Comment> {
+        telemetry::resolver("Mutation.editComment", async {
+            let comment_service: Box<dyn CommentService> = ctx.module.provide().unwrap();
+
+            comment_service
+                .update_comment(
+                    ctx.access_token().await?.sub,
+                    comment_id.as_str().into(),
+                    body,
+                )
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+
+    async fn delete_comment(ctx: &Context, comment_id: ID) -> Result<Comment> {
+        telemetry::resolver("Mutation.deleteComment", async {
+            let comment_service: Box<dyn CommentService> = ctx.module.provide().unwrap();
+
+            comment_service
+                .delete_comment(ctx.access_token().await?.sub, comment_id.as_str().into())
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+
+    /// Resolve the thread of the comment, or reopen it when `resolved` is
+    /// false. The root comment of the thread is returned.
+    async fn resolve_thread(
+        ctx: &Context,
+        comment_id: ID,
+        resolved: Option<bool>,
+    ) -> Result<Comment> {
+        telemetry::resolver("Mutation.resolveThread", async {
+            let comment_service: Box<dyn CommentService> = ctx.module.provide().unwrap();
+
+            comment_service
+                .resolve_thread(
+                    ctx.access_token().await?.sub,
+                    comment_id.as_str().into(),
+                    resolved.unwrap_or(true),
+                )
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+
+    /// Mark the notifications of the viewer as read, all of them when
+    /// `notification_ids` is not given. Returns how many have been marked.
+    async fn mark_notifications_read(
+        ctx: &Context,
+        notification_ids: Option<Vec<ID>>,
+    ) -> Result<i32> {
+        telemetry::resolver("Mutation.markNotificationsRead", async {
+            let viewer_id = ctx.access_token().await?.sub;
+
+            let notification_service: Box<dyn NotificationService> = ctx.module.provide().unwrap();
+
+            notification_service
+                .mark_notifications_read(
+                    viewer_id.to_owned(),
+                    viewer_id,
+                    notification_ids.map(|x| x.iter().map(|x| x.as_str().into()).collect()),
+                )
+                .await
+                .map(|x| x as i32)
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+
+    async fn admin(ctx: &Context) -> Result<AdminMutation> {
+        telemetry::resolver("Mutation.admin", async {
+            let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
+
+            user_service
+                .can_viewer_act_as(ctx.access_token().await?.sub, Role::Admin)
+                .await?;
+
+            Ok(AdminMutation)
+        })
+        .await
     }
 }
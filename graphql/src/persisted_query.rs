@@ -0,0 +1,166 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::Read,
+    sync::Mutex,
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{config::ConfigPersistedQueries, Error, GraphQLOperation, Result};
+
+/// Queries registered by clients, the oldest one is evicted once the cache is
+/// full.
+#[derive(Default)]
+struct Cache {
+    queries: HashMap<String, String>,
+
+    order: VecDeque<String>,
+}
+
+/// Persisted queries loaded from the manifest and registered by clients with
+/// the Apollo `persistedQuery` extension. One instance is shared by all http
+/// workers.
+pub struct PersistedQueries {
+    config: ConfigPersistedQueries,
+
+    manifest: HashMap<String, String>,
+
+    cache: Mutex<Cache>,
+}
+
+impl PersistedQueries {
+    pub fn new(
+        config: ConfigPersistedQueries,
+    ) -> std::result::Result<Self, Box<dyn std::error::Error>> {
+        let manifest = match &config.manifest {
+            Some(path) => Self::load_manifest(path)?,
+            None => HashMap::new(),
+        };
+
+        Ok(Self {
+            config,
+            manifest,
+            cache: Mutex::new(Cache::default()),
+        })
+    }
+
+    fn load_manifest(
+        path: &str,
+    ) -> std::result::Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+        let mut file = File::open(path)?;
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+
+        let manifest: HashMap<String, String> = serde_json::from_str(&content)?;
+
+        for (hash, query) in manifest.iter() {
+            if !hash.eq_ignore_ascii_case(&sha256(query)) {
+                return Err(format!("Persisted query {} does not match its hash", hash).into());
+            }
+        }
+
+        Ok(manifest
+            .into_iter()
+            .map(|(hash, query)| (hash.to_ascii_lowercase(), query))
+            .collect())
+    }
+
+    pub fn max_age_sec(&self) -> u64 {
+        self.config.get_max_age_sec
+    }
+
+    /// Fill in the query of an operation sent by hash, or register the query
+    /// of an operation sent with both. In allow list mode every query must be
+    /// part of the manifest.
+    pub fn resolve(&self, operation: &mut GraphQLOperation) -> Result<()> {
+        let hash = operation
+            .persisted_query_hash()
+            .map(|x| x.to_ascii_lowercase());
+
+        match (operation.query.as_ref(), hash) {
+            (None, None) => Err(Error::unknown("Query is not present".to_owned())),
+            (None, Some(hash)) => {
+                operation.query = Some(self.get(&hash)?);
+                Ok(())
+            }
+            (Some(query), hash) => {
+                if !self.config.allow_list_only && hash.is_none() {
+                    return Ok(());
+                }
+
+                let query_hash = sha256(query);
+
+                if hash.map_or(false, |x| x != query_hash) {
+                    return Err(Error::unknown(
+                        "Provided sha256Hash does not match query".to_owned(),
+                    ));
+                }
+
+                if self.config.allow_list_only {
+                    return match self.manifest.contains_key(&query_hash) {
+                        true => Ok(()),
+                        false => Err(Error::forbidden(
+                            "Query is not in the allow list".to_owned(),
+                        )),
+                    };
+                }
+
+                if self.config.enabled {
+                    self.register(query_hash, query.to_owned());
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    fn get(&self, hash: &str) -> Result<String> {
+        if let Some(query) = self.manifest.get(hash) {
+            return Ok(query.to_owned());
+        }
+
+        if self.config.allow_list_only {
+            return Err(Error::forbidden(
+                "Query is not in the allow list".to_owned(),
+            ));
+        }
+
+        if !self.config.enabled {
+            return Err(Error::persisted_query_not_supported());
+        }
+
+        self.cache
+            .lock()
+            .expect("Persisted query lock poisoned")
+            .queries
+            .get(hash)
+            .cloned()
+            .ok_or_else(Error::persisted_query_not_found)
+    }
+
+    fn register(&self, hash: String, query: String) {
+        if self.config.cache_size == 0 || self.manifest.contains_key(&hash) {
+            return;
+        }
+
+        let mut cache = self.cache.lock().expect("Persisted query lock poisoned");
+
+        if cache.queries.contains_key(&hash) {
+            return;
+        }
+
+        while cache.order.len() >= self.config.cache_size {
+            if let Some(oldest) = cache.order.pop_front() {
+                cache.queries.remove(&oldest);
+            }
+        }
+
+        cache.order.push_back(hash.to_owned());
+        cache.queries.insert(hash, query);
+    }
+}
+
+fn sha256(query: &str) -> String {
+    hex::encode(Sha256::digest(query.as_bytes()))
+}
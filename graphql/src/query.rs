@@ -1,33 +1,65 @@
 use std::convert::TryInto;
 
-use paper::user::{UserIdentifier, UserService};
+use juniper::ID;
+use paper::user::{Role, UserService};
 use shaku::HasProvider;
 
-use crate::{models::user::User, *};
+use crate::{
+    models::{
+        admin::Admin,
+        node::{self, NodeValue},
+        user::User,
+    },
+    *,
+};
 
 pub struct Query;
 
 #[juniper::graphql_object(context = Context)]
 impl super::Query {
     async fn viewer(ctx: &Context) -> Result<User> {
-        let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
+        telemetry::resolver("Query.viewer", async {
+            let user_id = ctx.access_token().await?.sub;
 
-        let user_id = ctx.access_token()?.sub;
-
-        user_service
-            .select_user(UserIdentifier::Id(user_id))
-            .await
-            .map(|x| x.into())
-            .map_err(|e| e.into())
+            ctx.loaders.user(user_id).await.map(|x| x.into())
+        })
+        .await
     }
 
     async fn user(ctx: &Context, identifier: super::models::user::UserIdentifier) -> Result<User> {
-        let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
+        telemetry::resolver("Query.user", async {
+            let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
+
+            user_service
+                .select_user(identifier.try_into()?)
+                .await
+                .map(|x| x.into())
+                .map_err(|e| e.into())
+        })
+        .await
+    }
+
+    async fn admin(ctx: &Context) -> Result<Admin> {
+        telemetry::resolver("Query.admin", async {
+            let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
+
+            user_service
+                .can_viewer_act_as(ctx.access_token().await?.sub, Role::Moderator)
+                .await?;
+
+            Ok(Admin)
+        })
+        .await
+    }
+
+    async fn node(ctx: &Context, id: ID) -> Result<Option<NodeValue>> {
+        telemetry::resolver("Query.node", async { node::node(ctx, &id).await }).await
+    }
 
-        user_service
-            .select_user(identifier.try_into()?)
-            .await
-            .map(|x| x.into())
-            .map_err(|e| e.into())
+    async fn nodes(ctx: &Context, ids: Vec<ID>) -> Result<Vec<Option<NodeValue>>> {
+        telemetry::resolver("Query.nodes", async {
+            futures::future::try_join_all(ids.iter().map(|id| node::node(ctx, id))).await
+        })
+        .await
     }
 }
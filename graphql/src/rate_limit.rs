@@ -0,0 +1,226 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    dev::{Service, ServiceRequest, ServiceResponse, Transform},
+    http::header,
+    Error, HttpResponse,
+};
+use futures::future::{ok, LocalBoxFuture, Ready};
+use paper::auth::AccessTokenPayload;
+
+use crate::config::{ConfigRateLimit, ConfigTokenBucket};
+
+/// A bucket keeps the limits it was created with, so it refills at its own
+/// rate whichever kind of request touches the map.
+struct Bucket {
+    tokens: f64,
+
+    capacity: f64,
+
+    refill_per_sec: f64,
+
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        self.updated_at = now;
+    }
+}
+
+/// Longest wait reported to a client, for buckets which never refill.
+const MAX_RETRY_AFTER_SEC: u64 = 3600;
+
+/// Token buckets keyed by client ip, user id and auth attempts. One instance
+/// is shared by all http workers.
+pub struct RateLimiter {
+    config: ConfigRateLimit,
+
+    access_token_secret: String,
+
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: ConfigRateLimit, access_token_secret: String) -> Self {
+        Self {
+            config,
+            access_token_secret,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token for an anonymous request from `ip`, returns the seconds to
+    /// wait when the bucket is empty.
+    pub fn check_ip(&self, ip: &str) -> Result<(), u64> {
+        self.take(format!("ip:{}", ip), self.config.ip)
+    }
+
+    /// Take a token for an authenticated request of `user_id`.
+    pub fn check_user(&self, user_id: &str) -> Result<(), u64> {
+        self.take(format!("user:{}", user_id), self.config.user)
+    }
+
+    /// Take a token for a sign in or token refresh attempt from `ip`.
+    pub fn check_auth(&self, ip: &str) -> Result<(), u64> {
+        self.take(format!("auth:{}", ip), self.config.auth)
+    }
+
+    fn take(&self, key: String, config: ConfigTokenBucket) -> Result<(), u64> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().expect("Rate limiter lock poisoned");
+
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: config.capacity as f64,
+            capacity: config.capacity as f64,
+            refill_per_sec: config.refill_per_sec,
+            updated_at: now,
+        });
+        bucket.refill(now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else if bucket.refill_per_sec > 0.0 {
+            let retry_after = ((1.0 - bucket.tokens) / bucket.refill_per_sec).ceil() as u64;
+            Err(retry_after.min(MAX_RETRY_AFTER_SEC))
+        } else {
+            Err(MAX_RETRY_AFTER_SEC)
+        }
+    }
+
+    /// Drop the buckets which are full again, they are recreated full when
+    /// their client comes back, so the map only holds recently active
+    /// clients.
+    pub fn prune(&self) {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().expect("Rate limiter lock poisoned");
+
+        buckets.retain(|_, bucket| {
+            bucket.refill(now);
+            bucket.tokens < bucket.capacity
+        });
+    }
+
+    /// Prune every `interval` for as long as the server runs.
+    pub async fn watch(self: Arc<Self>, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            self.prune();
+        }
+    }
+
+    /// Client ip of the request, honouring proxy headers only when trusted.
+    pub fn client_ip(&self, req: &actix_web::HttpRequest) -> String {
+        if self.config.trust_proxy {
+            if let Some(ip) = req.connection_info().realip_remote_addr() {
+                return ip.to_owned();
+            }
+        }
+
+        req.peer_addr()
+            .map(|x| x.ip().to_string())
+            .unwrap_or_default()
+    }
+
+    fn user_id(&self, req: &ServiceRequest) -> Option<String> {
+        req.headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|x| x.to_str().ok())
+            .filter(|x| x.starts_with("Bearer "))
+            .and_then(|x| {
+                AccessTokenPayload::decode(
+                    x.trim_start_matches("Bearer "),
+                    &self.access_token_secret,
+                )
+                .ok()
+            })
+            .map(|x| x.sub.to_string())
+    }
+}
+
+/// Middleware limiting requests to `/graphql` and connections to
+/// `/subscriptions`, keyed by user id for requests with a valid access token
+/// and by client ip otherwise. Browsers can't set headers on a WebSocket, so
+/// connections are mostly keyed by client ip.
+pub struct RateLimit(pub Arc<RateLimiter>);
+
+impl<S, B> Transform<S> for RateLimit
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = RateLimitMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(RateLimitMiddleware {
+            service,
+            limiter: self.0.clone(),
+        })
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: S,
+
+    limiter: Arc<RateLimiter>,
+}
+
+impl<S, B> Service for RateLimitMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        if !req.path().starts_with("/graphql") && !req.path().starts_with("/subscriptions") {
+            return Box::pin(self.service.call(req));
+        }
+
+        let checked = match self.limiter.user_id(&req) {
+            Some(user_id) => self.limiter.check_user(&user_id),
+            None => self
+                .limiter
+                .check_ip(&self.limiter.client_ip(req.request())),
+        };
+
+        match checked {
+            Ok(()) => Box::pin(self.service.call(req)),
+            Err(retry_after) => {
+                let response = HttpResponse::TooManyRequests()
+                    .header(header::RETRY_AFTER, retry_after.to_string())
+                    .json(crate::Error::rate_limited(retry_after).to_response_body())
+                    .into_body();
+
+                Box::pin(ok(req.into_response(response)))
+            }
+        }
+    }
+}
@@ -0,0 +1,19 @@
+use std::sync::{Arc, RwLock};
+
+/// A value swapped as a whole on reload, readers keep the snapshot they
+/// loaded until they drop it.
+pub struct Reloadable<T>(RwLock<Arc<T>>);
+
+impl<T> Reloadable<T> {
+    pub fn new(value: T) -> Self {
+        Self(RwLock::new(Arc::new(value)))
+    }
+
+    pub fn load(&self) -> Arc<T> {
+        self.0.read().expect("Reload lock poisoned").clone()
+    }
+
+    pub fn store(&self, value: T) {
+        *self.0.write().expect("Reload lock poisoned") = Arc::new(value);
+    }
+}
@@ -0,0 +1,67 @@
+use actix_web::middleware::DefaultHeaders;
+use sha2::{Digest, Sha256};
+
+use crate::config::ConfigSecurityHeaders;
+
+/// Policy of the API responses, nothing they contain should be loaded or
+/// framed by a browser.
+const API_CSP: &str = "default-src 'none'; frame-ancestors 'none'";
+
+/// Hosts GraphiQL loads its scripts and styles from.
+const GRAPHIQL_CDN: &str = "cdn.jsdelivr.net cdnjs.cloudflare.com unpkg.com";
+
+/// Headers added to every response which does not set them itself.
+pub fn security_headers(config: &ConfigSecurityHeaders) -> DefaultHeaders {
+    let headers = DefaultHeaders::new()
+        .header("X-Content-Type-Options", "nosniff")
+        .header("X-Frame-Options", "DENY")
+        .header("Referrer-Policy", "no-referrer")
+        .header("Content-Security-Policy", API_CSP);
+
+    match config.hsts_max_age_sec {
+        0 => headers,
+        max_age => headers.header(
+            "Strict-Transport-Security",
+            match config.hsts_include_subdomains {
+                true => format!("max-age={}; includeSubDomains", max_age),
+                false => format!("max-age={}", max_age),
+            },
+        ),
+    }
+}
+
+/// Policy of the GraphiQL page, its inline script and style are allowed by
+/// their hash.
+pub fn graphiql_csp(html: &str) -> String {
+    format!(
+        "default-src 'none'; script-src {} {}; style-src {} {}; font-src {} data:; \
+         img-src 'self' data:; connect-src 'self'; base-uri 'none'; frame-ancestors 'none'",
+        GRAPHIQL_CDN,
+        inline_hashes(html, "script"),
+        GRAPHIQL_CDN,
+        inline_hashes(html, "style"),
+        GRAPHIQL_CDN,
+    )
+}
+
+/// CSP sources of the `<tag>` elements of `html` without attributes.
+fn inline_hashes(html: &str, tag: &str) -> String {
+    let open = format!("<{}>", tag);
+    let close = format!("</{}>", tag);
+
+    let mut hashes = Vec::new();
+    let mut rest = html;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start + open.len()..];
+        let end = match rest.find(&close) {
+            Some(end) => end,
+            None => break,
+        };
+        hashes.push(format!(
+            "'sha256-{}'",
+            base64::encode(Sha256::digest(rest[..end].as_bytes()))
+        ));
+        rest = &rest[end + close.len()..];
+    }
+    hashes.join(" ")
+}
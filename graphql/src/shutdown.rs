@@ -0,0 +1,26 @@
+use lazy_static::lazy_static;
+use tokio::sync::watch;
+
+lazy_static! {
+    static ref SHUTDOWN: (watch::Sender<bool>, watch::Receiver<bool>) = watch::channel(false);
+}
+
+/// Start draining, readiness fails and subscriptions complete so that
+/// clients reconnect to another instance.
+pub fn trigger() {
+    let _ = SHUTDOWN.0.broadcast(true);
+}
+
+pub fn is_triggered() -> bool {
+    *SHUTDOWN.1.borrow()
+}
+
+/// Resolves once the shutdown has been triggered.
+pub async fn triggered() {
+    let mut receiver = SHUTDOWN.1.clone();
+    while let Some(triggered) = receiver.recv().await {
+        if triggered {
+            return;
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use std::{future::Future, pin::Pin, time::Duration};
+
+use futures::{future, stream, FutureExt, Stream, StreamExt};
+use juniper::ID;
+use paper::{
+    auth::AccessTokenPayload,
+    notification::Notification as NotificationValue,
+    paper::{PaperEvent, PaperService},
+    user::{UserIdentifier, UserService},
+};
+use paper_impl::event::{Event, EventBusInterface};
+use shaku::{HasComponent, HasProvider};
+use tokio::sync::broadcast::RecvError;
+
+use crate::{
+    models::{
+        node::{decode_paper_id, decode_user_id},
+        notification::Notification,
+        paper::{Paper, PaperChangedPayload},
+    },
+    *,
+};
+
+pub struct Subscription;
+
+type PaperChangedStream = Pin<Box<dyn Stream<Item = PaperChangedPayload> + Send>>;
+
+type PaperStream = Pin<Box<dyn Stream<Item = Paper> + Send>>;
+
+type NotificationStream = Pin<Box<dyn Stream<Item = Notification> + Send>>;
+
+#[juniper::graphql_subscription(context = Context)]
+impl Subscription {
+    async fn paper_changed(ctx: &Context, user_id: ID) -> Result<PaperChangedStream> {
+        telemetry::resolver("Subscription.paperChanged", async {
+            let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
+
+            let user_id = decode_user_id(&user_id)?;
+
+            let access_token = ctx.verify_access_token().await?;
+            user_service
+                .can_viewer_read_user(access_token.sub.to_owned(), user_id.to_owned())
+                .await?;
+
+            let stream = paper_events(ctx, &access_token)
+                .filter(move |e| future::ready(e.paper.user_id == user_id))
+                .map(|e| e.into());
+
+            // Boxed here, `resolver` gives the block no return type to coerce to.
+            let stream: PaperChangedStream = Box::pin(stream);
+            Ok(stream)
+        })
+        .await
+    }
+
+    async fn paper_updated(ctx: &Context, paper_id: ID) -> Result<PaperStream> {
+        telemetry::resolver("Subscription.paperUpdated", async {
+            let paper_id = decode_paper_id(&paper_id)?;
+            let access_token = ctx.verify_access_token().await?;
+            let viewer_id = access_token.sub.to_owned();
+            let module = ctx.module.clone();
+
+            let stream = paper_events(ctx, &access_token)
+                .filter(move |e| future::ready(e.paper.id == paper_id))
+                .filter_map(move |e| {
+                    let paper_service: Box<dyn PaperService> = module.provide().unwrap();
+                    let viewer_id = viewer_id.to_owned();
+
+                    async move {
+                        paper_service
+                            .can_viewer_read_paper(
+                                viewer_id,
+                                e.paper.user_id.to_owned(),
+                                e.paper.id.to_owned(),
+                            )
+                            .await
+                            .ok()
+                            .map(|_| e.paper.into())
+                    }
+                });
+
+            let stream: PaperStream = Box::pin(stream);
+            Ok(stream)
+        })
+        .await
+    }
+
+    /// The notifications of the viewer as they are added.
+    async fn notification_added(ctx: &Context) -> Result<NotificationStream> {
+        telemetry::resolver("Subscription.notificationAdded", async {
+            let access_token = ctx.verify_access_token().await?;
+            let viewer_id = access_token.sub.to_owned();
+
+            let stream = notifications(ctx, &access_token)
+                .filter(move |x| future::ready(x.user_id == viewer_id))
+                .map(|x| x.into());
+
+            let stream: NotificationStream = Box::pin(stream);
+            Ok(stream)
+        })
+        .await
+    }
+}
+
+/// Paper events until the server starts shutting down or the session ends.
+fn paper_events(
+    ctx: &Context,
+    access_token: &AccessTokenPayload,
+) -> impl Stream<Item = PaperEvent> + Send {
+    let event_bus: &dyn EventBusInterface = ctx.module.resolve_ref();
+
+    stream::unfold(event_bus.subscribe(), |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(Event::Paper(event)) => return Some((event, receiver)),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+    .take_until(future::select(
+        Box::pin(shutdown::triggered()),
+        session_ended(ctx, access_token),
+    ))
+}
+
+/// Notifications of all users until the server starts shutting down or the
+/// session ends.
+fn notifications(
+    ctx: &Context,
+    access_token: &AccessTokenPayload,
+) -> impl Stream<Item = NotificationValue> + Send {
+    let event_bus: &dyn EventBusInterface = ctx.module.resolve_ref();
+
+    stream::unfold(event_bus.subscribe(), |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(Event::Notification(x)) => return Some((x, receiver)),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+    .take_until(future::select(
+        Box::pin(shutdown::triggered()),
+        session_ended(ctx, access_token),
+    ))
+}
+
+/// Resolves once the user of `access_token` can no longer sign in with it,
+/// they have been disabled or deleted or their sessions have been revoked, or
+/// once it expires.
+fn session_ended(
+    ctx: &Context,
+    access_token: &AccessTokenPayload,
+) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+    let event_bus: &dyn EventBusInterface = ctx.module.resolve_ref();
+    let user_service: Box<dyn UserService> = ctx.module.provide().unwrap();
+    let mut receiver = event_bus.subscribe();
+    let user_id = access_token.sub.to_owned();
+    let issued_at = access_token.iat;
+
+    let signed_out = async move {
+        loop {
+            let user = match receiver.recv().await {
+                Ok(Event::User(e)) if e.user.id == user_id => e.user,
+                Ok(_) => continue,
+                // The event of the user may have been missed.
+                Err(RecvError::Lagged(_)) => {
+                    match user_service
+                        .select_account(UserIdentifier::Id(user_id.to_owned()))
+                        .await
+                    {
+                        Ok(user) => user,
+                        Err(_) => return,
+                    }
+                }
+                Err(RecvError::Closed) => return,
+            };
+            if user.can_sign_in(Some(issued_at)).is_err() {
+                return;
+            }
+        }
+    };
+
+    let now_sec = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .expect("Invalid system time")
+        .as_secs();
+    let expired = tokio::time::delay_for(Duration::from_secs(
+        access_token.exp.saturating_sub(now_sec),
+    ));
+
+    Box::pin(future::select(Box::pin(signed_out), Box::pin(expired)).map(|_| ()))
+}
@@ -0,0 +1,199 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+use actix_web::http::HeaderMap;
+use mongodb::event::command::{
+    CommandEventHandler, CommandFailedEvent, CommandStartedEvent, CommandSucceededEvent,
+};
+use opentelemetry::{
+    global,
+    propagation::Extractor,
+    sdk::{
+        propagation::TraceContextPropagator,
+        trace::{self, Sampler},
+        Resource,
+    },
+    KeyValue,
+};
+use tracing::{Instrument, Span};
+use tracing_subscriber::layer::SubscriberExt;
+
+use crate::{
+    config::{ConfigTelemetry, TelemetryExporter},
+    Result,
+};
+
+/// Keeps the exporter running, spans are flushed when it is dropped.
+pub struct Telemetry {
+    /// The OTLP exporter runs on tokio 1, the http server on tokio 0.2.
+    _runtime: Option<tokio_1::runtime::Runtime>,
+}
+
+impl Drop for Telemetry {
+    fn drop(&mut self) {
+        global::shutdown_tracer_provider();
+    }
+}
+
+impl Telemetry {
+    /// Install the exporter and the W3C trace context propagator, spans are
+    /// not recorded when telemetry is disabled.
+    pub fn init(
+        config: &ConfigTelemetry,
+    ) -> std::result::Result<Option<Self>, Box<dyn std::error::Error>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+
+        global::set_text_map_propagator(TraceContextPropagator::new());
+
+        let trace_config = trace::config()
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                config.sample_ratio,
+            ))))
+            .with_resource(Resource::new(vec![KeyValue::new(
+                "service.name",
+                config.service_name.to_owned(),
+            )]));
+
+        let (tracer, runtime) = match config.exporter {
+            TelemetryExporter::Otlp => {
+                let runtime = tokio_1::runtime::Builder::new_multi_thread()
+                    .worker_threads(1)
+                    .thread_name("otlp-exporter")
+                    .enable_all()
+                    .build()?;
+                let _guard = runtime.enter();
+
+                let tracer = opentelemetry_otlp::new_pipeline()
+                    .with_endpoint(config.endpoint.to_owned())
+                    .with_trace_config(trace_config)
+                    .with_tonic()
+                    .install_batch(opentelemetry::runtime::Tokio)?;
+
+                (tracer, Some(runtime))
+            }
+            TelemetryExporter::Stdout => (
+                opentelemetry::sdk::export::trace::stdout::new_pipeline()
+                    .with_trace_config(trace_config)
+                    .install_simple(),
+                None,
+            ),
+        };
+
+        let subscriber =
+            tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+        tracing::subscriber::set_global_default(subscriber)?;
+
+        Ok(Some(Self { _runtime: runtime }))
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl<'a> Extractor for HeaderExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|x| x.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|x| x.as_str()).collect()
+    }
+}
+
+/// The trace context of the `traceparent` and `tracestate` headers.
+pub fn extract_context(headers: &HeaderMap) -> opentelemetry::Context {
+    global::get_text_map_propagator(|x| x.extract(&HeaderExtractor(headers)))
+}
+
+/// Resolve a field in a span of its own, `name` is `Type.field`. Fields read
+/// from their parent value are not worth a span and resolve without one.
+pub async fn resolver<T>(
+    name: &'static str,
+    resolve: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let span = tracing::info_span!(
+        "resolver",
+        otel.name = name,
+        otel.status_code = tracing::field::Empty,
+        graphql.field = name,
+    );
+
+    let result = resolve.instrument(span.clone()).await;
+    if let Err(e) = &result {
+        span.record("otel.status_code", &"ERROR");
+        span.in_scope(|| tracing::debug!(error = %e, "Resolver failed"));
+    }
+    result
+}
+
+/// A span per MongoDB command, it is a child of the span the command has been
+/// started in.
+#[derive(Default)]
+pub struct MongoCommandSpans {
+    spans: Mutex<HashMap<i32, Span>>,
+}
+
+impl CommandEventHandler for MongoCommandSpans {
+    fn handle_command_started_event(&self, event: CommandStartedEvent) {
+        let span = tracing::info_span!(
+            "mongodb",
+            otel.name = format!("mongodb.{}", event.command_name).as_str(),
+            otel.kind = "client",
+            otel.status_code = tracing::field::Empty,
+            db.system = "mongodb",
+            db.name = %event.db,
+            db.operation = %event.command_name,
+        );
+
+        self.spans
+            .lock()
+            .expect("Span lock poisoned")
+            .insert(event.request_id, span);
+    }
+
+    fn handle_command_succeeded_event(&self, event: CommandSucceededEvent) {
+        self.spans
+            .lock()
+            .expect("Span lock poisoned")
+            .remove(&event.request_id);
+    }
+
+    fn handle_command_failed_event(&self, event: CommandFailedEvent) {
+        if let Some(span) = self
+            .spans
+            .lock()
+            .expect("Span lock poisoned")
+            .remove(&event.request_id)
+        {
+            span.record("otel.status_code", &"ERROR");
+            span.in_scope(|| tracing::error!(error = %event.failure, "MongoDB command failed"));
+        }
+    }
+}
+
+/// Forwards the command events of a MongoDB client to every handler.
+pub struct CommandEventHandlers(pub Vec<Arc<dyn CommandEventHandler>>);
+
+impl CommandEventHandler for CommandEventHandlers {
+    fn handle_command_started_event(&self, event: CommandStartedEvent) {
+        for handler in self.0.iter() {
+            handler.handle_command_started_event(event.clone());
+        }
+    }
+
+    fn handle_command_succeeded_event(&self, event: CommandSucceededEvent) {
+        for handler in self.0.iter() {
+            handler.handle_command_succeeded_event(event.clone());
+        }
+    }
+
+    fn handle_command_failed_event(&self, event: CommandFailedEvent) {
+        for handler in self.0.iter() {
+            handler.handle_command_failed_event(event.clone());
+        }
+    }
+}
@@ -0,0 +1,362 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    sync::{Arc, Mutex, RwLock},
+    time::{Duration, SystemTime},
+};
+
+use rustls::{
+    internal::pemfile,
+    sign::{self, CertifiedKey},
+    AllowAnyAnonymousOrAuthenticatedClient, AllowAnyAuthenticatedClient, ClientHello, NoClientAuth,
+    ResolvesServerCert, RootCertStore, ServerConfig,
+};
+
+use crate::config::ConfigTls;
+
+/// Serves the certificate and key of the config, loaded again when their
+/// files change so that renewed certificates are picked up without a restart.
+pub struct CertificateResolver {
+    cert: String,
+
+    key: String,
+
+    modified: Mutex<Option<SystemTime>>,
+
+    certified_key: RwLock<CertifiedKey>,
+}
+
+impl ResolvesServerCert for CertificateResolver {
+    fn resolve(&self, _: ClientHello) -> Option<CertifiedKey> {
+        Some(
+            self.certified_key
+                .read()
+                .expect("Certificate lock poisoned")
+                .clone(),
+        )
+    }
+}
+
+impl CertificateResolver {
+    pub fn new(config: &ConfigTls) -> std::result::Result<Self, String> {
+        Ok(Self {
+            cert: config.cert.to_owned(),
+            key: config.key.to_owned(),
+            modified: Mutex::new(modified(&config.cert, &config.key)),
+            certified_key: RwLock::new(load_certified_key(&config.cert, &config.key)?),
+        })
+    }
+
+    /// Load the certificate again if its files have been modified, returns
+    /// whether it has been replaced. An invalid certificate is an error and
+    /// the current one is kept.
+    pub fn reload_if_changed(&self) -> std::result::Result<bool, String> {
+        let modified = modified(&self.cert, &self.key);
+
+        let mut current = self.modified.lock().expect("Certificate lock poisoned");
+        if modified.is_none() || modified == *current {
+            return Ok(false);
+        }
+        *current = modified;
+
+        let certified_key = load_certified_key(&self.cert, &self.key)?;
+        *self
+            .certified_key
+            .write()
+            .expect("Certificate lock poisoned") = certified_key;
+        Ok(true)
+    }
+
+    /// Check the files every `interval` for as long as the server runs.
+    pub async fn watch(self: Arc<Self>, interval: Duration) {
+        let mut interval = tokio::time::interval(interval);
+        loop {
+            interval.tick().await;
+            match self.reload_if_changed() {
+                Ok(true) => log::info!("Reloaded TLS certificate {}", self.cert),
+                Ok(false) => {}
+                Err(e) => log::error!("Failed to reload TLS certificate: {}", e),
+            }
+        }
+    }
+}
+
+/// The rustls config of the server, clients must present a certificate issued
+/// by `client_ca` when it is set and `client_auth_required`. HTTP/2 is
+/// preferred when the client offers it.
+pub fn server_config(
+    config: &ConfigTls,
+    resolver: Arc<CertificateResolver>,
+) -> std::result::Result<ServerConfig, String> {
+    let verifier = match &config.client_ca {
+        Some(client_ca) => {
+            let mut roots = RootCertStore::empty();
+            let (valid, _) = roots
+                .add_pem_file(&mut BufReader::new(open(client_ca)?))
+                .map_err(|_| format!("Failed to parse {}", client_ca))?;
+            if valid == 0 {
+                return Err(format!("No CA certificate in {}", client_ca));
+            }
+
+            match config.client_auth_required {
+                true => AllowAnyAuthenticatedClient::new(roots),
+                false => AllowAnyAnonymousOrAuthenticatedClient::new(roots),
+            }
+        }
+        None => NoClientAuth::new(),
+    };
+
+    let mut server_config = ServerConfig::new(verifier);
+    server_config.cert_resolver = resolver;
+    server_config.set_protocols(&[b"h2".to_vec(), b"http/1.1".to_vec()]);
+    Ok(server_config)
+}
+
+fn load_certified_key(cert: &str, key: &str) -> std::result::Result<CertifiedKey, String> {
+    let certs = pemfile::certs(&mut BufReader::new(open(cert)?))
+        .map_err(|_| format!("Failed to parse {}", cert))?;
+    if certs.is_empty() {
+        return Err(format!("No certificate in {}", cert));
+    }
+
+    // PKCS#8 keys first, then PKCS#1 RSA keys.
+    let private_key = pemfile::pkcs8_private_keys(&mut BufReader::new(open(key)?))
+        .ok()
+        .and_then(|x| x.into_iter().next())
+        .or_else(|| {
+            open(key).ok().and_then(|x| {
+                pemfile::rsa_private_keys(&mut BufReader::new(x))
+                    .ok()
+                    .and_then(|x| x.into_iter().next())
+            })
+        })
+        .ok_or_else(|| format!("No private key in {}", key))?;
+    let signing_key = sign::any_supported_type(&private_key)
+        .map_err(|_| format!("Unsupported private key in {}", key))?;
+
+    Ok(CertifiedKey::new(certs, Arc::new(signing_key)))
+}
+
+fn open(path: &str) -> std::result::Result<File, String> {
+    File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))
+}
+
+/// The latest modification time of the files.
+fn modified(cert: &str, key: &str) -> Option<SystemTime> {
+    let cert = std::fs::metadata(cert).and_then(|x| x.modified()).ok()?;
+    let key = std::fs::metadata(key).and_then(|x| x.modified()).ok()?;
+    Some(std::cmp::max(cert, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rcgen::{BasicConstraints, Certificate, CertificateParams, DnType, IsCa};
+    use rustls::{ClientConfig, ClientSession, ServerSession, Session, TLSError};
+
+    use super::*;
+
+    struct Pki {
+        ca: Certificate,
+
+        dir: PathBuf,
+    }
+
+    impl Pki {
+        fn new(name: &str) -> Self {
+            let dir =
+                std::env::temp_dir().join(format!("paper-tls-{}-{}", name, std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+
+            let mut params = CertificateParams::new(vec![]);
+            params
+                .distinguished_name
+                .push(DnType::CommonName, "Paper test CA");
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            let ca = Certificate::from_params(params).unwrap();
+            std::fs::write(dir.join("ca.pem"), ca.serialize_pem().unwrap()).unwrap();
+
+            Self { ca, dir }
+        }
+
+        fn path(&self, name: &str) -> String {
+            self.dir.join(name).to_string_lossy().into_owned()
+        }
+
+        /// Issue a certificate for localhost, written to `<name>.pem` and
+        /// `<name>.key`, returns its DER.
+        fn issue(&self, name: &str) -> Vec<u8> {
+            let mut params = CertificateParams::new(vec!["localhost".into()]);
+            params.distinguished_name.push(DnType::CommonName, name);
+            let cert = Certificate::from_params(params).unwrap();
+
+            let pem = self.path(&format!("{}.pem", name));
+            std::fs::write(&pem, cert.serialize_pem_with_signer(&self.ca).unwrap()).unwrap();
+            std::fs::write(
+                self.path(&format!("{}.key", name)),
+                cert.serialize_private_key_pem(),
+            )
+            .unwrap();
+
+            // Signatures are randomized, the DER is read back from the file.
+            pemfile::certs(&mut BufReader::new(open(&pem).unwrap())).unwrap()[0]
+                .0
+                .to_owned()
+        }
+
+        fn config(&self, name: &str) -> ConfigTls {
+            ConfigTls {
+                enabled: true,
+                cert: self.path(&format!("{}.pem", name)),
+                key: self.path(&format!("{}.key", name)),
+                ..Default::default()
+            }
+        }
+
+        fn client_config(&self) -> ClientConfig {
+            let mut client_config = ClientConfig::new();
+            client_config
+                .root_store
+                .add(&rustls::Certificate(self.ca.serialize_der().unwrap()))
+                .unwrap();
+            client_config.set_protocols(&[b"h2".to_vec(), b"http/1.1".to_vec()]);
+            client_config
+        }
+    }
+
+    impl Drop for Pki {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    fn served_cert(resolver: &CertificateResolver) -> Vec<u8> {
+        resolver.certified_key.read().unwrap().cert[0].0.to_owned()
+    }
+
+    fn transfer(from: &mut dyn Session, to: &mut dyn Session) -> Result<(), TLSError> {
+        let mut buf = Vec::new();
+        while from.wants_write() {
+            from.write_tls(&mut buf).unwrap();
+        }
+
+        let mut rd = &buf[..];
+        while !rd.is_empty() {
+            to.read_tls(&mut rd).unwrap();
+            to.process_new_packets()?;
+        }
+        Ok(())
+    }
+
+    /// Run the handshake of `client_config` with the server of `config`.
+    fn handshake(
+        config: &ConfigTls,
+        client_config: ClientConfig,
+    ) -> Result<(ClientSession, ServerSession), TLSError> {
+        let resolver = Arc::new(CertificateResolver::new(config).unwrap());
+        let server_config = Arc::new(server_config(config, resolver).unwrap());
+        let dns_name = webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap();
+
+        let mut client = ClientSession::new(&Arc::new(client_config), dns_name);
+        let mut server = ServerSession::new(&server_config);
+        for _ in 0..10 {
+            if !client.is_handshaking() && !server.is_handshaking() {
+                break;
+            }
+            transfer(&mut client, &mut server)?;
+            transfer(&mut server, &mut client)?;
+        }
+        assert!(!client.is_handshaking() && !server.is_handshaking());
+
+        Ok((client, server))
+    }
+
+    #[test]
+    fn load_certificate() {
+        let pki = Pki::new("load");
+        let cert = pki.issue("server");
+
+        let resolver = CertificateResolver::new(&pki.config("server")).unwrap();
+        assert_eq!(served_cert(&resolver), cert);
+
+        let mut config = pki.config("server");
+        config.key = pki.path("server.pem");
+        assert!(CertificateResolver::new(&config).is_err());
+
+        config.cert = pki.path("missing.pem");
+        assert!(CertificateResolver::new(&config).is_err());
+    }
+
+    #[test]
+    fn reload_certificate() {
+        let pki = Pki::new("reload");
+        let cert = pki.issue("server");
+
+        let resolver = CertificateResolver::new(&pki.config("server")).unwrap();
+        assert_eq!(resolver.reload_if_changed(), Ok(false));
+
+        // The files are rewritten within the resolution of their modification
+        // time, the recorded one is moved back instead of waiting.
+        let renewed = pki.issue("server");
+        *resolver.modified.lock().unwrap() = Some(SystemTime::UNIX_EPOCH);
+        assert_eq!(resolver.reload_if_changed(), Ok(true));
+        assert_ne!(renewed, cert);
+        assert_eq!(served_cert(&resolver), renewed);
+
+        // An invalid certificate keeps the current one.
+        std::fs::write(pki.path("server.pem"), "").unwrap();
+        *resolver.modified.lock().unwrap() = Some(SystemTime::UNIX_EPOCH);
+        assert!(resolver.reload_if_changed().is_err());
+        assert_eq!(served_cert(&resolver), renewed);
+    }
+
+    #[test]
+    fn negotiate_http2() {
+        let pki = Pki::new("alpn");
+        pki.issue("server");
+
+        let (client, server) = handshake(&pki.config("server"), pki.client_config()).unwrap();
+        assert_eq!(client.get_alpn_protocol(), Some(&b"h2"[..]));
+        assert_eq!(server.get_alpn_protocol(), Some(&b"h2"[..]));
+
+        let mut client_config = pki.client_config();
+        client_config.set_protocols(&[b"http/1.1".to_vec()]);
+        let (client, _) = handshake(&pki.config("server"), client_config).unwrap();
+        assert_eq!(client.get_alpn_protocol(), Some(&b"http/1.1"[..]));
+    }
+
+    #[test]
+    fn verify_client_certificate() {
+        let pki = Pki::new("mtls");
+        pki.issue("server");
+        let client_cert = pki.issue("client");
+
+        let mut config = pki.config("server");
+        config.client_ca = Some(pki.path("ca.pem"));
+        config.client_auth_required = true;
+
+        // Without a certificate.
+        assert!(handshake(&config, pki.client_config()).is_err());
+
+        // With a certificate issued by the CA.
+        let mut client_config = pki.client_config();
+        client_config
+            .set_single_client_cert(
+                vec![rustls::Certificate(client_cert.to_owned())],
+                pemfile::pkcs8_private_keys(&mut BufReader::new(
+                    open(&pki.path("client.key")).unwrap(),
+                ))
+                .unwrap()
+                .remove(0),
+            )
+            .unwrap();
+        let (_, server) = handshake(&config, client_config).unwrap();
+        assert_eq!(server.get_peer_certificates().unwrap()[0].0, client_cert);
+
+        // Optional when not required.
+        config.client_auth_required = false;
+        let (_, server) = handshake(&config, pki.client_config()).unwrap();
+        assert!(server.get_peer_certificates().is_none());
+    }
+}
@@ -0,0 +1,852 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bson::doc;
+use futures::StreamExt;
+use mongodb::options::{FindOneAndUpdateOptions, FindOptions};
+use paper::{
+    admin::*, audit::*, auth::*, export::DataExport, paper::*, user::*, Error, OrderBy,
+    OrderDirection, Pagination, PaginationList, Result,
+};
+use shaku::{Component, Provider};
+use tracing::instrument;
+
+use crate::{
+    attachment::{purge_attachments, AttachmentCollection},
+    audit::{audit_event_filter_doc, AuditCollection, AUDIT_EVENT_ORDER},
+    auth::{AccessTokenConfigInterface, RefreshTokenConfigInterface},
+    blob::{purge_with_blobs, BlobStorageInterface},
+    comment::CommentCollection,
+    event::{Event, EventPublisher},
+    export::ExportCollection,
+    notification::NotificationCollection,
+    paper::{paper_filter_doc, paper_order_to_str, PaperCollection, PAPER_PROJECTION},
+    quota::{add_to_counter, QuotaCollection, PAPERS},
+    user::{UserCollection, USER_PROJECTION},
+    utils::*,
+    webhook::{WebhookCollection, WebhookDeliveryCollection},
+};
+
+#[derive(Provider)]
+#[shaku(interface = AdminService)]
+pub struct AdminServiceImpl {
+    #[shaku(provide)]
+    pub user_collection: Box<dyn UserCollection>,
+
+    #[shaku(provide)]
+    pub paper_collection: Box<dyn PaperCollection>,
+
+    #[shaku(provide)]
+    pub export_collection: Box<dyn ExportCollection>,
+
+    #[shaku(provide)]
+    pub audit_collection: Box<dyn AuditCollection>,
+
+    #[shaku(provide)]
+    pub comment_collection: Box<dyn CommentCollection>,
+
+    #[shaku(provide)]
+    pub webhook_collection: Box<dyn WebhookCollection>,
+
+    #[shaku(provide)]
+    pub webhook_delivery_collection: Box<dyn WebhookDeliveryCollection>,
+
+    #[shaku(provide)]
+    pub notification_collection: Box<dyn NotificationCollection>,
+
+    #[shaku(provide)]
+    pub attachment_collection: Box<dyn AttachmentCollection>,
+
+    #[shaku(provide)]
+    pub quota_collection: Box<dyn QuotaCollection>,
+
+    #[shaku(inject)]
+    pub storage_database: Arc<dyn StorageDatabaseInterface>,
+
+    #[shaku(inject)]
+    pub blob_storage: Arc<dyn BlobStorageInterface>,
+
+    #[shaku(inject)]
+    pub access_token_config: Arc<dyn AccessTokenConfigInterface>,
+
+    #[shaku(inject)]
+    pub refresh_token_config: Arc<dyn RefreshTokenConfigInterface>,
+
+    #[shaku(provide)]
+    pub event_publisher: Box<dyn EventPublisher>,
+
+    #[shaku(provide)]
+    pub audit_service: Box<dyn AuditService>,
+
+    #[shaku(inject)]
+    pub admin_config: Arc<dyn AdminConfigInterface>,
+}
+
+#[derive(Debug, Clone, Component)]
+#[shaku(interface = AdminConfigInterface)]
+pub struct AdminConfig {
+    /// Users made admins, as `github:<github user id>`,
+    /// `google:<google user id>` or a user id.
+    pub bootstrap_admins: Vec<String>,
+
+    pub impersonation_expires_in_sec: u64,
+}
+
+crate::shaku_deref_self_interface!(AdminConfigInterface, AdminConfig);
+
+impl AdminConfig {
+    pub fn is_bootstrap_admin(&self, user: &User) -> bool {
+        self.bootstrap_admins.iter().any(|x| {
+            let github_user_id = user
+                .github_user
+                .as_ref()
+                .and_then(|x| x.get("id"))
+                .and_then(|x| x.as_u64());
+            let google_user_id = user
+                .google_user
+                .as_ref()
+                .and_then(|x| x.get("id"))
+                .and_then(|x| x.as_str());
+
+            match x.split_once(':') {
+                Some(("github", id)) => {
+                    github_user_id.map(|x| x.to_string()).as_deref() == Some(id)
+                }
+                Some(("google", id)) => google_user_id == Some(id),
+                _ => x == user.id.as_ref(),
+            }
+        })
+    }
+
+    /// The query of the users of `bootstrap_admins`, `None` when it is empty.
+    fn bootstrap_admins_filter(&self) -> Option<bson::Document> {
+        let identities: Vec<bson::Document> = self
+            .bootstrap_admins
+            .iter()
+            .map(|x| match x.split_once(':') {
+                Some(("github", id)) => match id.parse::<i64>() {
+                    Ok(id) => doc! { "github_user.id": id },
+                    Err(_) => doc! { "github_user.id": id },
+                },
+                Some(("google", id)) => doc! { "google_user.id": id },
+                _ => doc! { "_id": x },
+            })
+            .collect();
+
+        match identities.is_empty() {
+            true => None,
+            false => Some(doc! { "$or": identities }),
+        }
+    }
+}
+
+/// The database of the collections, for the commands which are not bound to a
+/// collection.
+#[derive(Component)]
+#[shaku(interface = StorageDatabaseInterface)]
+pub struct StorageDatabase {
+    pub database: mongodb::Database,
+}
+
+crate::shaku_deref_self_interface!(StorageDatabaseInterface, StorageDatabase);
+
+#[async_trait]
+impl AdminService for AdminServiceImpl {
+    #[instrument(name = "AdminService.select_user_page", skip_all)]
+    async fn select_user_page(
+        &self,
+        pagination: Pagination<UserCursor>,
+        filter: UserFilter,
+    ) -> Result<PaginationList<User>> {
+        let mut query = doc! {};
+        if let Some(search) = filter.search.filter(|x| !x.is_empty()) {
+            query.insert(
+                "$or",
+                vec![
+                    doc! { "_id": search.to_owned() },
+                    doc! { "name": { "$regex": regex_escape(&search), "$options": "i" } },
+                ],
+            );
+        }
+        match filter.disabled {
+            Some(true) => query.insert("disabled", true),
+            Some(false) => query.insert("disabled", doc! { "$ne": true }),
+            None => None,
+        };
+
+        mongodb_select_pagination(
+            &self.user_collection,
+            pagination,
+            query,
+            OrderBy {
+                field: "_id",
+                direction: OrderDirection::Asc,
+            },
+            FindOptions::builder()
+                .projection(USER_PROJECTION.to_owned())
+                .build(),
+        )
+        .await
+    }
+
+    #[instrument(name = "AdminService.rename_user", skip_all, fields(user_id = %user_id))]
+    async fn rename_user(&self, user_id: UserId, name: String) -> Result<User> {
+        if name.trim().is_empty() {
+            return Err(Error::invalid_input("Name is empty".to_owned()));
+        }
+
+        self.update_user(user_id, doc! { "name": name }).await
+    }
+
+    #[instrument(name = "AdminService.set_user_disabled", skip_all, fields(user_id = %user_id))]
+    async fn set_user_disabled(&self, user_id: UserId, disabled: bool) -> Result<User> {
+        self.update_user(user_id, doc! { "disabled": disabled })
+            .await
+    }
+
+    #[instrument(name = "AdminService.set_user_role", skip_all, fields(user_id = %user_id))]
+    async fn set_user_role(&self, user_id: UserId, role: Role) -> Result<User> {
+        let role = bson::to_bson(&role).map_err(|e| Error::unknown(e.to_string()))?;
+
+        self.update_user(user_id, doc! { "role": role }).await
+    }
+
+    #[instrument(name = "AdminService.bootstrap_admins", skip_all)]
+    async fn bootstrap_admins(&self) -> Result<u64> {
+        let mut filter = match self.admin_config.bootstrap_admins_filter() {
+            Some(filter) => filter,
+            None => return Ok(0),
+        };
+        filter.insert("role", doc! { "$ne": "admin" });
+
+        let result = self
+            .user_collection
+            .update_many(filter, doc! { "$set": { "role": "admin" } }, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        Ok(result.modified_count as u64)
+    }
+
+    #[instrument(name = "AdminService.select_statistics", skip_all)]
+    async fn select_statistics(&self) -> Result<Statistics> {
+        let count = |collection: &mongodb::Collection, filter: bson::Document| {
+            let collection = collection.clone();
+            async move {
+                collection
+                    .count_documents(filter, None)
+                    .await
+                    .map(|x| x as u64)
+                    .map_err(|e| Error::unknown(e.to_string()))
+            }
+        };
+
+        let (users, disabled_users, admins, papers, deleted_papers) = futures::try_join!(
+            count(&self.user_collection, doc! {}),
+            count(&self.user_collection, doc! { "disabled": true }),
+            count(&self.user_collection, doc! { "role": "admin" }),
+            count(&self.paper_collection, doc! { "deleted_at": null }),
+            count(
+                &self.paper_collection,
+                doc! { "deleted_at": { "$ne": null } }
+            ),
+        )?;
+
+        Ok(Statistics {
+            users,
+            disabled_users,
+            admins,
+            papers,
+            deleted_papers,
+        })
+    }
+
+    #[instrument(name = "AdminService.select_paper_page", skip_all)]
+    async fn select_paper_page(
+        &self,
+        pagination: Pagination<PaperCursor>,
+        order_by: OrderBy<PaperOrderField>,
+        filter: AdminPaperFilter,
+    ) -> Result<PaginationList<Paper>> {
+        let mut query = doc! {};
+        if let Some(user_id) = filter.user_id {
+            query.insert("user_id", user_id.to_string());
+        }
+        query.extend(paper_filter_doc(&filter.paper));
+
+        mongodb_select_pagination(
+            &self.paper_collection,
+            pagination,
+            query,
+            paper_order_to_str(order_by),
+            FindOptions::builder()
+                .projection(PAPER_PROJECTION.to_owned())
+                .build(),
+        )
+        .await
+    }
+
+    #[instrument(name = "AdminService.restore_paper", skip_all, fields(paper_id = %paper_id))]
+    async fn restore_paper(&self, paper_id: PaperId) -> Result<Paper> {
+        let paper = self
+            .paper_collection
+            .find_one_and_update(
+                doc! { "_id": paper_id.to_string(), "deleted_at": { "$ne": null } },
+                doc! { "$set": { "deleted_at": null, "updated_at": now_msec() } },
+                FindOneAndUpdateOptions::builder()
+                    .return_document(mongodb::options::ReturnDocument::After)
+                    .projection(PAPER_PROJECTION.to_owned())
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|doc| from_doc::<Paper>(doc))
+            .transpose()?
+            .ok_or_else(|| Error::not_found("Deleted paper not found".to_owned()))?;
+
+        // A restore by an admin is not held to the limit of the plan, and
+        // the paper is restored whether or not it is counted.
+        if let Err(e) = add_to_counter(&self.quota_collection, &paper.user_id, PAPERS, 1).await {
+            log::error!("Failed to count restored paper {}: {}", paper.id, e);
+        }
+
+        self.event_publisher
+            .publish(Event::Paper(PaperEvent {
+                kind: PaperEventKind::Updated,
+                paper: paper.to_owned(),
+            }))
+            .await;
+
+        Ok(paper)
+    }
+
+    #[instrument(name = "AdminService.purge_paper", skip_all, fields(paper_id = %paper_id))]
+    async fn purge_paper(&self, paper_id: PaperId) -> Result<()> {
+        let result = self
+            .paper_collection
+            .delete_one(
+                doc! { "_id": paper_id.to_string(), "deleted_at": { "$ne": null } },
+                None,
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        if result.deleted_count == 0 {
+            return Err(Error::not_found("Deleted paper not found".to_owned()));
+        }
+
+        for collection in vec![&**self.comment_collection, &**self.notification_collection] {
+            collection
+                .delete_many(doc! { "paper_id": paper_id.to_string() }, None)
+                .await
+                .map_err(|e| Error::unknown(e.to_string()))?;
+        }
+
+        purge_attachments(
+            &self.attachment_collection,
+            &self.quota_collection,
+            &*self.blob_storage.store,
+            doc! { "paper_id": paper_id.to_string() },
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(name = "AdminService.select_audit_event_page", skip_all)]
+    async fn select_audit_event_page(
+        &self,
+        pagination: Pagination<AuditEventCursor>,
+        filter: AuditEventFilter,
+    ) -> Result<PaginationList<AuditEvent>> {
+        mongodb_select_pagination(
+            &self.audit_collection,
+            pagination,
+            audit_event_filter_doc(&filter)?,
+            AUDIT_EVENT_ORDER,
+            FindOptions::default(),
+        )
+        .await
+    }
+
+    #[instrument(name = "AdminService.purge_deleted_users", skip_all)]
+    async fn purge_deleted_users(&self, purge_before: u64) -> Result<u64> {
+        let user_ids: Vec<String> = self
+            .user_collection
+            .find(
+                doc! { "purge_at": { "$lt": purge_before as i64 } },
+                FindOptions::builder().projection(doc! { "_id": 1 }).build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .filter_map(|x| async move { x.ok()?.get_str("_id").ok().map(str::to_owned) })
+            .collect()
+            .await;
+
+        if user_ids.is_empty() {
+            return Ok(0);
+        }
+
+        // The comments they wrote on other papers, and the notifications they
+        // caused.
+        self.comment_collection
+            .delete_many(doc! { "author_id": { "$in": user_ids.clone() } }, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+        self.notification_collection
+            .delete_many(doc! { "actor_id": { "$in": user_ids.clone() } }, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        // Attachments, papers, exports, webhooks and notifications first, a
+        // failure leaves the users to the next purge.
+        purge_attachments(
+            &self.attachment_collection,
+            &self.quota_collection,
+            &*self.blob_storage.store,
+            doc! { "user_id": { "$in": user_ids.clone() } },
+        )
+        .await?;
+        purge_with_blobs(
+            &self.export_collection,
+            &*self.blob_storage.store,
+            doc! { "user_id": { "$in": user_ids.clone() } },
+            DataExport::blob_key,
+            |_| {},
+        )
+        .await?;
+
+        for collection in vec![
+            &**self.paper_collection,
+            &**self.comment_collection,
+            &**self.webhook_collection,
+            &**self.webhook_delivery_collection,
+            &**self.notification_collection,
+        ] {
+            collection
+                .delete_many(doc! { "user_id": { "$in": user_ids.clone() } }, None)
+                .await
+                .map_err(|e| Error::unknown(e.to_string()))?;
+        }
+
+        self.quota_collection
+            .delete_many(doc! { "_id": { "$in": user_ids.clone() } }, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        let result = self
+            .user_collection
+            .delete_many(doc! { "_id": { "$in": user_ids } }, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        Ok(result.deleted_count as u64)
+    }
+
+    #[instrument(name = "AdminService.purge_deleted_papers", skip_all)]
+    async fn purge_deleted_papers(&self, deleted_before: u64) -> Result<u64> {
+        let paper_ids: Vec<String> = self
+            .paper_collection
+            .find(
+                doc! { "deleted_at": { "$lt": deleted_before as i64 } },
+                FindOptions::builder().projection(doc! { "_id": 1 }).build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .filter_map(|x| async move { x.ok()?.get_str("_id").ok().map(str::to_owned) })
+            .collect()
+            .await;
+
+        if paper_ids.is_empty() {
+            return Ok(0);
+        }
+
+        // Comments, notifications and attachments first, a failure leaves
+        // the papers to the next purge.
+        for collection in vec![&**self.comment_collection, &**self.notification_collection] {
+            collection
+                .delete_many(doc! { "paper_id": { "$in": paper_ids.clone() } }, None)
+                .await
+                .map_err(|e| Error::unknown(e.to_string()))?;
+        }
+        purge_attachments(
+            &self.attachment_collection,
+            &self.quota_collection,
+            &*self.blob_storage.store,
+            doc! { "paper_id": { "$in": paper_ids.clone() } },
+        )
+        .await?;
+
+        let result = self
+            .paper_collection
+            .delete_many(doc! { "_id": { "$in": paper_ids } }, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        Ok(result.deleted_count as u64)
+    }
+
+    #[instrument(name = "AdminService.mint_access_token", skip_all, fields(user_id = %user_id))]
+    async fn mint_access_token(
+        &self,
+        user_id: UserId,
+        expires_in_sec: Option<u64>,
+    ) -> Result<AccessToken> {
+        let user = self.find_user(user_id).await?;
+        let expires_in_sec = expires_in_sec.unwrap_or(self.access_token_config.expires_in_sec);
+
+        // Minted by an operator, there is no actor.
+        self.audit_service
+            .record(AuditEventInput {
+                user_id: Some(user.id.to_owned()),
+                details: Some(serde_json::json!({
+                    "expires_at": now_msec() + expires_in_sec * 1000,
+                })),
+                ..AuditEventInput::new(AuditEventKind::TokenMint)
+            })
+            .await?;
+
+        Ok(AccessToken::new(
+            user,
+            (expires_in_sec, self.access_token_config.secret.as_ref()),
+            (
+                self.refresh_token_config.expires_in_sec,
+                self.refresh_token_config.secret.as_ref(),
+            ),
+        ))
+    }
+
+    #[instrument(
+        name = "AdminService.impersonate_user",
+        skip_all,
+        fields(actor_id = %actor_id, user_id = %user_id)
+    )]
+    async fn impersonate_user(
+        &self,
+        actor_id: UserId,
+        user_id: UserId,
+        reason: String,
+    ) -> Result<AccessToken> {
+        if reason.trim().is_empty() {
+            return Err(Error::invalid_input("A reason is required".to_owned()));
+        }
+
+        let user = self.find_user(user_id).await?;
+        if user.role >= Role::Admin {
+            return Err(Error::forbidden(
+                "Administrators can not be impersonated".to_owned(),
+            ));
+        }
+
+        // No token is handed out without its record.
+        let expires_in_sec = self.admin_config.impersonation_expires_in_sec;
+        self.audit_service
+            .record(AuditEventInput {
+                actor_id: Some(actor_id.to_owned()),
+                user_id: Some(user.id.to_owned()),
+                details: Some(serde_json::json!({
+                    "reason": reason,
+                    "expires_at": now_msec() + expires_in_sec * 1000,
+                })),
+                ..AuditEventInput::new(AuditEventKind::Impersonation)
+            })
+            .await?;
+
+        Ok(AccessToken::impersonation(
+            user,
+            actor_id,
+            (expires_in_sec, self.access_token_config.secret.as_ref()),
+        ))
+    }
+
+    #[instrument(name = "AdminService.migrate", skip_all)]
+    async fn migrate(&self) -> Result<Vec<String>> {
+        let indexes = vec![
+            (
+                self.user_collection.name().to_owned(),
+                vec![
+                    doc! { "name": "name", "key": { "name": 1 } },
+                    doc! { "name": "github_user_id", "key": { "github_user.id": 1 } },
+                    doc! { "name": "google_user_id", "key": { "google_user.id": 1 } },
+                    doc! { "name": "role", "key": { "role": 1 } },
+                    doc! { "name": "purge_at", "key": { "purge_at": 1 } },
+                ],
+            ),
+            (
+                self.paper_collection.name().to_owned(),
+                vec![
+                    doc! {
+                        "name": "user_id_updated_at",
+                        "key": { "user_id": 1, "updated_at": 1, "_id": 1 },
+                    },
+                    doc! { "name": "deleted_at", "key": { "deleted_at": 1 } },
+                ],
+            ),
+            (
+                self.export_collection.name().to_owned(),
+                vec![
+                    doc! {
+                        "name": "user_id_created_at",
+                        "key": { "user_id": 1, "created_at": -1 },
+                    },
+                    doc! { "name": "expires_at", "key": { "expires_at": 1 } },
+                ],
+            ),
+            (
+                self.audit_collection.name().to_owned(),
+                vec![
+                    doc! { "name": "actor_id", "key": { "actor_id": 1, "_id": -1 } },
+                    doc! { "name": "user_id", "key": { "user_id": 1, "_id": -1 } },
+                    doc! { "name": "paper_id", "key": { "paper_id": 1, "_id": -1 } },
+                    doc! { "name": "kind", "key": { "kind": 1, "_id": -1 } },
+                    doc! { "name": "created_at", "key": { "created_at": 1 } },
+                ],
+            ),
+            (
+                self.comment_collection.name().to_owned(),
+                vec![
+                    doc! {
+                        "name": "paper_id_thread_id",
+                        "key": { "paper_id": 1, "thread_id": 1, "_id": 1 },
+                    },
+                    doc! { "name": "thread_id", "key": { "thread_id": 1, "_id": 1 } },
+                    doc! { "name": "user_id", "key": { "user_id": 1 } },
+                    doc! { "name": "author_id", "key": { "author_id": 1 } },
+                ],
+            ),
+            (
+                self.webhook_collection.name().to_owned(),
+                vec![doc! {
+                    "name": "user_id_events",
+                    "key": { "user_id": 1, "events": 1 },
+                }],
+            ),
+            (
+                self.webhook_delivery_collection.name().to_owned(),
+                vec![
+                    doc! { "name": "webhook_id", "key": { "webhook_id": 1, "_id": -1 } },
+                    doc! {
+                        "name": "status_next_attempt_at",
+                        "key": { "status": 1, "next_attempt_at": 1 },
+                    },
+                    doc! { "name": "user_id", "key": { "user_id": 1 } },
+                    doc! { "name": "created_at", "key": { "created_at": 1 } },
+                ],
+            ),
+            (
+                self.notification_collection.name().to_owned(),
+                vec![
+                    doc! { "name": "user_id", "key": { "user_id": 1, "_id": -1 } },
+                    doc! { "name": "user_id_read_at", "key": { "user_id": 1, "read_at": 1 } },
+                    doc! { "name": "actor_id", "key": { "actor_id": 1 } },
+                    doc! { "name": "paper_id", "key": { "paper_id": 1 } },
+                    doc! { "name": "created_at", "key": { "created_at": 1 } },
+                ],
+            ),
+            (
+                self.attachment_collection.name().to_owned(),
+                vec![
+                    doc! { "name": "paper_id", "key": { "paper_id": 1, "_id": 1 } },
+                    doc! { "name": "user_id", "key": { "user_id": 1 } },
+                    doc! { "name": "status_created_at", "key": { "status": 1, "created_at": 1 } },
+                ],
+            ),
+        ];
+
+        let mut applied = vec![];
+        // Creating an index which already exists is a no-op.
+        for (collection, indexes) in indexes {
+            let names: Vec<String> = indexes
+                .iter()
+                .filter_map(|x| x.get_str("name").ok())
+                .map(|x| format!("index {}.{}", collection, x))
+                .collect();
+
+            self.storage_database
+                .database
+                .run_command(
+                    doc! { "createIndexes": collection, "indexes": indexes },
+                    None,
+                )
+                .await
+                .map_err(|e| Error::unknown(e.to_string()))?;
+
+            applied.extend(names);
+        }
+
+        Ok(applied)
+    }
+}
+
+impl AdminServiceImpl {
+    async fn find_user(&self, user_id: UserId) -> Result<User> {
+        self.user_collection
+            .find_one(
+                doc! { "_id": user_id.to_string() },
+                mongodb::options::FindOneOptions::builder()
+                    .projection(USER_PROJECTION.to_owned())
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|doc| from_doc::<User>(doc))
+            .transpose()?
+            .ok_or_else(|| Error::not_found("User not found".to_owned()))
+    }
+
+    async fn update_user(&self, user_id: UserId, update_set: bson::Document) -> Result<User> {
+        let user = self
+            .user_collection
+            .find_one_and_update(
+                doc! { "_id": user_id.to_string() },
+                doc! { "$set": update_set },
+                FindOneAndUpdateOptions::builder()
+                    .return_document(mongodb::options::ReturnDocument::After)
+                    .projection(USER_PROJECTION.to_owned())
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|doc| from_doc::<User>(doc))
+            .transpose()?
+            .ok_or_else(|| Error::not_found("User not found".to_owned()))?;
+
+        self.event_publisher
+            .publish(Event::User(UserEvent {
+                kind: UserEventKind::Updated,
+                user: user.to_owned(),
+            }))
+            .await;
+
+        Ok(user)
+    }
+}
+
+impl PaginationCursor for UserCursor {
+    fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn value(&self, _field: &str) -> Option<bson::Bson> {
+        None
+    }
+}
+
+/// Match `s` literally in a `$regex`.
+fn regex_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if "\\.+*?()|[]{}^$".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        audit::{AuditConfig, AuditServiceImpl},
+        auth::{AccessTokenConfig, RefreshTokenConfig},
+        blob::{BlobStorage, FileBlobStore},
+        test_utils::*,
+    };
+
+    fn admin_service(db: &mongodb::Database) -> AdminServiceImpl {
+        let collection = |name: &str| Box::new(TestCollection(db.collection(name)));
+
+        AdminServiceImpl {
+            user_collection: collection("users"),
+            paper_collection: collection("papers"),
+            export_collection: collection("exports"),
+            audit_collection: collection("audit_events"),
+            comment_collection: collection("comments"),
+            webhook_collection: collection("webhooks"),
+            webhook_delivery_collection: collection("webhook_deliveries"),
+            notification_collection: collection("notifications"),
+            attachment_collection: collection("attachments"),
+            quota_collection: collection("quotas"),
+            storage_database: Arc::new(StorageDatabase {
+                database: db.clone(),
+            }),
+            blob_storage: Arc::new(BlobStorage {
+                store: Arc::new(FileBlobStore::new(std::env::temp_dir().join(new_id()))),
+            }),
+            access_token_config: Arc::new(AccessTokenConfig {
+                expires_in_sec: 3600,
+                secret: "access".to_owned(),
+            }),
+            refresh_token_config: Arc::new(RefreshTokenConfig {
+                expires_in_sec: 3600,
+                secret: "refresh".to_owned(),
+            }),
+            event_publisher: Box::new(NoEvents),
+            audit_service: Box::new(AuditServiceImpl {
+                audit_collection: collection("audit_events"),
+                user_service: Box::new(user_service(db)),
+                audit_config: Arc::new(AuditConfig { retention_days: 0 }),
+            }),
+            admin_config: Arc::new(AdminConfig {
+                bootstrap_admins: vec![],
+                impersonation_expires_in_sec: 600,
+            }),
+        }
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn audit_issued_tokens() {
+        let db = test_database().await;
+        let admin_service = admin_service(&db);
+        let admin = create_user(&db, "admin", Role::Admin).await;
+        let member = create_user(&db, "member", Role::Member).await;
+
+        let before = now_msec();
+        admin_service
+            .impersonate_user(
+                admin.id.to_owned(),
+                member.id.to_owned(),
+                "Support request".to_owned(),
+            )
+            .await
+            .unwrap();
+        admin_service
+            .mint_access_token(member.id.to_owned(), Some(60))
+            .await
+            .unwrap();
+        let after = now_msec();
+
+        let events = db
+            .collection("audit_events")
+            .find(None, FindOptions::builder().sort(doc! { "_id": 1 }).build())
+            .await
+            .unwrap()
+            .map(|x| from_doc::<AuditEvent>(x.unwrap()).unwrap())
+            .collect::<Vec<_>>()
+            .await;
+        let expires_at = |event: &AuditEvent| {
+            event.details.as_ref().unwrap()["expires_at"]
+                .as_u64()
+                .unwrap()
+        };
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].kind, AuditEventKind::Impersonation);
+        assert_eq!(events[0].actor_id, Some(admin.id));
+        assert_eq!(events[0].user_id, Some(member.id.to_owned()));
+        assert_eq!(
+            events[0].details.as_ref().unwrap()["reason"],
+            "Support request"
+        );
+        assert!((before + 600_000..=after + 600_000).contains(&expires_at(&events[0])));
+
+        assert_eq!(events[1].kind, AuditEventKind::TokenMint);
+        assert_eq!(events[1].actor_id, None);
+        assert_eq!(events[1].user_id, Some(member.id));
+        assert!((before + 60_000..=after + 60_000).contains(&expires_at(&events[1])));
+
+        db.drop(None).await.unwrap();
+    }
+}
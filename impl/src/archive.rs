@@ -0,0 +1,295 @@
+use std::{convert::TryFrom, io::Write};
+
+use chrono::{Datelike, Timelike};
+use flate2::{write::DeflateEncoder, Compression};
+use futures::{channel::mpsc, SinkExt};
+use paper::{Error, Result};
+
+/// The size of the chunks sent to the reader of the archive.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A zip archive written as a stream of chunks, e.g. to `BlobStore::put_stream`
+/// while it is assembled. The sizes of a file follow its content, as the
+/// local header can not be rewritten, and zip64 is not written, an archive is
+/// up to 4GB.
+pub(crate) struct ZipWriter {
+    sender: mpsc::Sender<Result<Vec<u8>>>,
+
+    /// Written but not sent yet.
+    buffer: Vec<u8>,
+
+    /// Bytes written so far, where the next header goes.
+    offset: u64,
+
+    /// The modification time of the files, in MS-DOS format.
+    dos_time: (u16, u16),
+
+    files: Vec<ZipFile>,
+
+    current: Option<(ZipFile, DeflateEncoder<Vec<u8>>, crc32fast::Hasher)>,
+}
+
+struct ZipFile {
+    name: String,
+    offset: u64,
+    crc32: u32,
+    compressed_size: u64,
+    size: u64,
+}
+
+impl ZipWriter {
+    pub fn new(sender: mpsc::Sender<Result<Vec<u8>>>) -> Self {
+        let now = chrono::Utc::now();
+        let time = now.hour() << 11 | now.minute() << 5 | now.second() / 2;
+        let date = ((now.year() - 1980) as u32) << 9 | now.month() << 5 | now.day();
+
+        Self {
+            sender,
+            buffer: Vec::with_capacity(CHUNK_SIZE),
+            offset: 0,
+            dos_time: (time as u16, date as u16),
+            files: vec![],
+            current: None,
+        }
+    }
+
+    /// Start the file `name`, the previous one is finished.
+    pub async fn start_file(&mut self, name: &str) -> Result<()> {
+        self.finish_file().await?;
+
+        if name.len() > u16::MAX as usize {
+            return Err(Error::unknown(format!("File name too long {}", name)));
+        }
+        let file = ZipFile {
+            name: name.to_owned(),
+            offset: self.offset,
+            crc32: 0,
+            compressed_size: 0,
+            size: 0,
+        };
+
+        let mut header = vec![];
+        header.extend_from_slice(&0x04034b50_u32.to_le_bytes());
+        header.extend_from_slice(&self.file_fields(&file));
+        // No extra field.
+        header.extend_from_slice(&0_u16.to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+        self.write(&header).await?;
+
+        self.current = Some((
+            file,
+            DeflateEncoder::new(vec![], Compression::default()),
+            crc32fast::Hasher::new(),
+        ));
+        Ok(())
+    }
+
+    /// Append `content` to the current file.
+    pub async fn write_all(&mut self, content: &[u8]) -> Result<()> {
+        let (file, encoder, hasher) = self
+            .current
+            .as_mut()
+            .ok_or_else(|| Error::unknown("No file started in the archive".to_owned()))?;
+        file.size += content.len() as u64;
+        hasher.update(content);
+        encoder
+            .write_all(content)
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        let compressed = std::mem::take(encoder.get_mut());
+        file.compressed_size += compressed.len() as u64;
+        self.write(&compressed).await
+    }
+
+    /// Write the central directory and send what is left, the archive is then
+    /// complete.
+    pub async fn finish(mut self) -> Result<()> {
+        self.finish_file().await?;
+
+        let directory_offset = self.offset;
+        let mut directory = vec![];
+        for file in &self.files {
+            directory.extend_from_slice(&0x02014b50_u32.to_le_bytes());
+            // Made by version 2.0.
+            directory.extend_from_slice(&20_u16.to_le_bytes());
+            directory.extend_from_slice(&self.file_fields(file));
+            // No extra field nor comment, disk 0, no attributes.
+            directory.extend_from_slice(&[0; 12]);
+            directory.extend_from_slice(&to_u32(file.offset)?.to_le_bytes());
+            directory.extend_from_slice(file.name.as_bytes());
+        }
+        let directory_size = to_u32(directory.len() as u64)?;
+        let count = u16::try_from(self.files.len())
+            .map_err(|_| Error::unknown("Too many files for an archive".to_owned()))?;
+
+        directory.extend_from_slice(&0x06054b50_u32.to_le_bytes());
+        // Disk 0, where the directory starts too.
+        directory.extend_from_slice(&[0; 4]);
+        directory.extend_from_slice(&count.to_le_bytes());
+        directory.extend_from_slice(&count.to_le_bytes());
+        directory.extend_from_slice(&directory_size.to_le_bytes());
+        directory.extend_from_slice(&to_u32(directory_offset)?.to_le_bytes());
+        // No comment.
+        directory.extend_from_slice(&0_u16.to_le_bytes());
+        self.write(&directory).await?;
+
+        self.flush().await
+    }
+
+    /// Send `error` instead of the rest of the archive, for the reader not to
+    /// keep what has been written.
+    pub async fn abort(mut self, error: Error) {
+        // The reader is gone when it can't be sent, and it keeps nothing.
+        let _ = self.sender.send(Err(error)).await;
+    }
+
+    async fn finish_file(&mut self) -> Result<()> {
+        let (mut file, encoder, hasher) = match self.current.take() {
+            Some(current) => current,
+            None => return Ok(()),
+        };
+        let compressed = encoder
+            .finish()
+            .map_err(|e| Error::unknown(e.to_string()))?;
+        file.compressed_size += compressed.len() as u64;
+        file.crc32 = hasher.finalize();
+        self.write(&compressed).await?;
+
+        let mut descriptor = vec![];
+        descriptor.extend_from_slice(&0x08074b50_u32.to_le_bytes());
+        descriptor.extend_from_slice(&file.crc32.to_le_bytes());
+        descriptor.extend_from_slice(&to_u32(file.compressed_size)?.to_le_bytes());
+        descriptor.extend_from_slice(&to_u32(file.size)?.to_le_bytes());
+        self.write(&descriptor).await?;
+
+        self.files.push(file);
+        Ok(())
+    }
+
+    /// The fields from the version needed to the file name length, shared by
+    /// the local header and the central directory. The sizes and the CRC-32
+    /// are zeros until the file is finished.
+    fn file_fields(&self, file: &ZipFile) -> Vec<u8> {
+        let mut fields = vec![];
+        // Version 2.0 for deflate.
+        fields.extend_from_slice(&20_u16.to_le_bytes());
+        // The sizes follow the content, the name is UTF-8.
+        fields.extend_from_slice(&(1_u16 << 3 | 1 << 11).to_le_bytes());
+        // Deflate.
+        fields.extend_from_slice(&8_u16.to_le_bytes());
+        fields.extend_from_slice(&self.dos_time.0.to_le_bytes());
+        fields.extend_from_slice(&self.dos_time.1.to_le_bytes());
+        fields.extend_from_slice(&file.crc32.to_le_bytes());
+        // Checked once the file is finished.
+        fields.extend_from_slice(&(file.compressed_size as u32).to_le_bytes());
+        fields.extend_from_slice(&(file.size as u32).to_le_bytes());
+        fields.extend_from_slice(&(file.name.len() as u16).to_le_bytes());
+        fields
+    }
+
+    async fn write(&mut self, content: &[u8]) -> Result<()> {
+        self.offset += content.len() as u64;
+        self.buffer.extend_from_slice(content);
+        if self.buffer.len() >= CHUNK_SIZE {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let chunk = std::mem::replace(&mut self.buffer, Vec::with_capacity(CHUNK_SIZE));
+        self.sender
+            .send(Ok(chunk))
+            .await
+            .map_err(|_| Error::unknown("The archive is no longer read".to_owned()))
+    }
+}
+
+fn to_u32(value: u64) -> Result<u32> {
+    u32::try_from(value).map_err(|_| Error::unknown("The archive is over 4GB".to_owned()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use futures::StreamExt;
+
+    use super::*;
+
+    async fn archive(files: Vec<(&str, Vec<&[u8]>)>) -> Vec<u8> {
+        let (sender, receiver) = mpsc::channel(4);
+        let mut zip = ZipWriter::new(sender);
+        let written = async {
+            for (name, chunks) in files {
+                zip.start_file(name).await?;
+                for chunk in chunks {
+                    zip.write_all(chunk).await?;
+                }
+            }
+            zip.finish().await
+        };
+        let (written, chunks) = futures::join!(written, receiver.collect::<Vec<_>>());
+        written.unwrap();
+        chunks
+            .into_iter()
+            .collect::<Result<Vec<_>>>()
+            .unwrap()
+            .concat()
+    }
+
+    #[tokio::test]
+    async fn write_readable_archive() {
+        let large = (0..200_000_u32)
+            .flat_map(|x| x.to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+        let archive = archive(vec![
+            ("README.txt", vec![&b"Hello"[..], &b" world"[..]]),
+            ("empty.json", vec![]),
+            (
+                "attachments/1/résumé.pdf",
+                vec![&large[..300_000], &large[300_000..]],
+            ),
+        ])
+        .await;
+
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(archive)).unwrap();
+        let mut read = |name: &str| {
+            let mut content = vec![];
+            zip.by_name(name)
+                .unwrap()
+                .read_to_end(&mut content)
+                .unwrap();
+            content
+        };
+        assert_eq!(read("README.txt"), b"Hello world");
+        assert_eq!(read("empty.json"), b"");
+        assert_eq!(read("attachments/1/résumé.pdf"), large);
+    }
+
+    #[tokio::test]
+    async fn write_empty_archive() {
+        let zip = zip::ZipArchive::new(std::io::Cursor::new(archive(vec![]).await)).unwrap();
+        assert_eq!(zip.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn abort_archive() {
+        let (sender, receiver) = mpsc::channel(4);
+        let mut zip = ZipWriter::new(sender);
+        let written = async {
+            zip.start_file("README.txt").await.unwrap();
+            zip.write_all(b"Hello").await.unwrap();
+            zip.abort(Error::unknown("Failed".to_owned())).await;
+        };
+        let (_, chunks) = futures::join!(written, receiver.collect::<Vec<_>>());
+
+        assert_eq!(
+            chunks.into_iter().collect::<Result<Vec<_>>>().unwrap_err(),
+            Error::unknown("Failed".to_owned())
+        );
+    }
+}
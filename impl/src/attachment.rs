@@ -0,0 +1,446 @@
+use std::{collections::HashMap, ops::Deref, sync::Arc};
+
+use async_trait::async_trait;
+use bson::{doc, Document};
+use futures::StreamExt;
+use mongodb::options::{FindOneAndUpdateOptions, FindOneOptions, FindOptions};
+use paper::{
+    attachment::*,
+    blob::{BlobStore, BlobStream},
+    paper::*,
+    quota::QuotaService,
+    user::*,
+    Error, Result,
+};
+use shaku::{Component, Provider};
+use tracing::instrument;
+
+use crate::{
+    blob::{purge_with_blobs, BlobStorageInterface},
+    quota::{add_to_counter, QuotaCollection, ATTACHMENT_STORAGE},
+    utils::*,
+};
+
+#[derive(Provider)]
+#[shaku(interface = AttachmentService)]
+pub struct AttachmentServiceImpl {
+    #[shaku(provide)]
+    pub attachment_collection: Box<dyn AttachmentCollection>,
+
+    #[shaku(provide)]
+    pub paper_service: Box<dyn PaperService>,
+
+    #[shaku(provide)]
+    pub user_service: Box<dyn UserService>,
+
+    #[shaku(provide)]
+    pub quota_service: Box<dyn QuotaService>,
+
+    #[shaku(provide)]
+    pub quota_collection: Box<dyn QuotaCollection>,
+
+    #[shaku(inject)]
+    pub blob_storage: Arc<dyn BlobStorageInterface>,
+
+    #[shaku(inject)]
+    pub attachment_config: Arc<dyn AttachmentConfigInterface>,
+}
+
+pub trait AttachmentCollection: Deref<Target = mongodb::Collection> + Send + Sync {}
+
+#[derive(Debug, Clone, Component)]
+#[shaku(interface = AttachmentConfigInterface)]
+pub struct AttachmentConfig {
+    /// Bytes of an attachment, 0 is unlimited.
+    pub max_size: u64,
+
+    /// Seconds the content of an attachment is waited for.
+    pub upload_expires_in_sec: u64,
+}
+
+crate::shaku_deref_self_interface!(AttachmentConfigInterface, AttachmentConfig);
+
+/// Characters of a file name or a content type.
+const MAX_NAME_LENGTH: usize = 255;
+
+#[async_trait]
+impl AttachmentService for AttachmentServiceImpl {
+    #[instrument(
+        name = "AttachmentService.create_attachment",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id, paper_id = %paper_id)
+    )]
+    async fn create_attachment(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        paper_id: PaperId,
+        input: CreateAttachmentInput,
+    ) -> Result<Attachment> {
+        let name = validate_name(&input.name)?;
+        let content_type = validate_content_type(&input.content_type)?;
+
+        if input.size == 0 {
+            return Err(Error::invalid_input(
+                "An attachment can not be empty".to_owned(),
+            ));
+        }
+        let max_size = self.attachment_config.max_size;
+        if max_size > 0 && input.size > max_size {
+            return Err(Error::invalid_input(format!(
+                "An attachment is at most {} bytes",
+                max_size
+            )));
+        }
+
+        self.paper_service
+            .can_viewer_write_paper(
+                viewer_id.to_owned(),
+                user_id.to_owned(),
+                paper_id.to_owned(),
+            )
+            .await?;
+
+        let attachment = Attachment {
+            id: new_id().into(),
+            user_id,
+            paper_id,
+            uploader_id: viewer_id,
+            name,
+            content_type,
+            size: input.size,
+            status: AttachmentStatus::Pending,
+            created_at: now_msec(),
+            uploaded_at: None,
+        };
+
+        let attachment_doc = to_doc(&attachment)?;
+
+        self.quota_service
+            .reserve_attachment_storage(&attachment.user_id, attachment.size)
+            .await?;
+
+        if let Err(e) = self
+            .attachment_collection
+            .insert_one(attachment_doc, None)
+            .await
+        {
+            if let Err(release_error) = self
+                .quota_service
+                .release_attachment_storage(&attachment.user_id, attachment.size)
+                .await
+            {
+                log::error!(
+                    "Failed to release attachment {}: {}",
+                    attachment.id,
+                    release_error
+                );
+            }
+            return Err(Error::unknown(e.to_string()));
+        }
+
+        Ok(attachment)
+    }
+
+    #[instrument(
+        name = "AttachmentService.upload_attachment",
+        skip_all,
+        fields(viewer_id = %viewer_id, attachment_id = %attachment_id, size = content.len())
+    )]
+    async fn upload_attachment(
+        &self,
+        viewer_id: UserId,
+        attachment_id: AttachmentId,
+        content: Vec<u8>,
+    ) -> Result<Attachment> {
+        let attachment = self.find_attachment(&attachment_id).await?;
+
+        self.paper_service
+            .can_viewer_write_paper(
+                viewer_id,
+                attachment.user_id.to_owned(),
+                attachment.paper_id.to_owned(),
+            )
+            .await?;
+
+        if attachment.status != AttachmentStatus::Pending {
+            return Err(Error::unknown(
+                "The attachment has already been uploaded".to_owned(),
+            ));
+        }
+        if content.len() as u64 != attachment.size {
+            return Err(Error::invalid_input(format!(
+                "The attachment was created with {} bytes, {} were uploaded",
+                attachment.size,
+                content.len()
+            )));
+        }
+
+        self.blob_storage
+            .store
+            .put(&attachment.blob_key(), content, &attachment.content_type)
+            .await?;
+
+        self.attachment_collection
+            .find_one_and_update(
+                doc! { "_id": attachment_id.to_string(), "status": "pending" },
+                doc! { "$set": { "status": "ready", "uploaded_at": now_msec() as i64 } },
+                FindOneAndUpdateOptions::builder()
+                    .return_document(mongodb::options::ReturnDocument::After)
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| from_doc::<Attachment>(x))
+            .ok_or_else(|| Error::not_found("Attachment not found".to_owned()))?
+    }
+
+    #[instrument(
+        name = "AttachmentService.delete_attachment",
+        skip_all,
+        fields(viewer_id = %viewer_id, attachment_id = %attachment_id)
+    )]
+    async fn delete_attachment(
+        &self,
+        viewer_id: UserId,
+        attachment_id: AttachmentId,
+    ) -> Result<Attachment> {
+        let attachment = self.find_attachment(&attachment_id).await?;
+
+        self.paper_service
+            .can_viewer_write_paper(
+                viewer_id,
+                attachment.user_id.to_owned(),
+                attachment.paper_id.to_owned(),
+            )
+            .await?;
+
+        purge_attachments(
+            &self.attachment_collection,
+            &self.quota_collection,
+            &*self.blob_storage.store,
+            doc! { "_id": attachment_id.to_string() },
+        )
+        .await?;
+
+        Ok(attachment)
+    }
+
+    #[instrument(
+        name = "AttachmentService.select_attachment",
+        skip_all,
+        fields(viewer_id = %viewer_id, attachment_id = %attachment_id)
+    )]
+    async fn select_attachment(
+        &self,
+        viewer_id: UserId,
+        attachment_id: AttachmentId,
+    ) -> Result<Attachment> {
+        let attachment = self.find_attachment(&attachment_id).await?;
+
+        self.paper_service
+            .can_viewer_read_paper(
+                viewer_id,
+                attachment.user_id.to_owned(),
+                attachment.paper_id.to_owned(),
+            )
+            .await?;
+
+        Ok(attachment)
+    }
+
+    #[instrument(
+        name = "AttachmentService.select_attachments",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id, paper_id = %paper_id)
+    )]
+    async fn select_attachments(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        paper_id: PaperId,
+    ) -> Result<Vec<Attachment>> {
+        self.paper_service
+            .can_viewer_read_paper(viewer_id, user_id, paper_id.to_owned())
+            .await?;
+
+        self.attachment_collection
+            .find(
+                doc! { "paper_id": paper_id.to_string() },
+                FindOptions::builder().sort(doc! { "_id": 1 }).build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| {
+                x.map_err(|e| Error::unknown(e.to_string()))
+                    .and_then(|x| from_doc::<Attachment>(x))
+            })
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    #[instrument(
+        name = "AttachmentService.select_attachment_content",
+        skip_all,
+        fields(viewer_id = %viewer_id, attachment_id = %attachment_id)
+    )]
+    async fn select_attachment_content(
+        &self,
+        viewer_id: UserId,
+        attachment_id: AttachmentId,
+    ) -> Result<(Attachment, BlobStream)> {
+        let attachment = self.select_attachment(viewer_id, attachment_id).await?;
+
+        if attachment.status != AttachmentStatus::Ready {
+            return Err(Error::not_found("Attachment not found".to_owned()));
+        }
+
+        let content = self
+            .blob_storage
+            .store
+            .get_stream(&attachment.blob_key())
+            .await?;
+
+        Ok((attachment, content))
+    }
+
+    #[instrument(
+        name = "AttachmentService.select_storage_usage",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
+    async fn select_storage_usage(&self, viewer_id: UserId, user_id: UserId) -> Result<u64> {
+        self.user_service
+            .can_viewer_read_user(viewer_id, user_id.to_owned())
+            .await?;
+
+        attachment_storage(&self.attachment_collection, &user_id).await
+    }
+
+    #[instrument(name = "AttachmentService.purge_abandoned_attachments", skip_all)]
+    async fn purge_abandoned_attachments(&self) -> Result<u64> {
+        let created_before =
+            now_msec().saturating_sub(self.attachment_config.upload_expires_in_sec * 1000);
+
+        purge_attachments(
+            &self.attachment_collection,
+            &self.quota_collection,
+            &*self.blob_storage.store,
+            doc! { "status": "pending", "created_at": { "$lt": created_before as i64 } },
+        )
+        .await
+    }
+}
+
+impl AttachmentServiceImpl {
+    async fn find_attachment(&self, attachment_id: &AttachmentId) -> Result<Attachment> {
+        self.attachment_collection
+            .find_one(
+                doc! { "_id": attachment_id.to_string() },
+                FindOneOptions::default(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| from_doc::<Attachment>(x))
+            .ok_or_else(|| Error::not_found("Attachment not found".to_owned()))?
+    }
+}
+
+/// Bytes of the attachments of the papers of the user, pending ones included.
+pub(crate) async fn attachment_storage(
+    collection: &mongodb::Collection,
+    user_id: &UserId,
+) -> Result<u64> {
+    let result = collection
+        .aggregate(
+            vec![
+                doc! { "$match": { "user_id": user_id.to_string() } },
+                doc! { "$group": { "_id": null, "size": { "$sum": "$size" } } },
+            ],
+            None,
+        )
+        .await
+        .map_err(|e| Error::unknown(e.to_string()))?
+        .next()
+        .await
+        .transpose()
+        .map_err(|e| Error::unknown(e.to_string()))?;
+
+    Ok(match result.as_ref().and_then(|x| x.get("size")) {
+        Some(bson::Bson::Int32(x)) => *x as u64,
+        Some(bson::Bson::Int64(x)) => *x as u64,
+        _ => 0,
+    })
+}
+
+/// Remove the attachments matching `filter` and their content, and release
+/// their size from the storage of their users. Returns how many have been
+/// removed. The attachments whose content could not be removed are kept and
+/// an error is returned.
+pub(crate) async fn purge_attachments(
+    collection: &mongodb::Collection,
+    quota_collection: &mongodb::Collection,
+    blob_store: &dyn BlobStore,
+    filter: Document,
+) -> Result<u64> {
+    let mut sizes: HashMap<UserId, u64> = HashMap::new();
+    let result = purge_with_blobs(
+        collection,
+        blob_store,
+        filter,
+        Attachment::blob_key,
+        |x: Attachment| *sizes.entry(x.user_id).or_default() += x.size,
+    )
+    .await;
+
+    // Those purged are released even when others could not be. The blobs
+    // are gone, a failure is only logged rather than failing the purge.
+    for (user_id, size) in sizes {
+        if let Err(e) = add_to_counter(
+            quota_collection,
+            &user_id,
+            ATTACHMENT_STORAGE,
+            -(size as i64),
+        )
+        .await
+        {
+            log::error!("Failed to release {} bytes of {}: {}", size, user_id, e);
+        }
+    }
+
+    result
+}
+
+/// The file name without its directories.
+fn validate_name(name: &str) -> Result<String> {
+    let name = name
+        .rsplit(|c| c == '/' || c == '\\')
+        .next()
+        .unwrap_or("")
+        .trim();
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(Error::invalid_input("Invalid attachment name".to_owned()));
+    }
+    if name.chars().count() > MAX_NAME_LENGTH || name.chars().any(char::is_control) {
+        return Err(Error::invalid_input("Invalid attachment name".to_owned()));
+    }
+    Ok(name.to_owned())
+}
+
+/// A `type/subtype` media type, parameters included.
+fn validate_content_type(content_type: &str) -> Result<String> {
+    let content_type = content_type.trim().to_ascii_lowercase();
+    let essence = content_type.split(';').next().unwrap_or("").trim();
+    let valid = content_type.len() <= MAX_NAME_LENGTH
+        && essence.split('/').count() == 2
+        && essence.split('/').all(|x| !x.is_empty())
+        && !content_type.chars().any(|c| c.is_control());
+    match valid {
+        true => Ok(content_type),
+        false => Err(Error::invalid_input(
+            "Invalid attachment content type".to_owned(),
+        )),
+    }
+}
@@ -0,0 +1,139 @@
+use std::{ops::Deref, sync::Arc};
+
+use async_trait::async_trait;
+use bson::doc;
+use mongodb::options::FindOptions;
+use paper::{
+    audit::*, user::*, Error, OrderBy, OrderDirection, Pagination, PaginationList, Result,
+};
+use shaku::{Component, Provider};
+use tracing::instrument;
+
+use crate::utils::*;
+
+#[derive(Provider)]
+#[shaku(interface = AuditService)]
+pub struct AuditServiceImpl {
+    #[shaku(provide)]
+    pub audit_collection: Box<dyn AuditCollection>,
+
+    #[shaku(provide)]
+    pub user_service: Box<dyn UserService>,
+
+    #[shaku(inject)]
+    pub audit_config: Arc<dyn AuditConfigInterface>,
+}
+
+pub trait AuditCollection: Deref<Target = mongodb::Collection> + Send + Sync {}
+
+#[derive(Debug, Clone, Component)]
+#[shaku(interface = AuditConfigInterface)]
+pub struct AuditConfig {
+    /// Days the events are kept, 0 keeps them forever.
+    pub retention_days: u64,
+}
+
+crate::shaku_deref_self_interface!(AuditConfigInterface, AuditConfig);
+
+#[async_trait]
+impl AuditService for AuditServiceImpl {
+    #[instrument(name = "AuditService.record", skip_all, fields(kind = ?input.kind))]
+    async fn record(&self, input: AuditEventInput) -> Result<AuditEvent> {
+        let event = AuditEvent {
+            id: new_id().into(),
+            created_at: now_msec(),
+            kind: input.kind,
+            actor_id: input.actor_id,
+            impersonator_id: input.impersonator_id,
+            user_id: input.user_id,
+            paper_id: input.paper_id,
+            ip: input.ip,
+            user_agent: input.user_agent,
+            details: input.details,
+        };
+
+        self.audit_collection
+            .insert_one(to_doc(&event)?, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        Ok(event)
+    }
+
+    #[instrument(
+        name = "AuditService.select_audit_event_page",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
+    async fn select_audit_event_page(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        pagination: Pagination<AuditEventCursor>,
+    ) -> Result<PaginationList<AuditEvent>> {
+        self.user_service
+            .can_viewer_administer_user(viewer_id, user_id.to_owned())
+            .await?;
+
+        mongodb_select_pagination(
+            &self.audit_collection,
+            pagination,
+            doc! {
+                "$or": vec![
+                    doc! { "actor_id": user_id.to_string() },
+                    doc! { "user_id": user_id.to_string() },
+                ],
+            },
+            AUDIT_EVENT_ORDER,
+            FindOptions::default(),
+        )
+        .await
+    }
+
+    #[instrument(name = "AuditService.purge_expired_events", skip_all)]
+    async fn purge_expired_events(&self) -> Result<u64> {
+        purge_older_than(
+            &self.audit_collection,
+            self.audit_config.retention_days,
+            doc! {},
+        )
+        .await
+    }
+}
+
+/// Newest first.
+pub(crate) const AUDIT_EVENT_ORDER: OrderBy<&str> = OrderBy {
+    field: "_id",
+    direction: OrderDirection::Desc,
+};
+
+/// The query of the events matching `filter`.
+pub(crate) fn audit_event_filter_doc(filter: &AuditEventFilter) -> Result<bson::Document> {
+    let mut query = doc! {};
+    if let Some(kind) = filter.kind {
+        query.insert(
+            "kind",
+            bson::to_bson(&kind).map_err(|e| Error::unknown(e.to_string()))?,
+        );
+    }
+    if let Some(actor_id) = &filter.actor_id {
+        query.insert("actor_id", actor_id.to_string());
+    }
+    if let Some(user_id) = &filter.user_id {
+        query.insert("user_id", user_id.to_string());
+    }
+    if let Some(paper_id) = &filter.paper_id {
+        query.insert("paper_id", paper_id.to_string());
+    }
+    Ok(query)
+}
+
+impl PaginationCursor for AuditEventCursor {
+    fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn value(&self, _field: &str) -> Option<bson::Bson> {
+        None
+    }
+}
@@ -4,6 +4,7 @@ use async_trait::async_trait;
 use paper::{auth::*, user::*, Error, ErrorKind, Result};
 use serde::{Deserialize, Serialize};
 use shaku::{Component, Provider};
+use tracing::instrument;
 
 #[derive(Provider)]
 #[shaku(interface = AuthService)]
@@ -26,6 +27,7 @@ pub struct AuthServiceImpl {
 
 #[async_trait]
 impl AuthService for AuthServiceImpl {
+    #[instrument(name = "AuthService.create_access_token", skip_all)]
     async fn create_access_token(&self, input: CreateAccessTokenInput) -> Result<AccessToken> {
         let user = match &input {
             CreateAccessTokenInput::Github { client_id, code } => {
@@ -53,7 +55,7 @@ impl AuthService for AuthServiceImpl {
 
                 match self
                     .user_service
-                    .select_user(UserIdentifier::GithubUserId(github_user_id))
+                    .select_account(UserIdentifier::GithubUserId(github_user_id))
                     .await
                 {
                     Ok(user) => user,
@@ -105,7 +107,7 @@ impl AuthService for AuthServiceImpl {
 
                 match self
                     .user_service
-                    .select_user(UserIdentifier::GoogleUserId(google_user_id.to_owned()))
+                    .select_account(UserIdentifier::GoogleUserId(google_user_id.to_owned()))
                     .await
                 {
                     Ok(user) => user,
@@ -121,19 +123,23 @@ impl AuthService for AuthServiceImpl {
                 }
             }
             CreateAccessTokenInput::RefreshToken { refresh_token } => {
-                let user_id = RefreshTokenPayload::decode(
+                let payload = RefreshTokenPayload::decode(
                     &refresh_token,
                     &(*self.refresh_token_config).secret,
                 )
-                .map_err(|e| Error::unauthorized(e.to_string()))?
-                .sub;
+                .map_err(|e| Error::unauthorized(e.to_string()))?;
 
-                self.user_service
-                    .select_user(UserIdentifier::Id(user_id.into()))
-                    .await?
+                let user = self
+                    .user_service
+                    .select_account(UserIdentifier::Id(payload.sub))
+                    .await?;
+                user.can_sign_in(Some(payload.iat))?;
+                user
             }
         };
 
+        user.can_sign_in(None)?;
+
         Ok(AccessToken::new(
             user,
             (
@@ -146,6 +152,43 @@ impl AuthService for AuthServiceImpl {
             ),
         ))
     }
+
+    #[instrument(name = "AuthService.delete_account", skip_all, fields(viewer_id = %viewer_id))]
+    async fn delete_account(&self, viewer_id: UserId) -> Result<AccountDeletion> {
+        let user = self
+            .user_service
+            .delete_user(viewer_id.to_owned(), viewer_id)
+            .await?;
+
+        let now_sec = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .expect("Invalid system time")
+            .as_secs();
+        let expires_in_sec = (user.purge_at.unwrap_or_default() / 1000).saturating_sub(now_sec);
+
+        Ok(AccountDeletion {
+            restore_token: RestoreTokenPayload::new(user.id.to_owned(), expires_in_sec)
+                .encode(self.restore_token_secret()),
+            user,
+        })
+    }
+
+    #[instrument(name = "AuthService.restore_account", skip_all)]
+    async fn restore_account(&self, restore_token: String) -> Result<User> {
+        let user_id = RestoreTokenPayload::decode(&restore_token, self.restore_token_secret())
+            .map_err(|e| Error::unauthorized(e.to_string()))?
+            .sub;
+
+        self.user_service.restore_user(user_id).await
+    }
+}
+
+impl AuthServiceImpl {
+    /// Restore tokens are signed with their own secret, derived from the
+    /// refresh token one, so that they can not be used as the other tokens.
+    fn restore_token_secret(&self) -> String {
+        format!("{}:restore_account", self.refresh_token_config.secret)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Component)]
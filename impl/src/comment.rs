@@ -0,0 +1,464 @@
+use std::ops::Deref;
+
+use async_trait::async_trait;
+use bson::doc;
+use futures::StreamExt;
+use mongodb::options::{FindOneAndUpdateOptions, FindOneOptions, FindOptions};
+use paper::{
+    comment::*, notification::*, paper::*, user::*, Error, OrderBy, OrderDirection, Pagination,
+    PaginationList, Result,
+};
+use shaku::Provider;
+use tracing::instrument;
+
+use crate::utils::*;
+
+#[derive(Provider)]
+#[shaku(interface = CommentService)]
+pub struct CommentServiceImpl {
+    #[shaku(provide)]
+    pub comment_collection: Box<dyn CommentCollection>,
+
+    #[shaku(provide)]
+    pub paper_service: Box<dyn PaperService>,
+
+    #[shaku(provide)]
+    pub user_service: Box<dyn UserService>,
+
+    #[shaku(provide)]
+    pub notification_service: Box<dyn NotificationService>,
+}
+
+pub trait CommentCollection: Deref<Target = mongodb::Collection> + Send + Sync {}
+
+/// Characters of the body of a comment.
+const MAX_BODY_LENGTH: usize = 10000;
+
+/// Users notified of a mention in a comment, the others are ignored.
+const MAX_MENTIONS: usize = 10;
+
+#[async_trait]
+impl CommentService for CommentServiceImpl {
+    #[instrument(
+        name = "CommentService.create_comment",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id, paper_id = %paper_id)
+    )]
+    async fn create_comment(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        paper_id: PaperId,
+        input: CreateCommentInput,
+    ) -> Result<Comment> {
+        let body = validate_body(input.body)?;
+
+        self.paper_service
+            .can_viewer_read_paper(
+                viewer_id.to_owned(),
+                user_id.to_owned(),
+                paper_id.to_owned(),
+            )
+            .await?;
+
+        let thread = match input.parent_id {
+            Some(parent_id) => {
+                let parent = self.find_comment(&parent_id).await?;
+                if parent.paper_id != paper_id {
+                    return Err(Error::not_found("Comment not found".to_owned()));
+                }
+                match &parent.thread_id {
+                    Some(thread_id) => Some(self.find_comment(thread_id).await?),
+                    None => Some(parent),
+                }
+            }
+            None => None,
+        };
+
+        if let Some(anchor) = &input.anchor {
+            if thread.is_some() {
+                return Err(Error::invalid_input(
+                    "A reply is anchored by its thread".to_owned(),
+                ));
+            }
+            if anchor.start > anchor.end {
+                return Err(Error::invalid_input(
+                    "The anchor starts after its end".to_owned(),
+                ));
+            }
+        }
+
+        let now = now_msec();
+
+        let comment = Comment {
+            id: new_id().into(),
+            user_id,
+            paper_id,
+            author_id: viewer_id,
+            thread_id: thread.as_ref().map(|x| x.id.to_owned()),
+            body,
+            anchor: input.anchor,
+            created_at: now,
+            updated_at: now,
+            deleted_at: None,
+            resolved_at: None,
+            resolved_by: None,
+        };
+
+        self.comment_collection
+            .insert_one(to_doc(&comment)?, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        // The comment is added even if its notifications are not.
+        if let Err(e) = self.notify(&comment, thread.as_ref()).await {
+            log::error!("Failed to notify of comment {}: {}", comment.id, e);
+        }
+
+        Ok(comment)
+    }
+
+    #[instrument(
+        name = "CommentService.update_comment",
+        skip_all,
+        fields(viewer_id = %viewer_id, comment_id = %comment_id)
+    )]
+    async fn update_comment(
+        &self,
+        viewer_id: UserId,
+        comment_id: CommentId,
+        body: String,
+    ) -> Result<Comment> {
+        let body = validate_body(body)?;
+
+        let comment = self.find_comment(&comment_id).await?;
+
+        self.paper_service
+            .can_viewer_read_paper(
+                viewer_id.to_owned(),
+                comment.user_id.to_owned(),
+                comment.paper_id.to_owned(),
+            )
+            .await?;
+
+        if comment.author_id != viewer_id {
+            return Err(Error::forbidden(
+                "Only the author can edit a comment".to_owned(),
+            ));
+        }
+        if comment.deleted_at.is_some() {
+            return Err(Error::not_found("Comment not found".to_owned()));
+        }
+
+        self.update_comment_set(
+            &comment_id,
+            doc! { "body": body, "updated_at": now_msec() as i64 },
+        )
+        .await
+    }
+
+    #[instrument(
+        name = "CommentService.delete_comment",
+        skip_all,
+        fields(viewer_id = %viewer_id, comment_id = %comment_id)
+    )]
+    async fn delete_comment(&self, viewer_id: UserId, comment_id: CommentId) -> Result<Comment> {
+        let comment = self.find_comment(&comment_id).await?;
+
+        self.can_viewer_moderate_comment(viewer_id, &comment)
+            .await?;
+
+        if comment.deleted_at.is_some() {
+            return Ok(comment);
+        }
+
+        let now = now_msec() as i64;
+        self.update_comment_set(
+            &comment_id,
+            doc! { "body": "", "deleted_at": now, "updated_at": now },
+        )
+        .await
+    }
+
+    #[instrument(
+        name = "CommentService.resolve_thread",
+        skip_all,
+        fields(viewer_id = %viewer_id, comment_id = %comment_id)
+    )]
+    async fn resolve_thread(
+        &self,
+        viewer_id: UserId,
+        comment_id: CommentId,
+        resolved: bool,
+    ) -> Result<Comment> {
+        let comment = self.find_comment(&comment_id).await?;
+        let thread = match &comment.thread_id {
+            Some(thread_id) => self.find_comment(thread_id).await?,
+            None => comment,
+        };
+
+        self.can_viewer_moderate_comment(viewer_id.to_owned(), &thread)
+            .await?;
+
+        let update_set = match resolved {
+            true => doc! {
+                "resolved_at": now_msec() as i64,
+                "resolved_by": viewer_id.to_string(),
+            },
+            false => doc! { "resolved_at": null, "resolved_by": null },
+        };
+
+        self.update_comment_set(&thread.id, update_set).await
+    }
+
+    #[instrument(
+        name = "CommentService.select_comment",
+        skip_all,
+        fields(viewer_id = %viewer_id, comment_id = %comment_id)
+    )]
+    async fn select_comment(&self, viewer_id: UserId, comment_id: CommentId) -> Result<Comment> {
+        let comment = self.find_comment(&comment_id).await?;
+
+        self.paper_service
+            .can_viewer_read_paper(
+                viewer_id,
+                comment.user_id.to_owned(),
+                comment.paper_id.to_owned(),
+            )
+            .await?;
+
+        Ok(comment)
+    }
+
+    #[instrument(
+        name = "CommentService.select_thread_page",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id, paper_id = %paper_id)
+    )]
+    async fn select_thread_page(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        paper_id: PaperId,
+        pagination: Pagination<CommentCursor>,
+        filter: CommentFilter,
+    ) -> Result<PaginationList<Comment>> {
+        self.paper_service
+            .can_viewer_read_paper(viewer_id, user_id, paper_id.to_owned())
+            .await?;
+
+        let mut query = doc! { "paper_id": paper_id.to_string(), "thread_id": null };
+        match filter.resolved {
+            Some(true) => query.insert("resolved_at", doc! { "$ne": null }),
+            Some(false) => query.insert("resolved_at", bson::Bson::Null),
+            None => None,
+        };
+
+        mongodb_select_pagination(
+            &self.comment_collection,
+            pagination,
+            query,
+            OrderBy {
+                field: "_id",
+                direction: OrderDirection::Asc,
+            },
+            FindOptions::default(),
+        )
+        .await
+    }
+
+    #[instrument(
+        name = "CommentService.select_replies",
+        skip_all,
+        fields(viewer_id = %viewer_id, thread_id = %thread_id)
+    )]
+    async fn select_replies(
+        &self,
+        viewer_id: UserId,
+        thread_id: CommentId,
+    ) -> Result<Vec<Comment>> {
+        let thread = self.find_comment(&thread_id).await?;
+
+        self.paper_service
+            .can_viewer_read_paper(viewer_id, thread.user_id, thread.paper_id)
+            .await?;
+
+        self.comment_collection
+            .find(
+                doc! { "thread_id": thread_id.to_string() },
+                FindOptions::builder().sort(doc! { "_id": 1 }).build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| {
+                x.map_err(|e| Error::unknown(e.to_string()))
+                    .and_then(|x| from_doc::<Comment>(x))
+            })
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect()
+    }
+}
+
+impl CommentServiceImpl {
+    async fn find_comment(&self, comment_id: &CommentId) -> Result<Comment> {
+        self.comment_collection
+            .find_one(
+                doc! { "_id": comment_id.to_string() },
+                FindOneOptions::default(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| from_doc::<Comment>(x))
+            .ok_or_else(|| Error::not_found("Comment not found".to_owned()))?
+    }
+
+    async fn update_comment_set(
+        &self,
+        comment_id: &CommentId,
+        update_set: bson::Document,
+    ) -> Result<Comment> {
+        self.comment_collection
+            .find_one_and_update(
+                doc! { "_id": comment_id.to_string() },
+                doc! { "$set": update_set },
+                FindOneAndUpdateOptions::builder()
+                    .return_document(mongodb::options::ReturnDocument::After)
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| from_doc::<Comment>(x))
+            .ok_or_else(|| Error::not_found("Comment not found".to_owned()))?
+    }
+
+    /// Notify the users mentioned in the comment and the author of its thread,
+    /// once each, if they can read the paper.
+    async fn notify(&self, comment: &Comment, thread: Option<&Comment>) -> Result<()> {
+        let mut recipients: Vec<(UserId, NotificationKind)> = vec![];
+
+        for name in mentions(&comment.body).into_iter().take(MAX_MENTIONS) {
+            if let Ok(user) = self
+                .user_service
+                .select_user(UserIdentifier::Name(name))
+                .await
+            {
+                recipients.push((user.id, NotificationKind::Mention));
+            }
+        }
+        if let Some(thread) = thread {
+            recipients.push((thread.author_id.to_owned(), NotificationKind::Reply));
+        }
+
+        let mut inputs: Vec<NotificationInput> = vec![];
+        for (user_id, kind) in recipients {
+            if user_id == comment.author_id || inputs.iter().any(|x| x.user_id == user_id) {
+                continue;
+            }
+
+            let can_read = self
+                .paper_service
+                .can_viewer_read_paper(
+                    user_id.to_owned(),
+                    comment.user_id.to_owned(),
+                    comment.paper_id.to_owned(),
+                )
+                .await
+                .is_ok();
+            if !can_read {
+                continue;
+            }
+
+            inputs.push(NotificationInput {
+                user_id,
+                kind,
+                actor_id: Some(comment.author_id.to_owned()),
+                paper_id: Some(comment.paper_id.to_owned()),
+                comment_id: Some(comment.id.to_owned()),
+            });
+        }
+
+        self.notification_service.notify(inputs).await.map(|_| ())
+    }
+
+    /// The author of a comment and the writers of its paper moderate it.
+    async fn can_viewer_moderate_comment(
+        &self,
+        viewer_id: UserId,
+        comment: &Comment,
+    ) -> Result<()> {
+        self.paper_service
+            .can_viewer_read_paper(
+                viewer_id.to_owned(),
+                comment.user_id.to_owned(),
+                comment.paper_id.to_owned(),
+            )
+            .await?;
+
+        if comment.author_id == viewer_id {
+            return Ok(());
+        }
+
+        self.paper_service
+            .can_viewer_write_paper(
+                viewer_id,
+                comment.user_id.to_owned(),
+                comment.paper_id.to_owned(),
+            )
+            .await
+            .map(|_| ())
+    }
+}
+
+impl PaginationCursor for CommentCursor {
+    fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn value(&self, _field: &str) -> Option<bson::Bson> {
+        None
+    }
+}
+
+/// The names mentioned by `@name` in `body`, in order and without
+/// duplicates. A name is made of letters, digits, `_`, `-` and `.`, other
+/// names can not be mentioned.
+fn mentions(body: &str) -> Vec<String> {
+    let is_name_char = |c: char| c.is_alphanumeric() || c == '_' || c == '-' || c == '.';
+
+    let mut names: Vec<String> = vec![];
+    for (index, _) in body.match_indices('@') {
+        // An `@` inside a word is part of an email address.
+        if body[..index].chars().last().map_or(false, is_name_char) {
+            continue;
+        }
+
+        let rest = &body[index + 1..];
+        let end = rest
+            .find(|c: char| !is_name_char(c))
+            .unwrap_or_else(|| rest.len());
+        let name = rest[..end].trim_end_matches('.');
+
+        if !name.is_empty() && !names.iter().any(|x| x == name) {
+            names.push(name.to_owned());
+        }
+    }
+    names
+}
+
+fn validate_body(body: String) -> Result<String> {
+    let body = body.trim().to_owned();
+    if body.is_empty() {
+        return Err(Error::invalid_input(
+            "A comment can not be empty".to_owned(),
+        ));
+    }
+    if body.chars().count() > MAX_BODY_LENGTH {
+        return Err(Error::invalid_input(format!(
+            "A comment is at most {} characters",
+            MAX_BODY_LENGTH
+        )));
+    }
+    Ok(body)
+}
@@ -0,0 +1,90 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use paper::{notification::Notification, paper::PaperEvent, user::UserEvent};
+use serde::{Deserialize, Serialize};
+use shaku::{Component, Provider};
+use tokio::sync::broadcast;
+
+use crate::webhook::{
+    enqueue_deliveries, webhook_event, WebhookCollection, WebhookDeliveryCollection,
+};
+
+/// In-process event bus, the sender is shared by all modules so subscribers
+/// receive events from every http worker.
+#[derive(Component)]
+#[shaku(interface = EventBusInterface)]
+pub struct EventBus {
+    pub sender: broadcast::Sender<Event>,
+}
+
+crate::shaku_deref_self_interface!(EventBusInterface, EventBus);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    Paper(PaperEvent),
+
+    User(UserEvent),
+
+    /// Stored by `NotificationService`, for the subscriptions of its
+    /// recipient.
+    Notification(Notification),
+}
+
+impl EventBus {
+    pub fn channel(capacity: usize) -> broadcast::Sender<Event> {
+        broadcast::channel(capacity).0
+    }
+
+    pub fn publish(&self, event: Event) {
+        // No receivers is not an error, nobody is listening right now.
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+/// Publishes the changes made by the services. Their webhook deliveries are
+/// stored before the event is published, they are not lost when the server
+/// stops or a receiver of the bus lags behind.
+#[async_trait]
+pub trait EventPublisher: Send + Sync {
+    async fn publish(&self, event: Event);
+}
+
+#[derive(Provider)]
+#[shaku(interface = EventPublisher)]
+pub struct EventPublisherImpl {
+    #[shaku(provide)]
+    pub webhook_collection: Box<dyn WebhookCollection>,
+
+    #[shaku(provide)]
+    pub webhook_delivery_collection: Box<dyn WebhookDeliveryCollection>,
+
+    #[shaku(inject)]
+    pub event_bus: Arc<dyn EventBusInterface>,
+}
+
+#[async_trait]
+impl EventPublisher for EventPublisherImpl {
+    async fn publish(&self, event: Event) {
+        // The change has been made, it is published even if its deliveries
+        // are not queued.
+        if let Some(webhook_event) = webhook_event(&event) {
+            if let Err(e) = enqueue_deliveries(
+                &self.webhook_collection,
+                &self.webhook_delivery_collection,
+                webhook_event,
+            )
+            .await
+            {
+                log::error!("Failed to queue webhook deliveries: {}", e);
+            }
+        }
+
+        self.event_bus.publish(event);
+    }
+}
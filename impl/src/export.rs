@@ -0,0 +1,447 @@
+use std::{ops::Deref, sync::Arc};
+
+use async_trait::async_trait;
+use bson::{doc, Document};
+use futures::{channel::mpsc, StreamExt};
+use lazy_static::lazy_static;
+use mongodb::options::{FindOneAndUpdateOptions, FindOneOptions, FindOptions};
+use paper::{
+    attachment::Attachment, blob::BlobStream, export::*, user::*, Error, ErrorKind, Result,
+};
+use serde::Serialize;
+use shaku::{Component, Provider};
+use tracing::instrument;
+
+use crate::{
+    archive::ZipWriter,
+    attachment::AttachmentCollection,
+    audit::AuditCollection,
+    blob::{purge_with_blobs, BlobStorageInterface},
+    comment::CommentCollection,
+    notification::NotificationCollection,
+    paper::PaperCollection,
+    user::UserCollection,
+    utils::*,
+    webhook::WebhookCollection,
+};
+
+#[derive(Provider)]
+#[shaku(interface = ExportService)]
+pub struct ExportServiceImpl {
+    #[shaku(provide)]
+    pub export_collection: Box<dyn ExportCollection>,
+
+    #[shaku(provide)]
+    pub user_collection: Box<dyn UserCollection>,
+
+    #[shaku(provide)]
+    pub paper_collection: Box<dyn PaperCollection>,
+
+    #[shaku(provide)]
+    pub comment_collection: Box<dyn CommentCollection>,
+
+    #[shaku(provide)]
+    pub audit_collection: Box<dyn AuditCollection>,
+
+    #[shaku(provide)]
+    pub webhook_collection: Box<dyn WebhookCollection>,
+
+    #[shaku(provide)]
+    pub notification_collection: Box<dyn NotificationCollection>,
+
+    #[shaku(provide)]
+    pub attachment_collection: Box<dyn AttachmentCollection>,
+
+    #[shaku(provide)]
+    pub user_service: Box<dyn UserService>,
+
+    #[shaku(inject)]
+    pub export_config: Arc<dyn ExportConfigInterface>,
+
+    #[shaku(inject)]
+    pub blob_storage: Arc<dyn BlobStorageInterface>,
+}
+
+pub trait ExportCollection: Deref<Target = mongodb::Collection> + Send + Sync {}
+
+#[derive(Debug, Clone, Component)]
+#[shaku(interface = ExportConfigInterface)]
+pub struct ExportConfig {
+    /// Seconds an export is kept once it has been requested or assembled.
+    pub expires_in_sec: u64,
+}
+
+crate::shaku_deref_self_interface!(ExportConfigInterface, ExportConfig);
+
+/// A pending export older than this has been interrupted, e.g. by a restart,
+/// and a new one is queued instead.
+const PENDING_TIMEOUT_MSEC: u64 = 10 * 60 * 1000;
+
+const README: &str = "\
+Everything held about your account.
+
+user.json           Your account, with the profile received from GitHub or
+                    Google.
+papers.json         Your papers, including the deleted ones not purged yet.
+comments.json       The comments you wrote, on your papers and others'.
+audit.json          The audit events you took part in, with their IP and user
+                    agent.
+webhooks.json       Your webhooks, without their secrets.
+notifications.json  Your notifications, read or not.
+attachments.json    The files attached to your papers.
+attachments/        The content of the files attached to your papers, in a
+                    directory per attachment.
+
+Times are milliseconds since 1970-01-01 UTC. Sessions are signed tokens which
+are not stored, user.json tells when they have last been revoked.
+";
+
+#[async_trait]
+impl ExportService for ExportServiceImpl {
+    #[instrument(
+        name = "ExportService.request_export",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
+    async fn request_export(&self, viewer_id: UserId, user_id: UserId) -> Result<DataExport> {
+        self.user_service
+            .can_viewer_administer_user(viewer_id, user_id.to_owned())
+            .await?;
+
+        let now = now_msec();
+
+        purge_with_blobs(
+            &self.export_collection,
+            &*self.blob_storage.store,
+            doc! { "expires_at": { "$lt": now as i64 } },
+            DataExport::blob_key,
+            |_| {},
+        )
+        .await?;
+
+        let pending = self
+            .export_collection
+            .find_one(
+                doc! {
+                    "user_id": user_id.to_string(),
+                    "status": "pending",
+                    "created_at": { "$gt": now.saturating_sub(PENDING_TIMEOUT_MSEC) as i64 },
+                },
+                FindOneOptions::builder()
+                    .projection(EXPORT_PROJECTION.to_owned())
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| from_doc::<DataExport>(x))
+            .transpose()?;
+
+        if let Some(export) = pending {
+            return Ok(export);
+        }
+
+        let export = DataExport {
+            id: new_id().into(),
+            user_id,
+            status: ExportStatus::Pending,
+            created_at: now,
+            expires_at: now + self.export_config.expires_in_sec * 1000,
+            error: None,
+        };
+
+        self.export_collection
+            .insert_one(to_doc(&export)?, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        Ok(export)
+    }
+
+    #[instrument(name = "ExportService.build_export", skip_all, fields(export_id = %export_id))]
+    async fn build_export(&self, export_id: ExportId) -> Result<DataExport> {
+        // Claim the export, so that it is assembled once.
+        let export = self
+            .export_collection
+            .find_one_and_update(
+                doc! { "_id": export_id.to_string(), "status": "pending", "started_at": null },
+                doc! { "$set": { "started_at": now_msec() as i64 } },
+                FindOneAndUpdateOptions::builder()
+                    .projection(EXPORT_PROJECTION.to_owned())
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| from_doc::<DataExport>(x))
+            .transpose()?
+            .ok_or_else(|| Error::not_found("Unclaimed pending export not found".to_owned()))?;
+
+        let mut update_set = doc! {
+            "expires_at": (now_msec() + self.export_config.expires_in_sec * 1000) as i64,
+        };
+        // The archive is kept in the blob store, a document is limited to
+        // 16MB, and sent to it as it is written.
+        let (sender, receiver) = mpsc::channel(4);
+        let written = async {
+            let mut zip = ZipWriter::new(sender);
+            match self.archive(&export.user_id, &mut zip).await {
+                Ok(()) => zip.finish().await,
+                Err(e) => {
+                    zip.abort(e.clone()).await;
+                    Err(e)
+                }
+            }
+        };
+        let stored = self.blob_storage.store.put_stream(
+            &export.blob_key(),
+            Box::pin(receiver),
+            "application/zip",
+        );
+        // An archive the store fails to take fails for not being read, the
+        // error of the store tells why.
+        let (written, stored) = futures::join!(written, stored);
+        match stored.and(written) {
+            Ok(()) => {
+                update_set.insert("status", "ready");
+            }
+            Err(e) => {
+                log::error!("Failed to assemble export {}: {}", export_id, e);
+                update_set.insert("status", "failed");
+                update_set.insert("error", e.to_string());
+            }
+        }
+
+        self.export_collection
+            .find_one_and_update(
+                doc! { "_id": export_id.to_string() },
+                doc! { "$set": update_set },
+                FindOneAndUpdateOptions::builder()
+                    .return_document(mongodb::options::ReturnDocument::After)
+                    .projection(EXPORT_PROJECTION.to_owned())
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| from_doc::<DataExport>(x))
+            .transpose()?
+            .ok_or_else(|| Error::not_found("Export not found".to_owned()))
+    }
+
+    #[instrument(
+        name = "ExportService.select_exports",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
+    async fn select_exports(&self, viewer_id: UserId, user_id: UserId) -> Result<Vec<DataExport>> {
+        self.user_service
+            .can_viewer_administer_user(viewer_id, user_id.to_owned())
+            .await?;
+
+        self.export_collection
+            .find(
+                doc! {
+                    "user_id": user_id.to_string(),
+                    "expires_at": { "$gt": now_msec() as i64 },
+                },
+                FindOptions::builder()
+                    .sort(doc! { "created_at": -1 })
+                    .projection(EXPORT_PROJECTION.to_owned())
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| {
+                x.map_err(|e| Error::unknown(e.to_string()))
+                    .and_then(|x| from_doc::<DataExport>(x))
+            })
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    #[instrument(
+        name = "ExportService.select_export_archive",
+        skip_all,
+        fields(export_id = %export_id)
+    )]
+    async fn select_export_archive(&self, export_id: ExportId) -> Result<BlobStream> {
+        let export = self
+            .export_collection
+            .find_one(
+                doc! {
+                    "_id": export_id.to_string(),
+                    "status": "ready",
+                    "expires_at": { "$gt": now_msec() as i64 },
+                },
+                FindOneOptions::builder()
+                    .projection(EXPORT_PROJECTION.to_owned())
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| from_doc::<DataExport>(x))
+            .transpose()?
+            .ok_or_else(|| Error::not_found("Export not found".to_owned()))?;
+
+        self.blob_storage.store.get_stream(&export.blob_key()).await
+    }
+}
+
+impl ExportServiceImpl {
+    /// Write the zip of the documents stored about the user, as they are
+    /// stored, and of the content of their attachments.
+    async fn archive(&self, user_id: &UserId, zip: &mut ZipWriter) -> Result<()> {
+        let user = self
+            .user_collection
+            .find_one(doc! { "_id": user_id.to_string() }, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .ok_or_else(|| Error::not_found("User not found".to_owned()))?;
+
+        zip.start_file("README.txt").await?;
+        zip.write_all(README.as_bytes()).await?;
+        zip.start_file("user.json").await?;
+        zip.write_all(&to_json(&user)?).await?;
+
+        let user_filter = doc! { "user_id": user_id.to_string() };
+        write_documents(
+            zip,
+            "papers.json",
+            &self.paper_collection,
+            user_filter.clone(),
+            None,
+        )
+        .await?;
+        write_documents(
+            zip,
+            "comments.json",
+            &self.comment_collection,
+            doc! { "author_id": user_id.to_string() },
+            None,
+        )
+        .await?;
+        write_documents(
+            zip,
+            "audit.json",
+            &self.audit_collection,
+            doc! {
+                "$or": vec![
+                    doc! { "actor_id": user_id.to_string() },
+                    doc! { "user_id": user_id.to_string() },
+                ],
+            },
+            None,
+        )
+        .await?;
+        // The secrets are not needed to tell what is sent where.
+        write_documents(
+            zip,
+            "webhooks.json",
+            &self.webhook_collection,
+            user_filter.clone(),
+            Some(doc! { "secret": 0 }),
+        )
+        .await?;
+        write_documents(
+            zip,
+            "notifications.json",
+            &self.notification_collection,
+            user_filter.clone(),
+            None,
+        )
+        .await?;
+        write_documents(
+            zip,
+            "attachments.json",
+            &self.attachment_collection,
+            user_filter,
+            None,
+        )
+        .await?;
+
+        let attachments = self
+            .attachment_collection
+            .find(
+                doc! { "user_id": user_id.to_string(), "status": "ready" },
+                FindOptions::builder().sort(doc! { "_id": 1 }).build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| {
+                x.map_err(|e| Error::unknown(e.to_string()))
+                    .and_then(|x| from_doc::<Attachment>(x))
+            })
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+
+        for attachment in attachments {
+            let mut content = match self
+                .blob_storage
+                .store
+                .get_stream(&attachment.blob_key())
+                .await
+            {
+                Ok(content) => content,
+                // Deleted since it has been listed.
+                Err(e) if e.kind == ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+
+            zip.start_file(&format!(
+                "attachments/{}/{}",
+                attachment.id, attachment.name
+            ))
+            .await?;
+            while let Some(chunk) = content.next().await {
+                zip.write_all(&chunk?).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Write the documents of `collection` matching `filter` to the file `name` of
+/// the archive, as a JSON array, as they are read.
+async fn write_documents(
+    zip: &mut ZipWriter,
+    name: &str,
+    collection: &mongodb::Collection,
+    filter: Document,
+    projection: Option<Document>,
+) -> Result<()> {
+    let mut options = FindOptions::builder().sort(doc! { "_id": 1 }).build();
+    options.projection = projection;
+    let mut docs = collection
+        .find(filter, options)
+        .await
+        .map_err(|e| Error::unknown(e.to_string()))?;
+
+    zip.start_file(name).await?;
+    zip.write_all(b"[").await?;
+    let mut separator: &[u8] = b"\n";
+    while let Some(doc) = docs.next().await {
+        let doc = doc.map_err(|e| Error::unknown(e.to_string()))?;
+        zip.write_all(separator).await?;
+        zip.write_all(&to_json(&doc)?).await?;
+        separator = b",\n";
+    }
+    zip.write_all(b"\n]\n").await
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    serde_json::to_vec_pretty(value).map_err(|e| Error::unknown(e.to_string()))
+}
+
+lazy_static! {
+    pub(crate) static ref EXPORT_PROJECTION: Document = doc! {
+        "_id": 1,
+        "user_id": 1,
+        "status": 1,
+        "created_at": 1,
+        "expires_at": 1,
+        "error": 1,
+    };
+}
@@ -1,6 +1,17 @@
+pub mod admin;
+mod archive;
+pub mod attachment;
+pub mod audit;
 pub mod auth;
+pub mod blob;
+pub mod comment;
+pub mod event;
+pub mod export;
+pub mod notification;
 pub mod paper;
+pub mod quota;
 pub mod user;
+pub mod webhook;
 
 #[macro_export]
 macro_rules! shaku_deref_self_interface {
@@ -26,7 +37,7 @@ mod utils {
         options::{FindOneOptions, FindOptions},
         Collection,
     };
-    use paper::{Error, OrderBy, OrderDirection, Pagination, PaginationList, Result};
+    use paper::{Error, OrderBy, OrderDirection, Pagination, PaginationList, Result, TimeRange};
     use serde::{de::DeserializeOwned, Serialize};
 
     pub fn new_id() -> String {
@@ -59,6 +70,48 @@ mod utils {
         from_document(doc).map_err(|e| Error::unknown(e.to_string()))
     }
 
+    /// Comparison operators matching the values of `range`.
+    pub fn time_range_filter(range: &TimeRange) -> Document {
+        let mut filter = doc! {};
+        if let Some(from) = range.from {
+            filter.insert("$gte", from as i64);
+        }
+        if let Some(to) = range.to {
+            filter.insert("$lt", to as i64);
+        }
+        filter
+    }
+
+    /// Delete the documents of `collection` matching `filter` created more than
+    /// `days` ago, 0 days keeps them forever. Returns the deleted count.
+    pub async fn purge_older_than(
+        collection: &Collection,
+        days: u64,
+        mut filter: Document,
+    ) -> Result<u64> {
+        if days == 0 {
+            return Ok(0);
+        }
+
+        let created_before = now_msec().saturating_sub(days * 24 * 3600 * 1000);
+        filter.insert("created_at", doc! { "$lt": created_before as i64 });
+
+        let result = collection
+            .delete_many(filter, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        Ok(result.deleted_count as u64)
+    }
+
+    /// A position in a sorted collection, it must carry the value of every
+    /// field a list can be ordered by.
+    pub trait PaginationCursor {
+        fn id(&self) -> String;
+
+        fn value(&self, field: &str) -> Option<Bson>;
+    }
+
     pub async fn mongodb_select_pagination<T, K, F, S, O>(
         collection: &Collection,
         pagination: Pagination<K>,
@@ -68,19 +121,19 @@ mod utils {
     ) -> Result<PaginationList<T>>
     where
         T: DeserializeOwned,
-        K: ToString,
+        K: PaginationCursor,
         F: Into<Option<Document>>,
         S: AsRef<str>,
         O: Into<Option<FindOptions>>,
     {
         let filter: Document = filter.into().unwrap_or(doc! {});
 
-        let (order_direction, order_op) = match (&pagination, order_by.direction) {
+        let (order_direction, order_op, reverse_op) = match (&pagination, order_by.direction) {
             (Pagination::After { .. }, OrderDirection::Asc)
-            | (Pagination::Before { .. }, OrderDirection::Desc) => (1 as i32, "$gt"),
+            | (Pagination::Before { .. }, OrderDirection::Desc) => (1 as i32, "$gt", "$lt"),
 
             (Pagination::After { .. }, OrderDirection::Desc)
-            | (Pagination::Before { .. }, OrderDirection::Asc) => (-1 as i32, "$lt"),
+            | (Pagination::Before { .. }, OrderDirection::Asc) => (-1 as i32, "$lt", "$gt"),
         };
         let order_field = order_by.field.as_ref();
 
@@ -88,7 +141,12 @@ mod utils {
             Pagination::After { after, skip, first } => (after, skip, *first),
             Pagination::Before { before, skip, last } => (before, skip, *last),
         };
-        let cursor = cursor.as_ref().map(|x| x.to_string());
+
+        if cursor.is_some() && skip.is_some() {
+            return Err(Error::invalid_input(
+                "skip can not be combined with after or before".to_owned(),
+            ));
+        }
 
         let mut list_opts = list_opts.into().unwrap_or_default();
         list_opts.sort = Some({
@@ -101,38 +159,10 @@ mod utils {
         list_opts.skip = skip.map(|x| x as i64);
         list_opts.limit = Some(limit as i64 + 1);
 
-        let mut list_filter = match &cursor {
-            Some(cursor) => match order_field {
-                "_id" => doc! { "_id": { order_op: cursor } },
-                order_field => {
-                    match collection
-                        .find_one(
-                            doc! { "_id": cursor },
-                            FindOneOptions::builder()
-                                .projection(doc! { order_field: 1 })
-                                .build(),
-                        )
-                        .await
-                        .map_err(|e| Error::unknown(e.to_string()))?
-                        .as_ref()
-                        .and_then(|doc| doc.get(order_field))
-                    {
-                        Some(order_field_value) => doc! {
-                            "$or": [
-                                { order_field: { order_op: order_field_value } },
-                                {
-                                    order_field: order_field_value,
-                                    "_id": { order_op: cursor },
-                                }
-                            ],
-                        },
-                        None => doc! { "_id": { order_op: cursor } },
-                    }
-                }
-            },
+        let mut list_filter = match cursor {
+            Some(cursor) => cursor_filter(cursor, order_field, order_op)?,
             None => doc! {},
         };
-
         list_filter.extend(filter.clone());
 
         let mut list = collection
@@ -148,24 +178,174 @@ mod utils {
             .into_iter()
             .collect::<Result<Vec<T>>>()?;
 
-        if let Pagination::Before { .. } = pagination {
-            list.reverse();
+        // Whether there are more items in the direction of the pagination.
+        let has_more = list.len() > limit as usize;
+        if has_more {
+            list.pop();
         }
 
+        // Whether there are items on the other side of the cursor or skipped.
+        let has_less = match cursor {
+            Some(cursor) => {
+                let mut reverse_filter = cursor_filter(cursor, order_field, reverse_op)?;
+                reverse_filter.extend(filter.clone());
+
+                collection
+                    .find_one(
+                        reverse_filter,
+                        FindOneOptions::builder()
+                            .projection(doc! { "_id": 1 })
+                            .build(),
+                    )
+                    .await
+                    .map_err(|e| Error::unknown(e.to_string()))?
+                    .is_some()
+            }
+            None => skip.unwrap_or(0) > 0,
+        };
+
         let total = collection
             .count_documents(filter, None)
             .await
             .map_err(|e| Error::unknown(e.to_string()))? as u64;
 
-        let has_next_page = list.len() > limit as usize;
-        if has_next_page {
-            list.pop();
-        }
+        let (has_next_page, has_previous_page) = match pagination {
+            Pagination::After { .. } => (has_more, has_less),
+            Pagination::Before { .. } => {
+                list.reverse();
+                (has_less, has_more)
+            }
+        };
 
         Ok(PaginationList {
             list,
             total,
             has_next_page,
+            has_previous_page,
         })
     }
+
+    fn cursor_filter<K: PaginationCursor>(
+        cursor: &K,
+        order_field: &str,
+        op: &str,
+    ) -> Result<Document> {
+        let id = cursor.id();
+
+        match order_field {
+            "_id" => Ok(doc! { "_id": { op: id } }),
+            order_field => {
+                let value = cursor.value(order_field).ok_or_else(|| {
+                    Error::unknown(format!("Cursor does not contain the {} value", order_field))
+                })?;
+
+                Ok(doc! {
+                    "$or": [
+                        { order_field: { op: value.clone() } },
+                        { order_field: value, "_id": { op: id } },
+                    ],
+                })
+            }
+        }
+    }
+}
+
+/// Stand-ins for the tests against a running MongoDB, e.g. `docker run -p
+/// 27017:27017 mongo`, then `cargo test -p paper_impl -- --ignored`.
+/// `PAPER_TEST_MONGODB_URI` overrides `mongodb://127.0.0.1:27017`.
+#[cfg(test)]
+mod test_utils {
+    use std::{ops::Deref, sync::Arc};
+
+    use async_trait::async_trait;
+    use bson::doc;
+    use paper::user::{CreateUserInput, Role, User, UserService};
+
+    use crate::{
+        admin::AdminConfig,
+        event::{Event, EventPublisher},
+        user::{AccountConfig, UserServiceImpl},
+        utils::new_id,
+    };
+
+    /// A database of its own for each test, to be dropped by the test.
+    pub async fn test_database() -> mongodb::Database {
+        let uri = std::env::var("PAPER_TEST_MONGODB_URI")
+            .unwrap_or("mongodb://127.0.0.1:27017".to_owned());
+        mongodb::Client::with_uri_str(&uri)
+            .await
+            .unwrap()
+            .database(&format!("paper_test_{}", new_id()))
+    }
+
+    pub fn user_service(db: &mongodb::Database) -> UserServiceImpl {
+        UserServiceImpl {
+            user_collection: Box::new(TestCollection(db.collection("users"))),
+            admin_config: Arc::new(AdminConfig {
+                bootstrap_admins: vec![],
+                impersonation_expires_in_sec: 600,
+            }),
+            account_config: Arc::new(AccountConfig {
+                deletion_grace_period_sec: 600,
+            }),
+            event_publisher: Box::new(NoEvents),
+        }
+    }
+
+    pub async fn create_user(db: &mongodb::Database, name: &str, role: Role) -> User {
+        let user = user_service(db)
+            .create_user(CreateUserInput::Github {
+                github_user: serde_json::json!({ "id": new_id(), "name": name }),
+            })
+            .await
+            .unwrap();
+        db.collection("users")
+            .update_one(
+                doc! { "_id": user.id.to_string() },
+                doc! { "$set": { "role": bson::to_bson(&role).unwrap() } },
+                None,
+            )
+            .await
+            .unwrap();
+        User { role, ..user }
+    }
+
+    /// Any collection of the module.
+    pub struct TestCollection(pub mongodb::Collection);
+
+    impl Deref for TestCollection {
+        type Target = mongodb::Collection;
+
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl crate::attachment::AttachmentCollection for TestCollection {}
+
+    impl crate::audit::AuditCollection for TestCollection {}
+
+    impl crate::comment::CommentCollection for TestCollection {}
+
+    impl crate::export::ExportCollection for TestCollection {}
+
+    impl crate::notification::NotificationCollection for TestCollection {}
+
+    impl crate::paper::PaperCollection for TestCollection {}
+
+    impl crate::quota::QuotaCollection for TestCollection {}
+
+    impl crate::user::UserCollection for TestCollection {}
+
+    impl crate::webhook::WebhookCollection for TestCollection {}
+
+    impl crate::webhook::WebhookDeliveryCollection for TestCollection {}
+
+    /// Events go nowhere.
+    pub struct NoEvents;
+
+    #[async_trait]
+    impl EventPublisher for NoEvents {
+        async fn publish(&self, _event: Event) {}
+    }
 }
@@ -0,0 +1,196 @@
+use std::{ops::Deref, sync::Arc};
+
+use async_trait::async_trait;
+use bson::doc;
+use mongodb::options::FindOptions;
+use paper::{
+    notification::*, user::*, Error, OrderBy, OrderDirection, Pagination, PaginationList, Result,
+};
+use shaku::{Component, Provider};
+use tracing::instrument;
+
+use crate::{
+    event::{Event, EventBusInterface},
+    utils::*,
+};
+
+#[derive(Provider)]
+#[shaku(interface = NotificationService)]
+pub struct NotificationServiceImpl {
+    #[shaku(provide)]
+    pub notification_collection: Box<dyn NotificationCollection>,
+
+    #[shaku(provide)]
+    pub user_service: Box<dyn UserService>,
+
+    #[shaku(inject)]
+    pub event_bus: Arc<dyn EventBusInterface>,
+
+    #[shaku(inject)]
+    pub notification_config: Arc<dyn NotificationConfigInterface>,
+}
+
+pub trait NotificationCollection: Deref<Target = mongodb::Collection> + Send + Sync {}
+
+#[derive(Debug, Clone, Component)]
+#[shaku(interface = NotificationConfigInterface)]
+pub struct NotificationConfig {
+    /// Days the notifications are kept, 0 keeps them forever.
+    pub retention_days: u64,
+}
+
+crate::shaku_deref_self_interface!(NotificationConfigInterface, NotificationConfig);
+
+#[async_trait]
+impl NotificationService for NotificationServiceImpl {
+    #[instrument(name = "NotificationService.notify", skip_all, fields(count = inputs.len()))]
+    async fn notify(&self, inputs: Vec<NotificationInput>) -> Result<Vec<Notification>> {
+        if inputs.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let now = now_msec();
+
+        let notifications: Vec<Notification> = inputs
+            .into_iter()
+            .map(|input| Notification {
+                id: new_id().into(),
+                user_id: input.user_id,
+                kind: input.kind,
+                actor_id: input.actor_id,
+                paper_id: input.paper_id,
+                comment_id: input.comment_id,
+                created_at: now,
+                read_at: None,
+            })
+            .collect();
+
+        self.notification_collection
+            .insert_many(
+                notifications
+                    .iter()
+                    .map(to_doc)
+                    .collect::<Result<Vec<_>>>()?,
+                None,
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        for notification in &notifications {
+            self.event_bus
+                .publish(Event::Notification(notification.to_owned()));
+        }
+
+        Ok(notifications)
+    }
+
+    #[instrument(
+        name = "NotificationService.select_notification_page",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
+    async fn select_notification_page(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        pagination: Pagination<NotificationCursor>,
+        filter: NotificationFilter,
+    ) -> Result<PaginationList<Notification>> {
+        self.user_service
+            .can_viewer_write_user(viewer_id, user_id.to_owned())
+            .await?;
+
+        let mut query = doc! { "user_id": user_id.to_string() };
+        match filter.unread {
+            Some(true) => query.insert("read_at", bson::Bson::Null),
+            Some(false) => query.insert("read_at", doc! { "$ne": null }),
+            None => None,
+        };
+
+        mongodb_select_pagination(
+            &self.notification_collection,
+            pagination,
+            query,
+            OrderBy {
+                field: "_id",
+                direction: OrderDirection::Desc,
+            },
+            FindOptions::default(),
+        )
+        .await
+    }
+
+    #[instrument(
+        name = "NotificationService.count_unread_notifications",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
+    async fn count_unread_notifications(&self, viewer_id: UserId, user_id: UserId) -> Result<u64> {
+        self.user_service
+            .can_viewer_write_user(viewer_id, user_id.to_owned())
+            .await?;
+
+        self.notification_collection
+            .count_documents(
+                doc! { "user_id": user_id.to_string(), "read_at": null },
+                None,
+            )
+            .await
+            .map(|x| x as u64)
+            .map_err(|e| Error::unknown(e.to_string()))
+    }
+
+    #[instrument(
+        name = "NotificationService.mark_notifications_read",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
+    async fn mark_notifications_read(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        notification_ids: Option<Vec<NotificationId>>,
+    ) -> Result<u64> {
+        self.user_service
+            .can_viewer_write_user(viewer_id, user_id.to_owned())
+            .await?;
+
+        let mut query = doc! { "user_id": user_id.to_string(), "read_at": null };
+        if let Some(notification_ids) = notification_ids {
+            let ids: Vec<String> = notification_ids.iter().map(|x| x.to_string()).collect();
+            query.insert("_id", doc! { "$in": ids });
+        }
+
+        let result = self
+            .notification_collection
+            .update_many(
+                query,
+                doc! { "$set": { "read_at": now_msec() as i64 } },
+                None,
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        Ok(result.modified_count as u64)
+    }
+
+    #[instrument(name = "NotificationService.purge_expired_notifications", skip_all)]
+    async fn purge_expired_notifications(&self) -> Result<u64> {
+        purge_older_than(
+            &self.notification_collection,
+            self.notification_config.retention_days,
+            doc! {},
+        )
+        .await
+    }
+}
+
+impl PaginationCursor for NotificationCursor {
+    fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn value(&self, _field: &str) -> Option<bson::Bson> {
+        None
+    }
+}
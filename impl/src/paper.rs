@@ -2,12 +2,20 @@ use std::ops::Deref;
 
 use async_trait::async_trait;
 use bson::{doc, Document};
+use futures::StreamExt;
 use lazy_static::lazy_static;
 use mongodb::options::{FindOneAndUpdateOptions, FindOneOptions, FindOptions};
-use paper::{paper::*, user::*, Error, OrderBy, Pagination, PaginationList, Result};
+use paper::{
+    paper::*, quota::QuotaService, user::*, Error, ErrorKind, OrderBy, Pagination, PaginationList,
+    Result,
+};
 use shaku::Provider;
+use tracing::instrument;
 
-use crate::utils::*;
+use crate::{
+    event::{Event, EventPublisher},
+    utils::*,
+};
 
 #[derive(Provider)]
 #[shaku(interface = PaperService)]
@@ -17,12 +25,23 @@ pub struct PaperServiceImpl {
 
     #[shaku(provide)]
     pub user_service: Box<dyn UserService>,
+
+    #[shaku(provide)]
+    pub quota_service: Box<dyn QuotaService>,
+
+    #[shaku(provide)]
+    pub event_publisher: Box<dyn EventPublisher>,
 }
 
 pub trait PaperCollection: Deref<Target = mongodb::Collection> + Send + Sync {}
 
 #[async_trait]
 impl PaperService for PaperServiceImpl {
+    #[instrument(
+        name = "PaperService.create_paper",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
     async fn create_paper(&self, viewer_id: UserId, user_id: UserId) -> Result<Paper> {
         self.user_service
             .can_viewer_write_user(viewer_id, user_id.to_owned())
@@ -40,14 +59,95 @@ impl PaperService for PaperServiceImpl {
             tags: None,
         };
 
-        self.paper_collection
-            .insert_one(to_doc(&paper)?, None)
-            .await
-            .map_err(|e| Error::unknown(e.to_string()))?;
+        let paper_doc = to_doc(&paper)?;
+
+        self.quota_service.reserve_paper(&paper.user_id).await?;
+
+        if let Err(e) = self.paper_collection.insert_one(paper_doc, None).await {
+            if let Err(release_error) = self.quota_service.release_paper(&paper.user_id).await {
+                log::error!("Failed to release paper {}: {}", paper.id, release_error);
+            }
+            return Err(Error::unknown(e.to_string()));
+        }
+
+        self.event_publisher
+            .publish(Event::Paper(PaperEvent {
+                kind: PaperEventKind::Created,
+                paper: paper.to_owned(),
+            }))
+            .await;
 
         Ok(paper.into())
     }
 
+    #[instrument(
+        name = "PaperService.update_paper",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id, paper_id = %paper_id)
+    )]
+    async fn update_paper(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        paper_id: PaperId,
+        input: UpdatePaperInput,
+    ) -> Result<Paper> {
+        let paper = self
+            .can_viewer_write_paper(viewer_id, user_id.to_owned(), paper_id.to_owned())
+            .await?;
+        if paper.deleted_at.is_some() {
+            return Err(Error::not_found("Paper not found".to_owned()));
+        }
+
+        let mut update_set = doc! {};
+
+        if let Some(title) = input.title {
+            update_set.insert("title", validate_title(&title)?);
+        }
+        if let Some(tags) = input.tags {
+            update_set.insert("tags", validate_tags(tags)?);
+        }
+
+        if update_set.is_empty() {
+            return Ok(paper);
+        }
+        update_set.insert("updated_at", now_msec() as i64);
+
+        let paper = self
+            .paper_collection
+            .find_one_and_update(
+                doc! {
+                    "_id": paper_id.to_string(),
+                    "user_id": user_id.to_string(),
+                    "deleted_at": null,
+                },
+                doc! { "$set": update_set },
+                FindOneAndUpdateOptions::builder()
+                    .return_document(mongodb::options::ReturnDocument::After)
+                    .projection(PAPER_PROJECTION.to_owned())
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|doc| from_doc::<Paper>(doc))
+            .transpose()?
+            .ok_or_else(|| Error::not_found("Paper not found".to_owned()))?;
+
+        self.event_publisher
+            .publish(Event::Paper(PaperEvent {
+                kind: PaperEventKind::Updated,
+                paper: paper.to_owned(),
+            }))
+            .await;
+
+        Ok(paper)
+    }
+
+    #[instrument(
+        name = "PaperService.delete_paper",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id, paper_id = %paper_id)
+    )]
     async fn delete_paper(
         &self,
         viewer_id: UserId,
@@ -57,20 +157,43 @@ impl PaperService for PaperServiceImpl {
         self.can_viewer_write_paper(viewer_id, user_id.to_owned(), paper_id.to_owned())
             .await?;
 
-        self.paper_collection
+        let paper = self
+            .paper_collection
             .find_one_and_update(
-                doc! { "_id": paper_id.to_string() },
+                doc! { "_id": paper_id.to_string(), "deleted_at": null },
                 doc! { "$set": { "deleted_at": now_msec() } },
                 FindOneAndUpdateOptions::builder()
-                    .projection(doc! {})
+                    .return_document(mongodb::options::ReturnDocument::After)
+                    .projection(PAPER_PROJECTION.to_owned())
                     .build(),
             )
             .await
-            .map_err(|e| Error::unknown(e.to_string()))?;
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|doc| from_doc::<Paper>(doc))
+            .transpose()?;
+
+        if let Some(paper) = paper {
+            // The paper is deleted whether or not its count is released.
+            if let Err(e) = self.quota_service.release_paper(&paper.user_id).await {
+                log::error!("Failed to release paper {}: {}", paper.id, e);
+            }
+
+            self.event_publisher
+                .publish(Event::Paper(PaperEvent {
+                    kind: PaperEventKind::Deleted,
+                    paper,
+                }))
+                .await;
+        }
 
         Ok(())
     }
 
+    #[instrument(
+        name = "PaperService.select_paper",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id, paper_id = %paper_id)
+    )]
     async fn select_paper(
         &self,
         viewer_id: UserId,
@@ -81,23 +204,74 @@ impl PaperService for PaperServiceImpl {
             .await
     }
 
+    #[instrument(name = "PaperService.select_papers", skip_all, fields(viewer_id = %viewer_id))]
+    async fn select_papers(
+        &self,
+        viewer_id: UserId,
+        paper_ids: Vec<PaperId>,
+    ) -> Result<Vec<Paper>> {
+        let ids: Vec<String> = paper_ids.iter().map(|x| x.to_string()).collect();
+
+        let papers = self
+            .paper_collection
+            .find(
+                doc! { "_id": { "$in": ids } },
+                FindOptions::builder()
+                    .projection(PAPER_PROJECTION.to_owned())
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| {
+                x.map_err(|e| Error::unknown(e.to_string()))
+                    .and_then(|x| from_doc::<Paper>(x))
+            })
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<Paper>>>()?;
+
+        let mut readable_user_ids: Vec<UserId> = vec![];
+        for user_id in papers.iter().map(|x| &x.user_id) {
+            if readable_user_ids.contains(user_id) {
+                continue;
+            }
+            match self
+                .user_service
+                .can_viewer_read_user(viewer_id.to_owned(), user_id.to_owned())
+                .await
+            {
+                Ok(_) => readable_user_ids.push(user_id.to_owned()),
+                Err(e) if e.kind == ErrorKind::Forbidden || e.kind == ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(papers
+            .into_iter()
+            .filter(|x| readable_user_ids.contains(&x.user_id))
+            .collect())
+    }
+
+    #[instrument(
+        name = "PaperService.select_paper_page_of_repository",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
     async fn select_paper_page_of_repository(
         &self,
         viewer_id: UserId,
         user_id: UserId,
-        pagination: Pagination<PaperId>,
+        pagination: Pagination<PaperCursor>,
         order_by: OrderBy<PaperOrderField>,
-        deleted: bool,
+        paper_filter: PaperFilter,
     ) -> Result<PaginationList<Paper>> {
         self.user_service
             .can_viewer_read_user(viewer_id, user_id.to_owned())
             .await?;
 
         let mut filter = doc! { "user_id": user_id.to_string() };
-        match deleted {
-            true => filter.insert("deleted_at", doc! { "$exists": true, "$ne": null }),
-            false => filter.insert("deleted_at", bson::Bson::Null),
-        };
+        filter.extend(paper_filter_doc(&paper_filter));
 
         mongodb_select_pagination(
             &self.paper_collection,
@@ -111,6 +285,11 @@ impl PaperService for PaperServiceImpl {
         .await
     }
 
+    #[instrument(
+        name = "PaperService.can_viewer_read_paper",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id, paper_id = %paper_id)
+    )]
     async fn can_viewer_read_paper(
         &self,
         viewer_id: UserId,
@@ -124,6 +303,11 @@ impl PaperService for PaperServiceImpl {
         self.find_paper(user_id, paper_id).await
     }
 
+    #[instrument(
+        name = "PaperService.can_viewer_write_paper",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id, paper_id = %paper_id)
+    )]
     async fn can_viewer_write_paper(
         &self,
         viewer_id: UserId,
@@ -154,7 +338,80 @@ impl PaperServiceImpl {
     }
 }
 
-fn paper_order_to_str(order_by: OrderBy<PaperOrderField>) -> OrderBy<&'static str> {
+/// Characters of a title.
+const MAX_TITLE_LENGTH: usize = 200;
+
+/// Tags of a paper.
+const MAX_TAGS: usize = 20;
+
+/// Characters of a tag.
+const MAX_TAG_LENGTH: usize = 50;
+
+fn validate_title(title: &str) -> Result<String> {
+    let title = title.trim();
+    if title.chars().count() > MAX_TITLE_LENGTH || title.chars().any(char::is_control) {
+        return Err(Error::invalid_input("Invalid paper title".to_owned()));
+    }
+    Ok(title.to_owned())
+}
+
+/// Trimmed and deduplicated, in the given order.
+fn validate_tags(tags: Vec<String>) -> Result<Vec<String>> {
+    let mut result: Vec<String> = vec![];
+    for tag in tags.iter().map(|x| x.trim()) {
+        if tag.is_empty()
+            || tag.chars().count() > MAX_TAG_LENGTH
+            || tag.chars().any(char::is_control)
+        {
+            return Err(Error::invalid_input(format!("Invalid paper tag {}", tag)));
+        }
+        if !result.iter().any(|x| x == tag) {
+            result.push(tag.to_owned());
+        }
+    }
+    if result.len() > MAX_TAGS {
+        return Err(Error::invalid_input(format!(
+            "A paper has at most {} tags",
+            MAX_TAGS
+        )));
+    }
+    Ok(result)
+}
+
+impl PaginationCursor for PaperCursor {
+    fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn value(&self, field: &str) -> Option<bson::Bson> {
+        match field {
+            "updated_at" => self.updated_at.map(|x| bson::Bson::from(x as i64)),
+            _ => None,
+        }
+    }
+}
+
+/// The query of the deleted or not deleted papers in the time ranges.
+pub(crate) fn paper_filter_doc(paper_filter: &PaperFilter) -> Document {
+    let mut filter = doc! {};
+    match paper_filter.deleted {
+        true => {
+            let mut deleted_at = doc! { "$exists": true, "$ne": null };
+            deleted_at.extend(time_range_filter(&paper_filter.deleted_at));
+            filter.insert("deleted_at", deleted_at)
+        }
+        false => filter.insert("deleted_at", bson::Bson::Null),
+    };
+    if !paper_filter.created_at.is_unbounded() {
+        filter.insert("created_at", time_range_filter(&paper_filter.created_at));
+    }
+    if !paper_filter.updated_at.is_unbounded() {
+        filter.insert("updated_at", time_range_filter(&paper_filter.updated_at));
+    }
+    filter
+}
+
+pub(crate) fn paper_order_to_str(order_by: OrderBy<PaperOrderField>) -> OrderBy<&'static str> {
     OrderBy {
         field: match order_by.field {
             PaperOrderField::Id => "_id",
@@ -165,7 +422,7 @@ fn paper_order_to_str(order_by: OrderBy<PaperOrderField>) -> OrderBy<&'static st
 }
 
 lazy_static! {
-    static ref PAPER_PROJECTION: Document = doc! {
+    pub(crate) static ref PAPER_PROJECTION: Document = doc! {
         "_id": 1,
         "user_id": 1,
         "created_at": 1,
@@ -175,3 +432,33 @@ lazy_static! {
         "tags": 1,
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuse_invalid_input() {
+        assert_eq!(validate_title(" Draft ").unwrap(), "Draft");
+        assert_eq!(
+            validate_title(&"a".repeat(MAX_TITLE_LENGTH + 1))
+                .unwrap_err()
+                .kind,
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            validate_tags(vec!["rust".to_owned(), " rust ".to_owned()]).unwrap(),
+            vec!["rust".to_owned()]
+        );
+        assert_eq!(
+            validate_tags(vec![" ".to_owned()]).unwrap_err().kind,
+            ErrorKind::InvalidInput
+        );
+        assert_eq!(
+            validate_tags((0..=MAX_TAGS).map(|x| x.to_string()).collect())
+                .unwrap_err()
+                .kind,
+            ErrorKind::InvalidInput
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use std::{collections::HashMap, ops::Deref, sync::Arc};
+
+use async_trait::async_trait;
+use bson::doc;
+use mongodb::options::{
+    FindOneAndUpdateOptions, FindOneOptions, ReturnDocument, UpdateModifications, UpdateOptions,
+};
+use paper::{quota::*, user::*, Error, Result};
+use shaku::{Component, Provider};
+use tracing::instrument;
+
+use crate::{
+    attachment::{attachment_storage, AttachmentCollection},
+    paper::PaperCollection,
+    utils::*,
+};
+
+#[derive(Provider)]
+#[shaku(interface = QuotaService)]
+pub struct QuotaServiceImpl {
+    #[shaku(provide)]
+    pub quota_collection: Box<dyn QuotaCollection>,
+
+    #[shaku(provide)]
+    pub paper_collection: Box<dyn PaperCollection>,
+
+    #[shaku(provide)]
+    pub attachment_collection: Box<dyn AttachmentCollection>,
+
+    #[shaku(provide)]
+    pub user_service: Box<dyn UserService>,
+
+    #[shaku(inject)]
+    pub quota_config: Arc<dyn QuotaConfigInterface>,
+}
+
+/// The API requests of the day of each user and the counters the limits are
+/// reserved from, `{ _id: user_id, day, api_requests, papers,
+/// attachment_storage }`.
+pub trait QuotaCollection: Deref<Target = mongodb::Collection> + Send + Sync {}
+
+#[derive(Debug, Clone, Component)]
+#[shaku(interface = QuotaConfigInterface)]
+pub struct QuotaConfig {
+    /// The plan of the users without one in `users`.
+    pub default_plan: String,
+
+    pub plans: HashMap<String, QuotaLimits>,
+
+    /// Overrides by user id.
+    pub users: HashMap<String, QuotaOverride>,
+}
+
+crate::shaku_deref_self_interface!(QuotaConfigInterface, QuotaConfig);
+
+const DAY_MSEC: u64 = 24 * 3600 * 1000;
+
+#[async_trait]
+impl QuotaService for QuotaServiceImpl {
+    #[instrument(
+        name = "QuotaService.select_usage",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
+    async fn select_usage(&self, viewer_id: UserId, user_id: UserId) -> Result<Usage> {
+        self.user_service
+            .can_viewer_write_user(viewer_id, user_id.to_owned())
+            .await?;
+
+        let (plan, limits) = self.select_limits(&user_id);
+
+        Ok(Usage {
+            plan,
+            limits,
+            papers: self.paper_count(&user_id).await?,
+            attachment_storage: attachment_storage(&self.attachment_collection, &user_id).await?,
+            api_requests_today: self.api_requests_today(&user_id).await?,
+        })
+    }
+
+    fn select_limits(&self, user_id: &UserId) -> (String, QuotaLimits) {
+        let user = self.quota_config.users.get(&user_id.to_string());
+        let plan = user
+            .and_then(|x| x.plan.to_owned())
+            .unwrap_or_else(|| self.quota_config.default_plan.to_owned());
+
+        // The config is validated to name existing plans, a missing one would
+        // leave the user unlimited.
+        let limits = self
+            .quota_config
+            .plans
+            .get(&plan)
+            .cloned()
+            .unwrap_or_default();
+
+        match user {
+            Some(user) => (plan, user.apply(&limits)),
+            None => (plan, limits),
+        }
+    }
+
+    #[instrument(name = "QuotaService.reserve_paper", skip_all, fields(user_id = %user_id))]
+    async fn reserve_paper(&self, user_id: &UserId) -> Result<()> {
+        let max_papers = self.select_limits(user_id).1.max_papers;
+        if !self.reserve(user_id, PAPERS, 1, max_papers).await? {
+            return Err(Error::quota_exceeded(format!(
+                "The plan allows at most {} papers",
+                max_papers
+            )));
+        }
+        Ok(())
+    }
+
+    #[instrument(name = "QuotaService.release_paper", skip_all, fields(user_id = %user_id))]
+    async fn release_paper(&self, user_id: &UserId) -> Result<()> {
+        add_to_counter(&self.quota_collection, user_id, PAPERS, -1).await
+    }
+
+    #[instrument(
+        name = "QuotaService.reserve_attachment_storage",
+        skip_all,
+        fields(user_id = %user_id, size = size)
+    )]
+    async fn reserve_attachment_storage(&self, user_id: &UserId, size: u64) -> Result<()> {
+        let max_storage = self.select_limits(user_id).1.max_attachment_storage;
+        if !self
+            .reserve(user_id, ATTACHMENT_STORAGE, size, max_storage)
+            .await?
+        {
+            return Err(Error::quota_exceeded(format!(
+                "The plan allows at most {} bytes of attachments",
+                max_storage
+            )));
+        }
+        Ok(())
+    }
+
+    #[instrument(
+        name = "QuotaService.release_attachment_storage",
+        skip_all,
+        fields(user_id = %user_id, size = size)
+    )]
+    async fn release_attachment_storage(&self, user_id: &UserId, size: u64) -> Result<()> {
+        add_to_counter(
+            &self.quota_collection,
+            user_id,
+            ATTACHMENT_STORAGE,
+            -(size as i64),
+        )
+        .await
+    }
+
+    #[instrument(name = "QuotaService.count_api_request", skip_all, fields(user_id = %user_id))]
+    async fn count_api_request(&self, user_id: &UserId) -> Result<u64> {
+        let day = (now_msec() / DAY_MSEC) as i64;
+
+        // The counter starts over on the first request of a day.
+        let doc = self
+            .quota_collection
+            .find_one_and_update(
+                doc! { "_id": user_id.to_string() },
+                UpdateModifications::Pipeline(vec![doc! {
+                    "$set": {
+                        "day": day,
+                        "api_requests": {
+                            "$cond": [
+                                { "$eq": ["$day", day] },
+                                { "$add": ["$api_requests", 1_i64] },
+                                1_i64,
+                            ],
+                        },
+                    },
+                }]),
+                FindOneAndUpdateOptions::builder()
+                    .upsert(true)
+                    .return_document(ReturnDocument::After)
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .ok_or_else(|| Error::unknown("The request was not counted".to_owned()))?;
+
+        let count = doc.get_i64("api_requests").unwrap_or_default() as u64;
+
+        let max_requests = self.select_limits(user_id).1.max_api_requests_per_day;
+        if max_requests > 0 && count > max_requests {
+            return Err(Error::quota_exceeded(format!(
+                "The plan allows at most {} requests per day",
+                max_requests
+            )));
+        }
+        Ok(count)
+    }
+}
+
+impl QuotaServiceImpl {
+    /// Add `amount` to `counter` unless it would go over `max`, 0 is
+    /// unlimited. A single conditional update, so concurrent reservations
+    /// can not both take the last of a limit. Returns whether it fit.
+    async fn reserve(
+        &self,
+        user_id: &UserId,
+        counter: &str,
+        amount: u64,
+        max: u64,
+    ) -> Result<bool> {
+        if max > 0 && amount > max {
+            return Ok(false);
+        }
+        self.seed_counter(user_id, counter).await?;
+
+        let mut filter = doc! { "_id": user_id.to_string() };
+        if max > 0 {
+            filter.insert(counter, doc! { "$lte": (max - amount) as i64 });
+        }
+
+        let result = self
+            .quota_collection
+            .update_one(filter, doc! { "$inc": { counter: amount as i64 } }, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        Ok(result.matched_count > 0)
+    }
+
+    /// Start `counter` from what the user already has, the first time it is
+    /// used.
+    async fn seed_counter(&self, user_id: &UserId, counter: &str) -> Result<()> {
+        let seeded = self
+            .quota_collection
+            .find_one(
+                doc! { "_id": user_id.to_string(), counter: { "$exists": true } },
+                FindOneOptions::builder()
+                    .projection(doc! { "_id": 1 })
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .is_some();
+        if seeded {
+            return Ok(());
+        }
+
+        let value = match counter {
+            PAPERS => self.paper_count(user_id).await?,
+            _ => attachment_storage(&self.attachment_collection, user_id).await?,
+        };
+
+        // Kept when a concurrent reservation has seeded it first.
+        self.quota_collection
+            .update_one(
+                doc! { "_id": user_id.to_string() },
+                UpdateModifications::Pipeline(vec![doc! {
+                    "$set": { counter: { "$ifNull": [format!("${}", counter), value as i64] } },
+                }]),
+                UpdateOptions::builder().upsert(true).build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        Ok(())
+    }
+
+    async fn paper_count(&self, user_id: &UserId) -> Result<u64> {
+        self.paper_collection
+            .count_documents(
+                doc! { "user_id": user_id.to_string(), "deleted_at": null },
+                None,
+            )
+            .await
+            .map(|x| x as u64)
+            .map_err(|e| Error::unknown(e.to_string()))
+    }
+
+    async fn api_requests_today(&self, user_id: &UserId) -> Result<u64> {
+        let day = (now_msec() / DAY_MSEC) as i64;
+
+        Ok(self
+            .quota_collection
+            .find_one(doc! { "_id": user_id.to_string(), "day": day }, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .and_then(|x| x.get_i64("api_requests").ok())
+            .unwrap_or_default() as u64)
+    }
+}
+
+/// The counters of the quota document.
+pub(crate) const PAPERS: &str = "papers";
+pub(crate) const ATTACHMENT_STORAGE: &str = "attachment_storage";
+
+/// Add `amount` to `counter` of the user, nothing until a reservation has
+/// seeded it.
+pub(crate) async fn add_to_counter(
+    collection: &mongodb::Collection,
+    user_id: &UserId,
+    counter: &str,
+    amount: i64,
+) -> Result<()> {
+    collection
+        .update_one(
+            doc! { "_id": user_id.to_string(), counter: { "$exists": true } },
+            doc! { "$inc": { counter: amount } },
+            None,
+        )
+        .await
+        .map_err(|e| Error::unknown(e.to_string()))?;
+    Ok(())
+}
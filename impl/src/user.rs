@@ -1,34 +1,88 @@
-use std::ops::Deref;
+use std::{ops::Deref, sync::Arc};
 
 use async_trait::async_trait;
 use bson::{doc, Document};
+use futures::StreamExt;
 use lazy_static::lazy_static;
-use mongodb::options::{FindOneAndUpdateOptions, FindOneOptions};
-use paper::{user::*, Error, Result};
-use shaku::Provider;
+use mongodb::options::{FindOneAndUpdateOptions, FindOneOptions, FindOptions};
+use paper::{user::*, Error, ErrorKind, Result};
+use shaku::{Component, Provider};
+use tracing::instrument;
 
-use crate::utils::*;
+use crate::{
+    admin::AdminConfigInterface,
+    event::{Event, EventPublisher},
+    utils::*,
+};
 
 #[derive(Provider)]
 #[shaku(interface = UserService)]
 pub struct UserServiceImpl {
     #[shaku(provide)]
-    user_collection: Box<dyn UserCollection>,
+    pub user_collection: Box<dyn UserCollection>,
+
+    #[shaku(inject)]
+    pub admin_config: Arc<dyn AdminConfigInterface>,
+
+    #[shaku(inject)]
+    pub account_config: Arc<dyn AccountConfigInterface>,
+
+    #[shaku(provide)]
+    pub event_publisher: Box<dyn EventPublisher>,
 }
 
 pub trait UserCollection: Deref<Target = mongodb::Collection> + Send + Sync {}
 
+#[derive(Debug, Clone, Component)]
+#[shaku(interface = AccountConfigInterface)]
+pub struct AccountConfig {
+    /// Seconds a deleted account can be restored before it is purged.
+    pub deletion_grace_period_sec: u64,
+}
+
+crate::shaku_deref_self_interface!(AccountConfigInterface, AccountConfig);
+
 #[async_trait]
 impl UserService for UserServiceImpl {
+    #[instrument(name = "UserService.select_user", skip_all)]
     async fn select_user(&self, identifier: UserIdentifier) -> Result<User> {
         self.find_user(identifier).await
     }
 
+    #[instrument(name = "UserService.select_account", skip_all)]
+    async fn select_account(&self, identifier: UserIdentifier) -> Result<User> {
+        self.find_account(identifier).await
+    }
+
+    #[instrument(name = "UserService.select_users", skip_all)]
+    async fn select_users(&self, user_ids: Vec<UserId>) -> Result<Vec<User>> {
+        let ids: Vec<String> = user_ids.iter().map(|x| x.to_string()).collect();
+
+        self.user_collection
+            .find(
+                doc! { "_id": { "$in": ids }, "deleted_at": null },
+                FindOptions::builder()
+                    .projection(USER_PROJECTION.to_owned())
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| {
+                x.map_err(|e| Error::unknown(e.to_string()))
+                    .and_then(|x| from_doc::<User>(x))
+            })
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    #[instrument(name = "UserService.create_user", skip_all)]
     async fn create_user(&self, input: CreateUserInput) -> Result<User> {
         let id = new_id().into();
         let created_at = now_msec();
 
-        let user = match input {
+        let mut user = match input {
             CreateUserInput::Github { github_user } => User {
                 id,
                 created_at,
@@ -40,6 +94,11 @@ impl UserService for UserServiceImpl {
                     .to_string(),
                 github_user: Some(github_user),
                 google_user: None,
+                role: Role::Member,
+                disabled: false,
+                deleted_at: None,
+                purge_at: None,
+                sessions_revoked_at: None,
             },
             CreateUserInput::Google { google_user } => User {
                 id,
@@ -52,9 +111,18 @@ impl UserService for UserServiceImpl {
                     .to_string(),
                 github_user: None,
                 google_user: Some(google_user),
+                role: Role::Member,
+                disabled: false,
+                deleted_at: None,
+                purge_at: None,
+                sessions_revoked_at: None,
             },
         };
 
+        if self.admin_config.is_bootstrap_admin(&user) {
+            user.role = Role::Admin;
+        }
+
         self.user_collection
             .insert_one(to_doc(&user)?, None)
             .await
@@ -63,6 +131,11 @@ impl UserService for UserServiceImpl {
         Ok(user)
     }
 
+    #[instrument(
+        name = "UserService.update_user",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
     async fn update_user(
         &self,
         viewer_id: UserId,
@@ -88,7 +161,8 @@ impl UserService for UserServiceImpl {
             .projection(USER_PROJECTION.to_owned())
             .build();
 
-        self.user_collection
+        let user = self
+            .user_collection
             .find_one_and_update(
                 doc! { "_id": user_id.to_string() },
                 doc! { "$set": update_set },
@@ -98,12 +172,28 @@ impl UserService for UserServiceImpl {
             .map_err(|e| Error::unknown(e.to_string()))?
             .map(|x| from_doc::<User>(x))
             .transpose()?
-            .ok_or_else(|| Error::unknown("User not found".to_owned()))
+            .ok_or_else(|| Error::unknown("User not found".to_owned()))?;
+
+        self.event_publisher
+            .publish(Event::User(UserEvent {
+                kind: UserEventKind::Updated,
+                user: user.to_owned(),
+            }))
+            .await;
+
+        Ok(user)
     }
 
+    #[instrument(
+        name = "UserService.can_viewer_read_user",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
     async fn can_viewer_read_user(&self, viewer_id: UserId, user_id: UserId) -> Result<User> {
         let user = self.find_user(UserIdentifier::Id(user_id)).await?;
 
+        // Moderators only see the site administration, not the papers,
+        // comments and subscriptions of others.
         if viewer_id == user.id {
             return Ok(user);
         }
@@ -113,6 +203,11 @@ impl UserService for UserServiceImpl {
         ));
     }
 
+    #[instrument(
+        name = "UserService.can_viewer_write_user",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
     async fn can_viewer_write_user(&self, viewer_id: UserId, user_id: UserId) -> Result<User> {
         let user = self.find_user(UserIdentifier::Id(user_id)).await?;
 
@@ -125,10 +220,15 @@ impl UserService for UserServiceImpl {
         ));
     }
 
+    #[instrument(
+        name = "UserService.can_viewer_administer_user",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
     async fn can_viewer_administer_user(&self, viewer_id: UserId, user_id: UserId) -> Result<User> {
         let user = self.find_user(UserIdentifier::Id(user_id)).await?;
 
-        if viewer_id == user.id {
+        if viewer_id == user.id || self.viewer_role(viewer_id).await? >= Role::Admin {
             return Ok(user);
         }
 
@@ -136,10 +236,111 @@ impl UserService for UserServiceImpl {
             "You are not allowed to administer this user".to_owned(),
         ));
     }
+
+    #[instrument(
+        name = "UserService.can_viewer_act_as",
+        skip_all,
+        fields(viewer_id = %viewer_id, role = ?role)
+    )]
+    async fn can_viewer_act_as(&self, viewer_id: UserId, role: Role) -> Result<User> {
+        let viewer = self.find_user(UserIdentifier::Id(viewer_id)).await?;
+
+        if viewer.role >= role {
+            return Ok(viewer);
+        }
+
+        Err(Error::forbidden(format!(
+            "Only a {:?} is allowed to do this",
+            role
+        )))
+    }
+
+    #[instrument(
+        name = "UserService.delete_user",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
+    async fn delete_user(&self, viewer_id: UserId, user_id: UserId) -> Result<User> {
+        self.can_viewer_administer_user(viewer_id, user_id.to_owned())
+            .await?;
+
+        let now = now_msec();
+        let purge_at = now + self.account_config.deletion_grace_period_sec * 1000;
+
+        let user = self
+            .user_collection
+            .find_one_and_update(
+                doc! { "_id": user_id.to_string(), "deleted_at": null },
+                doc! {
+                    "$set": {
+                        "deleted_at": now as i64,
+                        "purge_at": purge_at as i64,
+                        "sessions_revoked_at": now as i64,
+                    }
+                },
+                FindOneAndUpdateOptions::builder()
+                    .return_document(mongodb::options::ReturnDocument::After)
+                    .projection(USER_PROJECTION.to_owned())
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| from_doc::<User>(x))
+            .transpose()?
+            .ok_or_else(|| Error::not_found("User not found".to_owned()))?;
+
+        // Ends the subscriptions of the user.
+        self.event_publisher
+            .publish(Event::User(UserEvent {
+                kind: UserEventKind::Updated,
+                user: user.to_owned(),
+            }))
+            .await;
+
+        Ok(user)
+    }
+
+    #[instrument(name = "UserService.restore_user", skip_all, fields(user_id = %user_id))]
+    async fn restore_user(&self, user_id: UserId) -> Result<User> {
+        self.user_collection
+            .find_one_and_update(
+                doc! {
+                    "_id": user_id.to_string(),
+                    "deleted_at": { "$ne": null },
+                    "purge_at": { "$gt": now_msec() as i64 },
+                },
+                doc! { "$unset": { "deleted_at": "", "purge_at": "" } },
+                FindOneAndUpdateOptions::builder()
+                    .return_document(mongodb::options::ReturnDocument::After)
+                    .projection(USER_PROJECTION.to_owned())
+                    .build(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| from_doc::<User>(x))
+            .transpose()?
+            .ok_or_else(|| Error::not_found("Deleted user not found".to_owned()))
+    }
 }
 
 impl UserServiceImpl {
+    async fn viewer_role(&self, viewer_id: UserId) -> Result<Role> {
+        match self.find_user(UserIdentifier::Id(viewer_id)).await {
+            Ok(viewer) => Ok(viewer.role),
+            Err(e) if e.kind == ErrorKind::NotFound => Ok(Role::Member),
+            Err(e) => Err(e),
+        }
+    }
+
     async fn find_user(&self, identifier: UserIdentifier) -> Result<User> {
+        match self.find_account(identifier).await? {
+            user if user.deleted_at.is_some() => Err(Error::not_found("User not found".to_owned())),
+            user => Ok(user),
+        }
+    }
+
+    /// The user, deleted or not.
+    async fn find_account(&self, identifier: UserIdentifier) -> Result<User> {
         let query = match &identifier {
             UserIdentifier::Id(id) => doc! { "_id": id.to_string() },
             UserIdentifier::Name(name) => doc! { "name": name },
@@ -169,11 +370,81 @@ impl UserServiceImpl {
 }
 
 lazy_static! {
-    static ref USER_PROJECTION: Document = doc! {
+    pub(crate) static ref USER_PROJECTION: Document = doc! {
         "_id": 1,
         "created_at": 1,
         "name": 1,
         "github_user": 1,
         "google_user": 1,
+        "role": 1,
+        "disabled": 1,
+        "deleted_at": 1,
+        "purge_at": 1,
+        "sessions_revoked_at": 1,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use paper::{paper::PaperService, quota::QuotaLimits};
+
+    use super::*;
+    use crate::{
+        paper::PaperServiceImpl,
+        quota::{QuotaConfig, QuotaServiceImpl},
+        test_utils::*,
     };
+
+    #[tokio::test]
+    #[ignore]
+    async fn moderator_can_not_read_private_papers() {
+        let db = test_database().await;
+        let paper_service = PaperServiceImpl {
+            paper_collection: Box::new(TestCollection(db.collection("papers"))),
+            user_service: Box::new(user_service(&db)),
+            quota_service: Box::new(QuotaServiceImpl {
+                quota_collection: Box::new(TestCollection(db.collection("quotas"))),
+                paper_collection: Box::new(TestCollection(db.collection("papers"))),
+                attachment_collection: Box::new(TestCollection(db.collection("attachments"))),
+                user_service: Box::new(user_service(&db)),
+                quota_config: Arc::new(QuotaConfig {
+                    default_plan: "free".to_owned(),
+                    plans: vec![("free".to_owned(), QuotaLimits::default())]
+                        .into_iter()
+                        .collect(),
+                    users: HashMap::new(),
+                }),
+            }),
+            event_publisher: Box::new(NoEvents),
+        };
+
+        let member = create_user(&db, "member", Role::Member).await;
+        let moderator = create_user(&db, "moderator", Role::Moderator).await;
+        let paper = paper_service
+            .create_paper(member.id.to_owned(), member.id.to_owned())
+            .await
+            .unwrap();
+
+        let read = paper_service
+            .can_viewer_read_paper(
+                member.id.to_owned(),
+                member.id.to_owned(),
+                paper.id.to_owned(),
+            )
+            .await;
+        assert_eq!(read.unwrap().id, paper.id);
+
+        let read = paper_service
+            .can_viewer_read_paper(
+                moderator.id.to_owned(),
+                member.id.to_owned(),
+                paper.id.to_owned(),
+            )
+            .await;
+        assert_eq!(read.unwrap_err().kind, ErrorKind::Forbidden);
+
+        db.drop(None).await.unwrap();
+    }
 }
@@ -0,0 +1,868 @@
+use std::{net::IpAddr, ops::Deref, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use bson::doc;
+use futures::{future, StreamExt};
+use hmac::{Hmac, Mac, NewMac};
+use hyper::client::{connect::dns::Name, HttpConnector};
+use hyper_tls::HttpsConnector;
+use mongodb::options::{FindOneAndUpdateOptions, FindOneOptions, FindOptions};
+use paper::{
+    paper::*, user::*, webhook::*, Error, ErrorKind, OrderBy, OrderDirection, Pagination,
+    PaginationList, Result,
+};
+use sha2::Sha256;
+use shaku::{Component, Provider};
+use tracing::instrument;
+
+use crate::{event::Event, utils::*};
+
+#[derive(Provider)]
+#[shaku(interface = WebhookService)]
+pub struct WebhookServiceImpl {
+    #[shaku(provide)]
+    pub webhook_collection: Box<dyn WebhookCollection>,
+
+    #[shaku(provide)]
+    pub webhook_delivery_collection: Box<dyn WebhookDeliveryCollection>,
+
+    #[shaku(provide)]
+    pub user_service: Box<dyn UserService>,
+
+    #[shaku(inject)]
+    pub webhook_config: Arc<dyn WebhookConfigInterface>,
+}
+
+pub trait WebhookCollection: Deref<Target = mongodb::Collection> + Send + Sync {}
+
+pub trait WebhookDeliveryCollection: Deref<Target = mongodb::Collection> + Send + Sync {}
+
+#[derive(Debug, Clone, Component)]
+#[shaku(interface = WebhookConfigInterface)]
+pub struct WebhookConfig {
+    /// Webhooks a user can register.
+    pub max_per_user: u64,
+
+    /// Attempts of a delivery before it is given up.
+    pub max_attempts: u32,
+
+    /// Seconds before the first retry, doubled after each failed attempt.
+    pub retry_base_sec: u64,
+
+    /// Seconds an endpoint is given to respond.
+    pub timeout_sec: u64,
+
+    /// Days the deliveries are kept, 0 keeps them forever.
+    pub delivery_retention_days: u64,
+
+    /// Hosts which may resolve to an internal address.
+    pub allowed_hosts: Vec<String>,
+}
+
+crate::shaku_deref_self_interface!(WebhookConfigInterface, WebhookConfig);
+
+/// Deliveries claimed by a `deliver_due` call.
+const DELIVERY_BATCH: usize = 20;
+
+/// A claimed delivery is attempted again after the timeout and this margin,
+/// when the server attempting it has stopped before recording the outcome.
+const CLAIM_MARGIN_MSEC: u64 = 60 * 1000;
+
+#[async_trait]
+impl WebhookService for WebhookServiceImpl {
+    #[instrument(
+        name = "WebhookService.create_webhook",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
+    async fn create_webhook(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        input: CreateWebhookInput,
+    ) -> Result<Webhook> {
+        self.user_service
+            .can_viewer_administer_user(viewer_id, user_id.to_owned())
+            .await?;
+
+        validate_url(&input.url)?;
+        check_destination(&input.url, &self.webhook_config.allowed_hosts).await?;
+        if input.secret.is_empty() {
+            return Err(Error::invalid_input("A secret is required".to_owned()));
+        }
+        let mut events: Vec<WebhookEventKind> = vec![];
+        for event in input.events {
+            if !events.contains(&event) {
+                events.push(event);
+            }
+        }
+        if events.is_empty() {
+            return Err(Error::invalid_input(
+                "At least one event is required".to_owned(),
+            ));
+        }
+
+        let count = self
+            .webhook_collection
+            .count_documents(doc! { "user_id": user_id.to_string() }, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))? as u64;
+        if count >= self.webhook_config.max_per_user {
+            return Err(Error::forbidden(format!(
+                "A user can register at most {} webhooks",
+                self.webhook_config.max_per_user
+            )));
+        }
+
+        let webhook = Webhook {
+            id: new_id().into(),
+            user_id,
+            url: input.url,
+            events,
+            secret: input.secret,
+            created_at: now_msec(),
+        };
+
+        self.webhook_collection
+            .insert_one(to_doc(&webhook)?, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        Ok(webhook)
+    }
+
+    #[instrument(
+        name = "WebhookService.delete_webhook",
+        skip_all,
+        fields(viewer_id = %viewer_id, webhook_id = %webhook_id)
+    )]
+    async fn delete_webhook(&self, viewer_id: UserId, webhook_id: WebhookId) -> Result<Webhook> {
+        let webhook = self.find_webhook(&webhook_id).await?;
+
+        self.user_service
+            .can_viewer_administer_user(viewer_id, webhook.user_id.to_owned())
+            .await?;
+
+        self.webhook_collection
+            .delete_one(doc! { "_id": webhook_id.to_string() }, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+        self.webhook_delivery_collection
+            .delete_many(doc! { "webhook_id": webhook_id.to_string() }, None)
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        Ok(webhook)
+    }
+
+    #[instrument(
+        name = "WebhookService.select_webhooks",
+        skip_all,
+        fields(viewer_id = %viewer_id, user_id = %user_id)
+    )]
+    async fn select_webhooks(&self, viewer_id: UserId, user_id: UserId) -> Result<Vec<Webhook>> {
+        self.user_service
+            .can_viewer_administer_user(viewer_id, user_id.to_owned())
+            .await?;
+
+        find_webhooks(
+            &self.webhook_collection,
+            doc! { "user_id": user_id.to_string() },
+        )
+        .await
+    }
+
+    #[instrument(
+        name = "WebhookService.select_delivery_page",
+        skip_all,
+        fields(viewer_id = %viewer_id, webhook_id = %webhook_id)
+    )]
+    async fn select_delivery_page(
+        &self,
+        viewer_id: UserId,
+        webhook_id: WebhookId,
+        pagination: Pagination<WebhookDeliveryCursor>,
+    ) -> Result<PaginationList<WebhookDelivery>> {
+        let webhook = self.find_webhook(&webhook_id).await?;
+
+        self.user_service
+            .can_viewer_administer_user(viewer_id, webhook.user_id)
+            .await?;
+
+        mongodb_select_pagination(
+            &self.webhook_delivery_collection,
+            pagination,
+            doc! { "webhook_id": webhook_id.to_string() },
+            OrderBy {
+                field: "_id",
+                direction: OrderDirection::Desc,
+            },
+            FindOptions::default(),
+        )
+        .await
+    }
+
+    #[instrument(name = "WebhookService.deliver_due", skip_all)]
+    async fn deliver_due(&self) -> Result<u64> {
+        let claim_msec = self.webhook_config.timeout_sec * 1000 + CLAIM_MARGIN_MSEC;
+
+        // Claim the deliveries by moving their next attempt past the timeout,
+        // so that each attempt is made by one server.
+        let mut deliveries = vec![];
+        while deliveries.len() < DELIVERY_BATCH {
+            let now = now_msec();
+            let delivery = self
+                .webhook_delivery_collection
+                .find_one_and_update(
+                    doc! { "status": "pending", "next_attempt_at": { "$lte": now as i64 } },
+                    doc! { "$set": { "next_attempt_at": (now + claim_msec) as i64 } },
+                    FindOneAndUpdateOptions::builder()
+                        .sort(doc! { "next_attempt_at": 1 })
+                        .build(),
+                )
+                .await
+                .map_err(|e| Error::unknown(e.to_string()))?
+                .map(|x| from_doc::<WebhookDelivery>(x))
+                .transpose()?;
+
+            match delivery {
+                Some(delivery) => deliveries.push(delivery),
+                None => break,
+            }
+        }
+
+        let count = deliveries.len() as u64;
+
+        futures::future::join_all(deliveries.into_iter().map(|x| self.attempt(x)))
+            .await
+            .into_iter()
+            .collect::<Result<Vec<()>>>()?;
+
+        Ok(count)
+    }
+
+    #[instrument(name = "WebhookService.purge_expired_deliveries", skip_all)]
+    async fn purge_expired_deliveries(&self) -> Result<u64> {
+        // The pending deliveries are still to be attempted.
+        purge_older_than(
+            &self.webhook_delivery_collection,
+            self.webhook_config.delivery_retention_days,
+            doc! { "status": { "$ne": "pending" } },
+        )
+        .await
+    }
+}
+
+impl WebhookServiceImpl {
+    async fn find_webhook(&self, webhook_id: &WebhookId) -> Result<Webhook> {
+        self.webhook_collection
+            .find_one(
+                doc! { "_id": webhook_id.to_string() },
+                FindOneOptions::default(),
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?
+            .map(|x| from_doc::<Webhook>(x))
+            .ok_or_else(|| Error::not_found("Webhook not found".to_owned()))?
+    }
+
+    /// Post a claimed delivery and record the outcome, a failed attempt is
+    /// retried with an exponential backoff until `max_attempts`.
+    async fn attempt(&self, delivery: WebhookDelivery) -> Result<()> {
+        let webhook = match self.find_webhook(&delivery.webhook_id).await {
+            Ok(webhook) => Some(webhook),
+            Err(e) if e.kind == ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+
+        let outcome = match &webhook {
+            Some(webhook) => {
+                post(
+                    webhook,
+                    &delivery,
+                    Duration::from_secs(self.webhook_config.timeout_sec),
+                    &self.webhook_config.allowed_hosts,
+                )
+                .await
+            }
+            None => Err("The webhook has been deleted".to_owned()),
+        };
+
+        let now = now_msec();
+        let attempts = delivery.attempts + 1;
+
+        let mut update_set = doc! {
+            "attempts": attempts,
+            "last_attempt_at": now as i64,
+        };
+        match outcome {
+            Ok(status) if (200..300).contains(&status) => {
+                update_set.insert("status", "succeeded");
+                update_set.insert("next_attempt_at", bson::Bson::Null);
+                update_set.insert("response_status", status as i32);
+                update_set.insert("error", bson::Bson::Null);
+            }
+            outcome => {
+                let (status, error) = match outcome {
+                    Ok(status) => (Some(status), format!("Responded with HTTP {}", status)),
+                    Err(e) => (None, e),
+                };
+                log::warn!(
+                    "Webhook delivery {} attempt {} failed: {}",
+                    delivery.id,
+                    attempts,
+                    error
+                );
+                update_set.insert("response_status", status.map(|x| x as i32));
+                update_set.insert("error", error);
+
+                match webhook.is_none() || attempts >= self.webhook_config.max_attempts {
+                    true => {
+                        update_set.insert("status", "failed");
+                        update_set.insert("next_attempt_at", bson::Bson::Null);
+                    }
+                    false => {
+                        let backoff_msec = (self.webhook_config.retry_base_sec * 1000)
+                            .saturating_mul(2u64.saturating_pow(attempts - 1));
+                        update_set
+                            .insert("next_attempt_at", now.saturating_add(backoff_msec) as i64);
+                    }
+                }
+            }
+        }
+
+        self.webhook_delivery_collection
+            .update_one(
+                doc! { "_id": delivery.id.to_string() },
+                doc! { "$set": update_set },
+                None,
+            )
+            .await
+            .map_err(|e| Error::unknown(e.to_string()))?;
+
+        Ok(())
+    }
+}
+
+impl PaginationCursor for WebhookDeliveryCursor {
+    fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    fn value(&self, _field: &str) -> Option<bson::Bson> {
+        None
+    }
+}
+
+/// Queue a delivery of the event to each webhook of its user subscribed to
+/// its kind, returns the number of deliveries queued.
+#[instrument(name = "webhook.enqueue_deliveries", skip_all, fields(kind = ?event.kind))]
+pub(crate) async fn enqueue_deliveries(
+    webhook_collection: &mongodb::Collection,
+    webhook_delivery_collection: &mongodb::Collection,
+    event: WebhookEvent,
+) -> Result<u64> {
+    let webhooks = find_webhooks(
+        webhook_collection,
+        doc! {
+            "user_id": event.user_id.to_string(),
+            "events": event.kind.name(),
+        },
+    )
+    .await?;
+
+    let now = now_msec();
+
+    let deliveries = webhooks
+        .into_iter()
+        .map(|webhook| {
+            let id: WebhookDeliveryId = new_id().into();
+            let payload = serde_json::json!({
+                "id": id,
+                "event": event.kind,
+                "created_at": now,
+                "data": event.data,
+            });
+
+            to_doc(&WebhookDelivery {
+                id,
+                webhook_id: webhook.id,
+                user_id: webhook.user_id,
+                event: event.kind,
+                payload: payload.to_string(),
+                status: WebhookDeliveryStatus::Pending,
+                attempts: 0,
+                created_at: now,
+                next_attempt_at: Some(now),
+                last_attempt_at: None,
+                response_status: None,
+                error: None,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    if deliveries.is_empty() {
+        return Ok(0);
+    }
+
+    let result = webhook_delivery_collection
+        .insert_many(deliveries, None)
+        .await
+        .map_err(|e| Error::unknown(e.to_string()))?;
+
+    Ok(result.inserted_ids.len() as u64)
+}
+
+async fn find_webhooks(
+    collection: &mongodb::Collection,
+    filter: bson::Document,
+) -> Result<Vec<Webhook>> {
+    collection
+        .find(
+            filter,
+            FindOptions::builder()
+                .sort(doc! { "created_at": 1 })
+                .build(),
+        )
+        .await
+        .map_err(|e| Error::unknown(e.to_string()))?
+        .map(|x| {
+            x.map_err(|e| Error::unknown(e.to_string()))
+                .and_then(|x| from_doc::<Webhook>(x))
+        })
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect()
+}
+
+/// Post a delivery to its webhook, returns the HTTP status of the response or
+/// why there is none. The destination is checked again, the host may resolve
+/// to another address since the webhook has been registered, and the request
+/// is sent to the addresses checked. Redirects are not followed, a 3xx fails
+/// the attempt like any other status out of 2xx.
+async fn post(
+    webhook: &Webhook,
+    delivery: &WebhookDelivery,
+    timeout: Duration,
+    allowed_hosts: &[String],
+) -> std::result::Result<u16, String> {
+    use hyper::*;
+
+    let addrs = check_destination(&webhook.url, allowed_hosts)
+        .await
+        .map_err(|e| e.message.unwrap_or_default())?;
+
+    let client = pinned_client(addrs);
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(&webhook.url)
+        .header("Content-Type", "application/json")
+        .header("User-Agent", "paper-webhook")
+        .header("X-Paper-Event", delivery.event.name())
+        .header("X-Paper-Delivery", delivery.id.to_string())
+        .header(
+            "X-Paper-Signature",
+            signature(&webhook.secret, &delivery.payload),
+        )
+        .body(Body::from(delivery.payload.to_owned()))
+        .map_err(|e| e.to_string())?;
+
+    match tokio::time::timeout(timeout, client.request(request)).await {
+        Ok(Ok(response)) => Ok(response.status().as_u16()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("No response within {} seconds", timeout.as_secs())),
+    }
+}
+
+/// The webhook event of an event of the bus, `None` when webhooks are not
+/// told about it.
+pub fn webhook_event(event: &Event) -> Option<WebhookEvent> {
+    match event {
+        Event::Paper(PaperEvent { kind, paper }) => Some(WebhookEvent {
+            kind: match kind {
+                PaperEventKind::Created => WebhookEventKind::PaperCreated,
+                PaperEventKind::Updated => WebhookEventKind::PaperUpdated,
+                PaperEventKind::Deleted => WebhookEventKind::PaperDeleted,
+            },
+            user_id: paper.user_id.to_owned(),
+            data: serde_json::to_value(paper).ok()?,
+        }),
+        Event::User(UserEvent {
+            kind: UserEventKind::Updated,
+            user,
+        }) => Some(WebhookEvent {
+            kind: WebhookEventKind::UserUpdated,
+            user_id: user.id.to_owned(),
+            // The public profile, the identities and account state are not
+            // sent to third parties.
+            data: serde_json::json!({
+                "id": user.id,
+                "name": user.name,
+                "created_at": user.created_at,
+            }),
+        }),
+        // The inbox is not sent to third parties.
+        Event::Notification(_) => None,
+    }
+}
+
+/// `sha256=` and the hex HMAC-SHA256 of `payload` keyed by `secret`, receivers
+/// compute it with their copy of the secret to authenticate a delivery.
+pub fn signature(secret: &str, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(payload.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn validate_url(url: &str) -> Result<()> {
+    let uri: hyper::Uri = url
+        .parse()
+        .map_err(|_| Error::invalid_input("Invalid webhook url".to_owned()))?;
+
+    match (uri.scheme_str(), uri.host()) {
+        (Some("http"), Some(_)) | (Some("https"), Some(_)) => Ok(()),
+        _ => Err(Error::invalid_input(
+            "The webhook url must be an http or https URL".to_owned(),
+        )),
+    }
+}
+
+/// A client connecting only to `addrs`, whatever the host of a request
+/// resolves to by then. The host is still the one of the Host header and of
+/// the TLS server name.
+fn pinned_client(
+    addrs: Vec<IpAddr>,
+) -> hyper::Client<HttpsConnector<HttpConnector<PinnedResolver>>, hyper::Body> {
+    let mut http = HttpConnector::new_with_resolver(PinnedResolver(addrs));
+    http.enforce_http(false);
+
+    hyper::Client::builder().build(HttpsConnector::new_with_connector(http))
+}
+
+/// Resolves any name to the addresses a destination has been checked with,
+/// a host can not resolve to an internal address once checked.
+#[derive(Clone)]
+struct PinnedResolver(Vec<IpAddr>);
+
+impl tower_service::Service<Name> for PinnedResolver {
+    type Response = std::vec::IntoIter<IpAddr>;
+    type Error = std::io::Error;
+    type Future = future::Ready<std::result::Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        _cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::result::Result<(), Self::Error>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _name: Name) -> Self::Future {
+        future::ready(Ok(self.0.clone().into_iter()))
+    }
+}
+
+/// Refuse a URL whose host resolves to a loopback, private, link-local or
+/// otherwise internal address, unless the host is in `allowed_hosts`. Returns
+/// the addresses the host resolves to, the request is to be sent to them.
+async fn check_destination(url: &str, allowed_hosts: &[String]) -> Result<Vec<IpAddr>> {
+    let uri: hyper::Uri = url
+        .parse()
+        .map_err(|_| Error::invalid_input("Invalid webhook url".to_owned()))?;
+    let host = uri
+        .host()
+        .ok_or_else(|| Error::invalid_input("Invalid webhook url".to_owned()))?
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+
+    let port = uri.port_u16().unwrap_or(match uri.scheme_str() {
+        Some("https") => 443,
+        _ => 80,
+    });
+    let addrs: Vec<IpAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| Error::unknown(format!("Failed to resolve {}: {}", host, e)))?
+        .map(|x| x.ip())
+        .collect();
+    if addrs.is_empty() {
+        return Err(Error::unknown(format!("Failed to resolve {}", host)));
+    }
+
+    if allowed_hosts.iter().any(|x| x.eq_ignore_ascii_case(host)) {
+        return Ok(addrs);
+    }
+
+    match addrs.iter().find(|x| is_internal(**x)) {
+        Some(addr) => Err(Error::forbidden(format!(
+            "{} resolves to the internal address {}",
+            host, addr
+        ))),
+        None => Ok(addrs),
+    }
+}
+
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // "This network", 0.0.0.0/8, and the shared address space of
+                // carrier-grade NAT, 100.64.0.0/10.
+                || a == 0
+                || (a == 100 && b & 0xc0 == 64)
+        }
+        IpAddr::V6(ip) => {
+            let segment = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local, fc00::/7, and link-local, fe80::/10.
+                || segment & 0xfe00 == 0xfc00
+                || segment & 0xffc0 == 0xfe80
+                // IPv4-mapped and IPv4-compatible addresses.
+                || ip.to_ipv4().map_or(false, |x| is_internal(IpAddr::V4(x)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    /// A local endpoint answering a single request with `status`, returns its
+    /// URL and the request it has received.
+    fn stand_in(status: u16) -> (String, thread::JoinHandle<String>) {
+        stand_in_with(status, "")
+    }
+
+    /// A stand-in also answering with `headers`, each ending with `\r\n`.
+    fn stand_in_with(status: u16, headers: &str) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            // The head, then as much of the body as its Content-Length.
+            let mut request = vec![];
+            let mut buf = [0; 4096];
+            loop {
+                let size = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..size]);
+
+                let text = String::from_utf8_lossy(&request).into_owned();
+                if let Some(head) = text.find("\r\n\r\n") {
+                    let length = text[..head]
+                        .lines()
+                        .find_map(|x| {
+                            x.to_ascii_lowercase()
+                                .strip_prefix("content-length:")
+                                .map(|x| x.trim().parse::<usize>().unwrap())
+                        })
+                        .unwrap_or(0);
+                    if request.len() >= head + 4 + length {
+                        break;
+                    }
+                }
+                if size == 0 {
+                    break;
+                }
+            }
+
+            write!(
+                stream,
+                "HTTP/1.1 {} Stand-in\r\n{}Content-Length: 0\r\nConnection: close\r\n\r\n",
+                status, headers
+            )
+            .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        (url, handle)
+    }
+
+    fn delivery(url: &str) -> (Webhook, WebhookDelivery) {
+        let webhook = Webhook {
+            id: new_id().into(),
+            user_id: new_id().into(),
+            url: url.to_owned(),
+            events: vec![WebhookEventKind::PaperCreated],
+            secret: "secret".to_owned(),
+            created_at: now_msec(),
+        };
+        let delivery = WebhookDelivery {
+            id: new_id().into(),
+            webhook_id: webhook.id.to_owned(),
+            user_id: webhook.user_id.to_owned(),
+            event: WebhookEventKind::PaperCreated,
+            payload: r#"{"event":"paper.created"}"#.to_owned(),
+            status: WebhookDeliveryStatus::Pending,
+            attempts: 0,
+            created_at: now_msec(),
+            next_attempt_at: Some(now_msec()),
+            last_attempt_at: None,
+            response_status: None,
+            error: None,
+        };
+        (webhook, delivery)
+    }
+
+    #[tokio::test]
+    async fn post_signed_delivery() {
+        let (url, stand_in) = stand_in(204);
+        let (webhook, delivery) = delivery(&url);
+
+        let status = post(
+            &webhook,
+            &delivery,
+            Duration::from_secs(5),
+            &["127.0.0.1".to_owned()],
+        )
+        .await;
+        assert_eq!(status, Ok(204));
+
+        let request = stand_in.join().unwrap();
+        let head = request.to_ascii_lowercase();
+        assert!(request.starts_with("POST /hook HTTP/1.1\r\n"));
+        assert!(head.contains("x-paper-event: paper.created\r\n"));
+        assert!(
+            head.contains(&format!("x-paper-delivery: {}\r\n", delivery.id).to_ascii_lowercase())
+        );
+        assert!(head.contains(&format!(
+            "x-paper-signature: {}\r\n",
+            signature("secret", &delivery.payload)
+        )));
+        assert!(request.ends_with(&delivery.payload));
+    }
+
+    #[tokio::test]
+    async fn post_failed_delivery() {
+        let (url, stand_in) = stand_in(500);
+        let (webhook, delivery) = delivery(&url);
+
+        let status = post(
+            &webhook,
+            &delivery,
+            Duration::from_secs(5),
+            &["127.0.0.1".to_owned()],
+        )
+        .await;
+        assert_eq!(status, Ok(500));
+        stand_in.join().unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuse_internal_destination() {
+        // Nothing listens, the delivery is refused before connecting.
+        let (webhook, delivery) = delivery("http://127.0.0.1:9/hook");
+
+        let error = post(&webhook, &delivery, Duration::from_secs(5), &[])
+            .await
+            .unwrap_err();
+        assert!(error.contains("internal address"), "{}", error);
+    }
+
+    #[tokio::test]
+    async fn post_to_checked_addresses() {
+        // The host would not resolve, the connection goes to the address it
+        // has been checked with and the host is kept in the request.
+        let (url, stand_in) = stand_in(204);
+        let url = url.replace("127.0.0.1", "rebinding.invalid");
+
+        let request = hyper::Request::post(&url)
+            .body(hyper::Body::empty())
+            .unwrap();
+        let response = pinned_client(vec!["127.0.0.1".parse().unwrap()])
+            .request(request)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 204);
+
+        let request = stand_in.join().unwrap().to_ascii_lowercase();
+        assert!(
+            request.contains("\r\nhost: rebinding.invalid:"),
+            "{}",
+            request
+        );
+    }
+
+    #[tokio::test]
+    async fn refuse_redirects() {
+        let internal = TcpListener::bind("127.0.0.1:0").unwrap();
+        internal.set_nonblocking(true).unwrap();
+        let (url, stand_in) = stand_in_with(
+            302,
+            &format!("Location: http://{}/\r\n", internal.local_addr().unwrap()),
+        );
+        let (webhook, delivery) = delivery(&url);
+
+        let status = post(
+            &webhook,
+            &delivery,
+            Duration::from_secs(5),
+            &["127.0.0.1".to_owned()],
+        )
+        .await;
+        assert_eq!(status, Ok(302));
+        stand_in.join().unwrap();
+
+        assert_eq!(
+            internal.accept().unwrap_err().kind(),
+            std::io::ErrorKind::WouldBlock
+        );
+    }
+
+    #[tokio::test]
+    async fn check_destinations() {
+        for url in vec![
+            "http://127.0.0.1/",
+            "http://localhost:8080/",
+            "http://10.1.2.3/",
+            "http://172.16.0.1/",
+            "http://192.168.1.1/",
+            "http://169.254.169.254/latest/meta-data",
+            "http://100.64.0.1/",
+            "http://0.0.0.0/",
+            "http://[::1]/",
+            "http://[fd00::1]/",
+            "http://[fe80::1]/",
+            "http://[::ffff:10.0.0.1]/",
+        ] {
+            assert!(check_destination(url, &[]).await.is_err(), "{}", url);
+        }
+
+        for (url, ip) in vec![
+            ("http://93.184.216.34/", "93.184.216.34"),
+            ("https://[2606:2800:220:1::]/", "2606:2800:220:1::"),
+        ] {
+            assert_eq!(
+                check_destination(url, &[]).await,
+                Ok(vec![ip.parse().unwrap()]),
+                "{}",
+                url
+            );
+        }
+
+        let allowed_hosts = vec!["LOCALHOST".to_owned(), "10.1.2.3".to_owned()];
+        assert!(check_destination("http://localhost:8080/", &allowed_hosts)
+            .await
+            .is_ok());
+        assert_eq!(
+            check_destination("https://10.1.2.3/", &allowed_hosts).await,
+            Ok(vec!["10.1.2.3".parse().unwrap()])
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audit::{AuditEvent, AuditEventCursor, AuditEventFilter},
+    auth::AccessToken,
+    paper::{Paper, PaperCursor, PaperFilter, PaperId, PaperOrderField},
+    user::{Role, User, UserCursor, UserFilter, UserId},
+    OrderBy, Pagination, PaginationList, Result,
+};
+
+/// Operations for the operators of the service, there is no viewer and no
+/// permission check, callers are trusted.
+#[async_trait]
+pub trait AdminService: Send + Sync {
+    async fn select_user_page(
+        &self,
+        pagination: Pagination<UserCursor>,
+        filter: UserFilter,
+    ) -> Result<PaginationList<User>>;
+
+    async fn rename_user(&self, user_id: UserId, name: String) -> Result<User>;
+
+    async fn set_user_disabled(&self, user_id: UserId, disabled: bool) -> Result<User>;
+
+    async fn set_user_role(&self, user_id: UserId, role: Role) -> Result<User>;
+
+    /// Make the users listed as admins by the config admins, returns how many
+    /// have been promoted.
+    async fn bootstrap_admins(&self) -> Result<u64>;
+
+    async fn select_statistics(&self) -> Result<Statistics>;
+
+    /// The audit events matching `filter`, newest first.
+    async fn select_audit_event_page(
+        &self,
+        pagination: Pagination<AuditEventCursor>,
+        filter: AuditEventFilter,
+    ) -> Result<PaginationList<AuditEvent>>;
+
+    /// Remove the deleted users whose grace period ended before `purge_before`
+    /// and their papers for good, returns how many users have been removed.
+    async fn purge_deleted_users(&self, purge_before: u64) -> Result<u64>;
+
+    async fn select_paper_page(
+        &self,
+        pagination: Pagination<PaperCursor>,
+        order_by: OrderBy<PaperOrderField>,
+        filter: AdminPaperFilter,
+    ) -> Result<PaginationList<Paper>>;
+
+    /// Undo the deletion of a paper.
+    async fn restore_paper(&self, paper_id: PaperId) -> Result<Paper>;
+
+    /// Remove a deleted paper for good.
+    async fn purge_paper(&self, paper_id: PaperId) -> Result<()>;
+
+    /// Remove the papers deleted before `deleted_before` for good, returns how
+    /// many have been removed.
+    async fn purge_deleted_papers(&self, deleted_before: u64) -> Result<u64>;
+
+    /// An access token of any user, `expires_in_sec` defaults to the lifetime
+    /// of the config. Its creation is audited.
+    async fn mint_access_token(
+        &self,
+        user_id: UserId,
+        expires_in_sec: Option<u64>,
+    ) -> Result<AccessToken>;
+
+    /// A short lived access token of `user_id` for the support work of
+    /// `actor_id`, the token names the actor and its creation is audited.
+    async fn impersonate_user(
+        &self,
+        actor_id: UserId,
+        user_id: UserId,
+        reason: String,
+    ) -> Result<AccessToken>;
+
+    /// Bring the storage up to date, returns what has been applied.
+    async fn migrate(&self) -> Result<Vec<String>>;
+}
+
+/// The papers of every user, or of `user_id` only.
+#[derive(Debug, Default, Clone)]
+pub struct AdminPaperFilter {
+    pub user_id: Option<UserId>,
+
+    pub paper: PaperFilter,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Statistics {
+    pub users: u64,
+
+    pub disabled_users: u64,
+
+    pub admins: u64,
+
+    pub papers: u64,
+
+    pub deleted_papers: u64,
+}
@@ -0,0 +1,118 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{blob::BlobStream, paper::PaperId, user::UserId, Id, Result};
+
+/// Files of a paper. They are created by the writers of the paper, then their
+/// content is uploaded, and they are downloaded by its readers. Their sizes
+/// count towards the storage quota of the owner of the paper.
+#[async_trait]
+pub trait AttachmentService: Send + Sync {
+    /// Reserve `input.size` bytes of the quota for an attachment waiting for
+    /// its content.
+    async fn create_attachment(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        paper_id: PaperId,
+        input: CreateAttachmentInput,
+    ) -> Result<Attachment>;
+
+    /// Store the content of a pending attachment, it must be of the size it
+    /// has been created with.
+    async fn upload_attachment(
+        &self,
+        viewer_id: UserId,
+        attachment_id: AttachmentId,
+        content: Vec<u8>,
+    ) -> Result<Attachment>;
+
+    async fn delete_attachment(
+        &self,
+        viewer_id: UserId,
+        attachment_id: AttachmentId,
+    ) -> Result<Attachment>;
+
+    async fn select_attachment(
+        &self,
+        viewer_id: UserId,
+        attachment_id: AttachmentId,
+    ) -> Result<Attachment>;
+
+    /// The attachments of the paper, oldest first.
+    async fn select_attachments(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        paper_id: PaperId,
+    ) -> Result<Vec<Attachment>>;
+
+    /// The content of a ready attachment, read from the blob store as it is
+    /// sent.
+    async fn select_attachment_content(
+        &self,
+        viewer_id: UserId,
+        attachment_id: AttachmentId,
+    ) -> Result<(Attachment, BlobStream)>;
+
+    /// Bytes of the attachments of the papers of the user, pending ones
+    /// included.
+    async fn select_storage_usage(&self, viewer_id: UserId, user_id: UserId) -> Result<u64>;
+
+    /// Remove the attachments whose content has not been uploaded in time,
+    /// returns how many have been removed.
+    async fn purge_abandoned_attachments(&self) -> Result<u64>;
+}
+
+pub type AttachmentId = Id<Attachment>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentStatus {
+    /// Waiting for its content.
+    Pending,
+
+    Ready,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateAttachmentInput {
+    pub name: String,
+
+    pub content_type: String,
+
+    /// Bytes of the content.
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: AttachmentId,
+
+    /// The owner of the paper.
+    pub user_id: UserId,
+
+    pub paper_id: PaperId,
+
+    pub uploader_id: UserId,
+
+    /// The file name.
+    pub name: String,
+
+    pub content_type: String,
+
+    pub size: u64,
+
+    pub status: AttachmentStatus,
+
+    pub created_at: u64,
+
+    pub uploaded_at: Option<u64>,
+}
+
+impl Attachment {
+    /// The key of the content in the blob store.
+    pub fn blob_key(&self) -> String {
+        format!("attachments/{}/{}", self.user_id, self.id)
+    }
+}
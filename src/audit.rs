@@ -0,0 +1,155 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{paper::PaperId, user::UserId, Id, Pagination, PaginationList, Result};
+
+/// An append-only record of who did what, events are never changed once
+/// recorded and are removed after the retention period only.
+#[async_trait]
+pub trait AuditService: Send + Sync {
+    async fn record(&self, input: AuditEventInput) -> Result<AuditEvent>;
+
+    /// The events of which the user is the actor or the target, newest first.
+    async fn select_audit_event_page(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        pagination: Pagination<AuditEventCursor>,
+    ) -> Result<PaginationList<AuditEvent>>;
+
+    /// Remove the events older than the retention period, returns how many
+    /// have been removed.
+    async fn purge_expired_events(&self) -> Result<u64>;
+}
+
+pub type AuditEventId = Id<AuditEvent>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditEventKind {
+    /// Signed in with the provider of `details`, signing up included.
+    SignIn,
+
+    TokenRefresh,
+
+    /// A sign in or a token refresh has been refused, `details` tells why.
+    AuthFailure,
+
+    UserRename,
+
+    UserDisable,
+
+    UserEnable,
+
+    UserRoleChange,
+
+    /// An admin got an access token of the user, `details` tells why and
+    /// when it expires.
+    Impersonation,
+
+    /// An operator minted an access token of the user, `details` tells when
+    /// it expires.
+    TokenMint,
+
+    AccountDelete,
+
+    AccountRestore,
+
+    DataExport,
+
+    PaperCreate,
+
+    PaperUpdate,
+
+    PaperDelete,
+
+    PaperRestore,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    pub id: AuditEventId,
+
+    pub created_at: u64,
+
+    pub kind: AuditEventKind,
+
+    /// Who did it, `None` for anonymous requests and operators.
+    pub actor_id: Option<UserId>,
+
+    /// The admin impersonating the actor.
+    pub impersonator_id: Option<UserId>,
+
+    /// The user it has been done to.
+    pub user_id: Option<UserId>,
+
+    /// The paper it has been done to.
+    pub paper_id: Option<PaperId>,
+
+    pub ip: Option<String>,
+
+    pub user_agent: Option<String>,
+
+    pub details: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditEventInput {
+    pub kind: AuditEventKind,
+
+    pub actor_id: Option<UserId>,
+
+    pub impersonator_id: Option<UserId>,
+
+    pub user_id: Option<UserId>,
+
+    pub paper_id: Option<PaperId>,
+
+    pub ip: Option<String>,
+
+    pub user_agent: Option<String>,
+
+    pub details: Option<serde_json::Value>,
+}
+
+impl AuditEventInput {
+    pub fn new(kind: AuditEventKind) -> Self {
+        Self {
+            kind,
+            actor_id: None,
+            impersonator_id: None,
+            user_id: None,
+            paper_id: None,
+            ip: None,
+            user_agent: None,
+            details: None,
+        }
+    }
+}
+
+/// Events of `kind`, by `actor_id`, or done to `user_id` or `paper_id`, the
+/// conditions which are set are combined.
+#[derive(Debug, Default, Clone)]
+pub struct AuditEventFilter {
+    pub kind: Option<AuditEventKind>,
+
+    pub actor_id: Option<UserId>,
+
+    pub user_id: Option<UserId>,
+
+    pub paper_id: Option<PaperId>,
+}
+
+/// Position of an event in a page ordered by id, ids grow with time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEventCursor {
+    pub id: AuditEventId,
+}
+
+impl From<&AuditEvent> for AuditEventCursor {
+    fn from(v: &AuditEvent) -> Self {
+        Self {
+            id: v.id.to_owned(),
+        }
+    }
+}
@@ -7,7 +7,15 @@ use super::user::{User, UserId};
 
 #[async_trait]
 pub trait AuthService: Send + Sync {
+    /// Disabled and deleted users are refused, as well as refresh tokens
+    /// issued before the sessions of the user have been revoked.
     async fn create_access_token(&self, input: CreateAccessTokenInput) -> Result<AccessToken>;
+
+    /// Delete the account of the viewer, it can be restored with the restore
+    /// token until it is purged.
+    async fn delete_account(&self, viewer_id: UserId) -> Result<AccountDeletion>;
+
+    async fn restore_account(&self, restore_token: String) -> Result<User>;
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +41,13 @@ pub struct AccessToken {
     pub refresh_token: String,
 }
 
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccountDeletion {
+    pub user: User,
+
+    pub restore_token: String,
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AccessTokenPayload {
     pub iat: u64,
@@ -40,10 +55,16 @@ pub struct AccessTokenPayload {
     pub exp: u64,
 
     pub sub: UserId,
+
+    /// The administrator acting as `sub` when impersonating them.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub act: Option<UserId>,
 }
 
 pub type RefreshTokenPayload = AccessTokenPayload;
 
+pub type RestoreTokenPayload = AccessTokenPayload;
+
 impl AccessToken {
     pub fn new(
         user: User,
@@ -60,6 +81,21 @@ impl AccessToken {
             refresh_token: refresh_token.encode(refresh_token_config.1),
         }
     }
+
+    /// An access token of `user` for `actor_id`, it can not be refreshed.
+    pub fn impersonation(user: User, actor_id: UserId, access_token_config: (u64, &[u8])) -> Self {
+        let access_token = AccessTokenPayload {
+            act: Some(actor_id),
+            ..AccessTokenPayload::new(user.id, access_token_config.0)
+        };
+
+        Self {
+            access_token: access_token.encode(access_token_config.1),
+            token_type: "Bearer".to_owned(),
+            expires_in: access_token_config.0,
+            refresh_token: String::new(),
+        }
+    }
 }
 
 impl AccessTokenPayload {
@@ -73,6 +109,7 @@ impl AccessTokenPayload {
             iat: now_sec,
             exp: now_sec + expires_in_sec,
             sub: user_id,
+            act: None,
         }
     }
 
@@ -93,4 +130,21 @@ impl AccessTokenPayload {
         )
         .map(|x| x.claims)
     }
+
+    /// Decode a token whether it has expired or not, its signature is still
+    /// checked. For telling whom a token has been issued to.
+    pub fn decode_expired(
+        token: &str,
+        secret: impl AsRef<[u8]>,
+    ) -> jsonwebtoken::errors::Result<Self> {
+        jsonwebtoken::decode::<Self>(
+            token,
+            &jsonwebtoken::DecodingKey::from_secret(secret.as_ref()),
+            &jsonwebtoken::Validation {
+                validate_exp: false,
+                ..Default::default()
+            },
+        )
+        .map(|x| x.claims)
+    }
 }
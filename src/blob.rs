@@ -0,0 +1,32 @@
+use std::pin::Pin;
+
+use async_trait::async_trait;
+use futures::Stream;
+
+use crate::Result;
+
+/// The content of a blob in chunks, in order.
+pub type BlobStream = Pin<Box<dyn Stream<Item = Result<Vec<u8>>> + Send>>;
+
+/// Where the content of attachments and data exports is kept, keys are paths
+/// of `/` separated segments.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Store `content` at `key`, replacing what was there.
+    async fn put(&self, key: &str, content: Vec<u8>, content_type: &str) -> Result<()>;
+
+    /// Store the chunks of `content` at `key` as they come, for contents not
+    /// to be held in memory. Nothing is stored when `content` yields an error,
+    /// which is returned.
+    async fn put_stream(&self, key: &str, content: BlobStream, content_type: &str) -> Result<()>;
+
+    /// The content at `key`, not found when there is none.
+    async fn get(&self, key: &str) -> Result<Vec<u8>>;
+
+    /// The content at `key` as it is read, for contents not to be held in
+    /// memory. Not found when there is none.
+    async fn get_stream(&self, key: &str) -> Result<BlobStream>;
+
+    /// Remove the content at `key`, removing a missing key is not an error.
+    async fn delete(&self, key: &str) -> Result<()>;
+}
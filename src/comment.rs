@@ -0,0 +1,122 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{paper::PaperId, user::UserId, Id, Pagination, PaginationList, Result};
+
+/// Comments are read and added by the viewers who can read their paper. Their
+/// author edits them, the author and the writers of the paper delete them and
+/// resolve their threads.
+#[async_trait]
+pub trait CommentService: Send + Sync {
+    /// Add a comment to the paper, a reply to `input.parent_id` joins the
+    /// thread of its parent.
+    async fn create_comment(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        paper_id: PaperId,
+        input: CreateCommentInput,
+    ) -> Result<Comment>;
+
+    async fn update_comment(
+        &self,
+        viewer_id: UserId,
+        comment_id: CommentId,
+        body: String,
+    ) -> Result<Comment>;
+
+    /// Remove the body of the comment, it stays in its thread as deleted.
+    async fn delete_comment(&self, viewer_id: UserId, comment_id: CommentId) -> Result<Comment>;
+
+    /// Resolve or reopen the thread of the comment, its root is returned.
+    async fn resolve_thread(
+        &self,
+        viewer_id: UserId,
+        comment_id: CommentId,
+        resolved: bool,
+    ) -> Result<Comment>;
+
+    async fn select_comment(&self, viewer_id: UserId, comment_id: CommentId) -> Result<Comment>;
+
+    /// The threads of the paper, oldest first.
+    async fn select_thread_page(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        paper_id: PaperId,
+        pagination: Pagination<CommentCursor>,
+        filter: CommentFilter,
+    ) -> Result<PaginationList<Comment>>;
+
+    /// The replies of a thread, oldest first.
+    async fn select_replies(&self, viewer_id: UserId, thread_id: CommentId)
+        -> Result<Vec<Comment>>;
+}
+
+pub type CommentId = Id<Comment>;
+
+/// A range of the content of the paper, in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommentAnchor {
+    pub start: u64,
+
+    pub end: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateCommentInput {
+    pub body: String,
+
+    pub anchor: Option<CommentAnchor>,
+
+    pub parent_id: Option<CommentId>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Comment {
+    pub id: CommentId,
+
+    /// The owner of the paper.
+    pub user_id: UserId,
+
+    pub paper_id: PaperId,
+
+    pub author_id: UserId,
+
+    /// The root comment of the thread, `None` for the root itself.
+    pub thread_id: Option<CommentId>,
+
+    pub body: String,
+
+    pub anchor: Option<CommentAnchor>,
+
+    pub created_at: u64,
+
+    pub updated_at: u64,
+
+    pub deleted_at: Option<u64>,
+
+    /// Set on the root of a resolved thread.
+    pub resolved_at: Option<u64>,
+
+    pub resolved_by: Option<UserId>,
+}
+
+impl Comment {
+    /// The id of the root comment of the thread.
+    pub fn thread_id(&self) -> &CommentId {
+        self.thread_id.as_ref().unwrap_or(&self.id)
+    }
+}
+
+/// Resolved or open threads, all of them when `resolved` is not set.
+#[derive(Debug, Default, Clone)]
+pub struct CommentFilter {
+    pub resolved: Option<bool>,
+}
+
+/// Position of a thread in a page ordered by id, ids grow with time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommentCursor {
+    pub id: CommentId,
+}
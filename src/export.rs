@@ -0,0 +1,58 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{blob::BlobStream, user::UserId, Id, Result};
+
+#[async_trait]
+pub trait ExportService: Send + Sync {
+    /// Queue an export of everything held about the user, the pending export
+    /// of the user is returned instead when there is one.
+    async fn request_export(&self, viewer_id: UserId, user_id: UserId) -> Result<DataExport>;
+
+    /// Assemble the archive of a pending export, it is run in the background.
+    /// An export already being assembled is not found.
+    async fn build_export(&self, export_id: ExportId) -> Result<DataExport>;
+
+    /// The exports of the user which have not expired, newest first.
+    async fn select_exports(&self, viewer_id: UserId, user_id: UserId) -> Result<Vec<DataExport>>;
+
+    /// The zip archive of a ready export, read from the blob store as it is
+    /// sent. There is no viewer, callers hold a signed download URL.
+    async fn select_export_archive(&self, export_id: ExportId) -> Result<BlobStream>;
+}
+
+pub type ExportId = Id<DataExport>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportStatus {
+    Pending,
+
+    Ready,
+
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataExport {
+    pub id: ExportId,
+
+    pub user_id: UserId,
+
+    pub status: ExportStatus,
+
+    pub created_at: u64,
+
+    /// The export and its archive are removed after it.
+    pub expires_at: u64,
+
+    /// Why the archive could not be assembled.
+    pub error: Option<String>,
+}
+
+impl DataExport {
+    /// The key of the archive in the blob store.
+    pub fn blob_key(&self) -> String {
+        format!("exports/{}/{}.zip", self.user_id, self.id)
+    }
+}
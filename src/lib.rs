@@ -1,14 +1,25 @@
+pub mod admin;
+pub mod attachment;
+pub mod audit;
 pub mod auth;
+pub mod blob;
+pub mod comment;
+pub mod export;
+pub mod notification;
 pub mod paper;
+pub mod quota;
 pub mod user;
+pub mod webhook;
 
 pub use id::Id;
 pub use pagination::{OrderBy, OrderDirection, Pagination, PaginationList};
 pub use result::{Error, ErrorKind, Result};
+pub use time_range::TimeRange;
 
 mod id {
     use std::{
         fmt::{self, Display, Formatter},
+        hash::{Hash, Hasher},
         marker::PhantomData,
     };
 
@@ -23,6 +34,12 @@ mod id {
         }
     }
 
+    impl<T> Hash for Id<T> {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            self.0.hash(state)
+        }
+    }
+
     impl<T> Serialize for Id<T> {
         fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
         where
@@ -58,6 +75,9 @@ mod id {
 }
 
 mod pagination {
+    /// Cursor based pagination, `skip` counts from the start of the list for
+    /// `After` and from its end for `Before`, it can not be combined with a
+    /// cursor.
     pub enum Pagination<T> {
         After {
             after: Option<T>,
@@ -75,6 +95,7 @@ mod pagination {
         pub list: Vec<T>,
         pub total: u64,
         pub has_next_page: bool,
+        pub has_previous_page: bool,
     }
 
     pub enum OrderDirection {
@@ -90,6 +111,23 @@ mod pagination {
     }
 }
 
+mod time_range {
+    /// Milliseconds since the unix epoch, `from` is inclusive and `to` is
+    /// exclusive, an absent bound is unbounded.
+    #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+    pub struct TimeRange {
+        pub from: Option<u64>,
+
+        pub to: Option<u64>,
+    }
+
+    impl TimeRange {
+        pub fn is_unbounded(&self) -> bool {
+            self.from.is_none() && self.to.is_none()
+        }
+    }
+}
+
 mod result {
     pub type Result<T> = std::result::Result<T, Error>;
 
@@ -99,6 +137,8 @@ mod result {
         Unaurhorized,
         Forbidden,
         NotFound,
+        QuotaExceeded,
+        InvalidInput,
         Unknown,
     }
 
@@ -144,6 +184,20 @@ mod result {
             }
         }
 
+        pub fn quota_exceeded<T: Into<Option<String>>>(message: T) -> Self {
+            Self {
+                kind: ErrorKind::QuotaExceeded,
+                message: message.into(),
+            }
+        }
+
+        pub fn invalid_input<T: Into<Option<String>>>(message: T) -> Self {
+            Self {
+                kind: ErrorKind::InvalidInput,
+                message: message.into(),
+            }
+        }
+
         pub fn unknown<T: Into<Option<String>>>(message: T) -> Self {
             Self {
                 kind: ErrorKind::Unknown,
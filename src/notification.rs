@@ -0,0 +1,108 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    comment::CommentId, paper::PaperId, user::UserId, Id, Pagination, PaginationList, Result,
+};
+
+/// The inbox of a user, only the user reads it and marks it as read.
+#[async_trait]
+pub trait NotificationService: Send + Sync {
+    /// Store the notifications and publish them to the subscribers of their
+    /// recipients.
+    async fn notify(&self, inputs: Vec<NotificationInput>) -> Result<Vec<Notification>>;
+
+    /// The notifications of the user, newest first.
+    async fn select_notification_page(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        pagination: Pagination<NotificationCursor>,
+        filter: NotificationFilter,
+    ) -> Result<PaginationList<Notification>>;
+
+    async fn count_unread_notifications(&self, viewer_id: UserId, user_id: UserId) -> Result<u64>;
+
+    /// Mark the notifications of the user as read, all of them when
+    /// `notification_ids` is `None`. Returns how many have been marked.
+    async fn mark_notifications_read(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        notification_ids: Option<Vec<NotificationId>>,
+    ) -> Result<u64>;
+
+    /// Remove the notifications older than the retention period, returns how
+    /// many have been removed.
+    async fn purge_expired_notifications(&self) -> Result<u64>;
+}
+
+pub type NotificationId = Id<Notification>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    /// The recipient is mentioned by `@name` in a comment.
+    Mention,
+
+    /// A comment has been added to a thread the recipient started.
+    Reply,
+
+    /// A paper has been shared with the recipient. Nothing raises it until
+    /// papers can be shared.
+    PaperShared,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Notification {
+    pub id: NotificationId,
+
+    /// The recipient.
+    pub user_id: UserId,
+
+    pub kind: NotificationKind,
+
+    /// Who caused it.
+    pub actor_id: Option<UserId>,
+
+    pub paper_id: Option<PaperId>,
+
+    pub comment_id: Option<CommentId>,
+
+    pub created_at: u64,
+
+    pub read_at: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NotificationInput {
+    pub user_id: UserId,
+
+    pub kind: NotificationKind,
+
+    pub actor_id: Option<UserId>,
+
+    pub paper_id: Option<PaperId>,
+
+    pub comment_id: Option<CommentId>,
+}
+
+/// Read or unread notifications, all of them when `unread` is not set.
+#[derive(Debug, Default, Clone)]
+pub struct NotificationFilter {
+    pub unread: Option<bool>,
+}
+
+/// Position of a notification in a page ordered by id, ids grow with time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NotificationCursor {
+    pub id: NotificationId,
+}
+
+impl From<&Notification> for NotificationCursor {
+    fn from(v: &Notification) -> Self {
+        Self {
+            id: v.id.to_owned(),
+        }
+    }
+}
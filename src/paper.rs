@@ -1,12 +1,22 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
-use crate::{user::UserId, Id, OrderBy, Pagination, PaginationList, Result};
+use crate::{user::UserId, Id, OrderBy, Pagination, PaginationList, Result, TimeRange};
 
 #[async_trait]
 pub trait PaperService: Send + Sync {
     async fn create_paper(&self, viewer_id: UserId, user_id: UserId) -> Result<Paper>;
 
+    /// Change the fields of `input` which are set, the paper must not be
+    /// deleted.
+    async fn update_paper(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        paper_id: PaperId,
+        input: UpdatePaperInput,
+    ) -> Result<Paper>;
+
     async fn delete_paper(
         &self,
         viewer_id: UserId,
@@ -21,13 +31,18 @@ pub trait PaperService: Send + Sync {
         paper_id: PaperId,
     ) -> Result<Paper>;
 
+    /// Select papers by id with a single query, papers the viewer is not
+    /// allowed to read or that do not exist are skipped.
+    async fn select_papers(&self, viewer_id: UserId, paper_ids: Vec<PaperId>)
+        -> Result<Vec<Paper>>;
+
     async fn select_paper_page_of_repository(
         &self,
         viewer_id: UserId,
         user_id: UserId,
-        pagination: Pagination<PaperId>,
+        pagination: Pagination<PaperCursor>,
         order_by: OrderBy<PaperOrderField>,
-        deleted: bool,
+        filter: PaperFilter,
     ) -> Result<PaginationList<Paper>>;
 
     async fn can_viewer_read_paper(
@@ -45,14 +60,69 @@ pub trait PaperService: Send + Sync {
     ) -> Result<Paper>;
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaperEventKind {
+    Created,
+
+    Updated,
+
+    Deleted,
+}
+
+/// Published after a paper has been changed by `PaperService`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaperEvent {
+    pub kind: PaperEventKind,
+
+    pub paper: Paper,
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct UpdatePaperInput {
+    pub title: Option<String>,
+
+    pub tags: Option<Vec<String>>,
+}
+
 pub enum PaperOrderField {
     Id,
 
     UpdatedAt,
 }
 
+/// Either the deleted or the not deleted papers, narrowed by the time ranges.
+#[derive(Debug, Default, Clone)]
+pub struct PaperFilter {
+    pub deleted: bool,
+
+    pub created_at: TimeRange,
+
+    pub updated_at: TimeRange,
+
+    pub deleted_at: TimeRange,
+}
+
 pub type PaperId = Id<Paper>;
 
+/// Position of a paper in a page, it carries the sort keys so a page can be
+/// continued even after the paper has been updated or deleted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaperCursor {
+    pub id: PaperId,
+
+    pub updated_at: Option<u64>,
+}
+
+impl From<&Paper> for PaperCursor {
+    fn from(v: &Paper) -> Self {
+        Self {
+            id: v.id.to_owned(),
+            updated_at: Some(v.updated_at),
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Paper {
     pub id: PaperId,
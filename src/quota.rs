@@ -0,0 +1,87 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{user::UserId, Result};
+
+/// Limits of what a user stores and does, set by the plan of the user with
+/// their overrides. Going over one fails with `QuotaExceeded`.
+#[async_trait]
+pub trait QuotaService: Send + Sync {
+    /// The plan of the user, its limits and how much of them is used.
+    async fn select_usage(&self, viewer_id: UserId, user_id: UserId) -> Result<Usage>;
+
+    /// The plan of the user and its limits, overrides applied.
+    fn select_limits(&self, user_id: &UserId) -> (String, QuotaLimits);
+
+    /// Count one more paper of the user, fails when their plan allows no
+    /// more. The count is atomic, concurrent creations can not go over.
+    async fn reserve_paper(&self, user_id: &UserId) -> Result<()>;
+
+    /// Uncount a paper of the user, once deleted or when its creation failed.
+    async fn release_paper(&self, user_id: &UserId) -> Result<()>;
+
+    /// Count `size` more bytes of attachments of the user, fails when they do
+    /// not fit in the storage of their plan.
+    async fn reserve_attachment_storage(&self, user_id: &UserId, size: u64) -> Result<()>;
+
+    /// Uncount `size` bytes of attachments of the user.
+    async fn release_attachment_storage(&self, user_id: &UserId, size: u64) -> Result<()>;
+
+    /// Count a request of the user to the API, fails once the user is over
+    /// their daily limit. Returns the requests of the day so far.
+    async fn count_api_request(&self, user_id: &UserId) -> Result<u64>;
+}
+
+/// 0 is unlimited.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuotaLimits {
+    /// Papers not deleted.
+    pub max_papers: u64,
+
+    /// Bytes of the attachments of the papers of the user.
+    pub max_attachment_storage: u64,
+
+    /// Requests to the API per UTC day.
+    pub max_api_requests_per_day: u64,
+}
+
+/// Limits replacing those of the plan for a single user, and the plan
+/// itself when `plan` is set.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuotaOverride {
+    pub plan: Option<String>,
+
+    pub max_papers: Option<u64>,
+
+    pub max_attachment_storage: Option<u64>,
+
+    pub max_api_requests_per_day: Option<u64>,
+}
+
+impl QuotaOverride {
+    pub fn apply(&self, limits: &QuotaLimits) -> QuotaLimits {
+        QuotaLimits {
+            max_papers: self.max_papers.unwrap_or(limits.max_papers),
+            max_attachment_storage: self
+                .max_attachment_storage
+                .unwrap_or(limits.max_attachment_storage),
+            max_api_requests_per_day: self
+                .max_api_requests_per_day
+                .unwrap_or(limits.max_api_requests_per_day),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Usage {
+    pub plan: String,
+
+    pub limits: QuotaLimits,
+
+    pub papers: u64,
+
+    pub attachment_storage: u64,
+
+    /// Requests to the API since the start of the UTC day.
+    pub api_requests_today: u64,
+}
@@ -1,14 +1,21 @@
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 
-use crate::{Id, Result};
+use crate::{Error, Id, Result};
 
 #[async_trait]
 pub trait UserService: Send + Sync {
     async fn create_user(&self, user: CreateUserInput) -> Result<User>;
 
+    /// Deleted users are not found.
     async fn select_user(&self, identifier: UserIdentifier) -> Result<User>;
 
+    /// The user whatever the state of their account, to sign them in.
+    async fn select_account(&self, identifier: UserIdentifier) -> Result<User>;
+
+    /// Select users by id with a single query, missing users are skipped.
+    async fn select_users(&self, user_ids: Vec<UserId>) -> Result<Vec<User>>;
+
     async fn update_user(
         &self,
         viewer_id: UserId,
@@ -21,6 +28,16 @@ pub trait UserService: Send + Sync {
     async fn can_viewer_write_user(&self, viewer_id: UserId, user_id: UserId) -> Result<User>;
 
     async fn can_viewer_administer_user(&self, viewer_id: UserId, user_id: UserId) -> Result<User>;
+
+    /// The viewer, if their role is `role` or above.
+    async fn can_viewer_act_as(&self, viewer_id: UserId, role: Role) -> Result<User>;
+
+    /// Soft delete the user and revoke their sessions, the user and their
+    /// papers are purged once the grace period is over.
+    async fn delete_user(&self, viewer_id: UserId, user_id: UserId) -> Result<User>;
+
+    /// Undo the deletion of the user during the grace period.
+    async fn restore_user(&self, user_id: UserId) -> Result<User>;
 }
 
 pub type UserId = Id<User>;
@@ -44,6 +61,29 @@ pub enum CreateUserInput {
     Google { google_user: serde_json::Value },
 }
 
+/// Users whose name contains `search` or whose id is `search`, and whose
+/// disabled state is `disabled` when set.
+#[derive(Debug, Default, Clone)]
+pub struct UserFilter {
+    pub search: Option<String>,
+
+    pub disabled: Option<bool>,
+}
+
+/// Position of a user in a page ordered by id.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserCursor {
+    pub id: UserId,
+}
+
+impl From<&User> for UserCursor {
+    fn from(v: &User) -> Self {
+        Self {
+            id: v.id.to_owned(),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UpdateUserInput {
     pub name: Option<String>,
@@ -60,4 +100,78 @@ pub struct User {
     pub github_user: Option<serde_json::Value>,
 
     pub google_user: Option<serde_json::Value>,
+
+    #[serde(default)]
+    pub role: Role,
+
+    /// Set by an operator, the account can not be used.
+    #[serde(default)]
+    pub disabled: bool,
+
+    /// Set when the user deleted their account.
+    #[serde(default)]
+    pub deleted_at: Option<u64>,
+
+    /// When the deleted account is removed for good.
+    #[serde(default)]
+    pub purge_at: Option<u64>,
+
+    /// The tokens issued before are no longer accepted.
+    #[serde(default)]
+    pub sessions_revoked_at: Option<u64>,
+}
+
+impl User {
+    /// Whether the account can be signed in, with a token issued at
+    /// `issued_at_sec` when given.
+    pub fn can_sign_in(&self, issued_at_sec: Option<u64>) -> Result<()> {
+        if self.disabled {
+            return Err(Error::forbidden("This account is disabled".to_owned()));
+        }
+        if self.deleted_at.is_some() {
+            return Err(Error::unauthorized("This account is deleted".to_owned()));
+        }
+        if let (Some(issued_at_sec), Some(revoked_at)) = (issued_at_sec, self.sessions_revoked_at) {
+            if issued_at_sec * 1000 <= revoked_at {
+                return Err(Error::unauthorized("The session is revoked".to_owned()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserEventKind {
+    Updated,
+}
+
+/// Published after a user has been changed by `UserService` or
+/// `AdminService`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserEvent {
+    pub kind: UserEventKind,
+
+    pub user: User,
+}
+
+/// Site wide role of a user, each role has the permissions of the roles
+/// before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Member,
+
+    /// Reads the site administration: the users, the audit log and the
+    /// statistics.
+    Moderator,
+
+    /// Administers every user.
+    Admin,
+}
+
+impl Default for Role {
+    fn default() -> Self {
+        Self::Member
+    }
 }
@@ -0,0 +1,154 @@
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::{user::UserId, Id, Pagination, PaginationList, Result};
+
+#[async_trait]
+pub trait WebhookService: Send + Sync {
+    /// Register a webhook of the user, its deliveries are signed with
+    /// `input.secret`.
+    async fn create_webhook(
+        &self,
+        viewer_id: UserId,
+        user_id: UserId,
+        input: CreateWebhookInput,
+    ) -> Result<Webhook>;
+
+    /// Remove a webhook and its delivery log.
+    async fn delete_webhook(&self, viewer_id: UserId, webhook_id: WebhookId) -> Result<Webhook>;
+
+    /// The webhooks of the user, oldest first.
+    async fn select_webhooks(&self, viewer_id: UserId, user_id: UserId) -> Result<Vec<Webhook>>;
+
+    /// The delivery log of a webhook, newest first.
+    async fn select_delivery_page(
+        &self,
+        viewer_id: UserId,
+        webhook_id: WebhookId,
+        pagination: Pagination<WebhookDeliveryCursor>,
+    ) -> Result<PaginationList<WebhookDelivery>>;
+
+    /// Attempt the queued deliveries which are due, returns the number of
+    /// deliveries attempted.
+    async fn deliver_due(&self) -> Result<u64>;
+
+    /// Remove the deliveries past their retention period.
+    async fn purge_expired_deliveries(&self) -> Result<u64>;
+}
+
+pub type WebhookId = Id<Webhook>;
+
+pub type WebhookDeliveryId = Id<WebhookDelivery>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum WebhookEventKind {
+    #[serde(rename = "paper.created")]
+    PaperCreated,
+
+    #[serde(rename = "paper.updated")]
+    PaperUpdated,
+
+    #[serde(rename = "paper.deleted")]
+    PaperDeleted,
+
+    #[serde(rename = "user.updated")]
+    UserUpdated,
+}
+
+impl WebhookEventKind {
+    /// The name the webhooks subscribe to, e.g. `paper.created`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            WebhookEventKind::PaperCreated => "paper.created",
+            WebhookEventKind::PaperUpdated => "paper.updated",
+            WebhookEventKind::PaperDeleted => "paper.deleted",
+            WebhookEventKind::UserUpdated => "user.updated",
+        }
+    }
+}
+
+/// A change of the user or one of their papers, `data` is the changed object
+/// as it is posted to the webhooks.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WebhookEvent {
+    pub kind: WebhookEventKind,
+
+    pub user_id: UserId,
+
+    pub data: serde_json::Value,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CreateWebhookInput {
+    /// An http or https URL the events are posted to.
+    pub url: String,
+
+    pub events: Vec<WebhookEventKind>,
+
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: WebhookId,
+
+    pub user_id: UserId,
+
+    pub url: String,
+
+    pub events: Vec<WebhookEventKind>,
+
+    /// Key of the HMAC-SHA256 signature of the deliveries.
+    pub secret: String,
+
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookDeliveryStatus {
+    /// Queued, or waiting to be retried.
+    Pending,
+
+    Succeeded,
+
+    /// Every attempt has failed.
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: WebhookDeliveryId,
+
+    pub webhook_id: WebhookId,
+
+    pub user_id: UserId,
+
+    pub event: WebhookEventKind,
+
+    /// The JSON body which is posted and signed.
+    pub payload: String,
+
+    pub status: WebhookDeliveryStatus,
+
+    pub attempts: u32,
+
+    pub created_at: u64,
+
+    /// When a pending delivery is attempted next.
+    pub next_attempt_at: Option<u64>,
+
+    pub last_attempt_at: Option<u64>,
+
+    /// HTTP status of the response to the last attempt.
+    pub response_status: Option<u16>,
+
+    /// Why the last attempt has failed.
+    pub error: Option<String>,
+}
+
+/// Position of a delivery in a page ordered by id, ids grow with time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WebhookDeliveryCursor {
+    pub id: WebhookDeliveryId,
+}